@@ -0,0 +1,16 @@
+//! Records what `tpdf --version --verbose` reports about the build: the
+//! target triple and the enabled cargo features.
+
+fn main() {
+    let target = std::env::var("TARGET").unwrap_or_default();
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| {
+            let feature = key.strip_prefix("CARGO_FEATURE_")?;
+            Some(feature.to_ascii_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=TPDF_TARGET={target}");
+    println!("cargo:rustc-env=TPDF_FEATURES={}", features.join(","));
+    println!("cargo:rerun-if-changed=build.rs");
+}
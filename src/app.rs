@@ -1,27 +1,112 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, stdout};
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event, KeyEventKind};
+use chrono::Timelike;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
 use crossterm::execute;
 use crossterm::terminal::{BeginSynchronizedUpdate, EndSynchronizedUpdate};
 use image::DynamicImage;
 use ratatui::layout::Rect;
-use ratatui::DefaultTerminal;
+use ratatui::{DefaultTerminal, TerminalOptions, Viewport};
 use ratatui_image::picker::Picker;
+use ratatui_image::{FilterType, Image as RatatuiImage};
 
-use crate::cache::PageCache;
+use crate::cache::{pan_offset, refocus_pan, same_scale, PageCache};
+use crate::clipboard::{Clipboard, ImageCopy};
+use crate::command;
+use crate::config::Keymap;
+use crate::dark::Paper;
+use crate::disk_cache::{DiskCache, RenderKey};
+use crate::document::{self, DocumentSource, OpenError};
 use crate::input;
-use crate::pdf::PdfDocument;
+use crate::ocr::{self, OCR_SCALE};
+use crate::pace::ReadingPace;
+use crate::pdf::{
+    self, DocumentInfo, FieldInfo, LinkInfo, LinkTarget, NoteInfo, PageLabels, Region, Source,
+};
+use crate::queue;
+use crate::session::{self, Bookmark, SessionState};
+use crate::text::{self, Row};
 use crate::view;
+use crate::watch::FileWatcher;
 
 pub struct AppConfig {
-    pub dark_mode: bool,
+    /// Explicit color mode; falls back to the saved session, then
+    /// `default_color_mode`.
+    pub color_mode: Option<ColorMode>,
+    /// Color mode of documents opened for the first time.
+    pub default_color_mode: ColorMode,
     pub fullscreen: bool,
-    pub start_page: usize,
-    pub layout: PageLayout,
+    /// Explicit start page; falls back to the saved session, then page 0.
+    pub start_page: Option<usize>,
+    /// Named destination to start at, ahead of `start_page`.
+    pub dest: Option<String>,
+    /// How far down the start page to open, from 0.0 (top) to 1.0.
+    pub scroll: Option<f32>,
+    /// Explicit fit mode; falls back to the saved session, then
+    /// `default_fit`.
+    pub fit: Option<FitMode>,
+    pub default_fit: FitMode,
+    /// Explicit layout; falls back to the saved session, then
+    /// `default_layout`.
+    pub layout: Option<PageLayout>,
+    pub default_layout: PageLayout,
+    /// Show the first page alone in dual layout, like a book's cover.
+    pub cover: bool,
+    pub paper: Paper,
+    pub quality: Quality,
+    pub show_status_bar: bool,
+    pub status_segments: StatusSegments,
+    /// Draw the status bar and background without colors or bold, for
+    /// `NO_COLOR`.
+    pub no_color: bool,
+    /// Read the text of pages without any with tesseract.
+    pub ocr: bool,
+    /// Pages rendered ahead of and behind the visible ones.
+    pub preload: usize,
+    pub render_threads: usize,
+    /// Size of the on-disk render cache in bytes, if it is on.
+    pub disk_cache: Option<u64>,
+    /// Fixed render resolution, instead of fitting renders to the terminal.
+    pub dpi: Option<u32>,
+    /// Multiplier on the fitted render scale, for terminals that report
+    /// cells too small for the screen's pixels.
+    pub scale_factor: f32,
+    pub password: Option<String>,
+    pub resume: bool,
+    /// What the session is saved under instead of the file's path, like
+    /// the URL of a download whose file only lasts the run.
+    pub session_key: Option<String>,
+    /// Memory budget for rendered pages, in bytes.
+    pub cache_budget: usize,
+    /// Reload the document when the file changes on disk.
+    pub watch: bool,
+    /// Presentation: start advancing pages on a timer, with Space pausing.
+    pub present: bool,
+    pub advance_interval: Duration,
+    /// Go back to the first page after the last when advancing on a timer.
+    pub loop_pages: bool,
+    /// Turning past the last page goes to the first, and back from the
+    /// first to the last.
+    pub wrap_navigation: bool,
+    /// `g` and `z` start two-key motions like `gg` and `zz`.
+    pub vim_motions: bool,
+    /// Lines moved per scroll step in text mode.
+    pub text_scroll: usize,
+    /// Start with lines drawn between pages.
+    pub separators: bool,
+    /// Columns between pages side by side while separators are shown.
+    pub separator_width: u16,
+    /// Color of the separator lines, instead of dark gray.
+    pub separator_color: Option<[u8; 3]>,
+    pub keymap: Keymap,
+    /// Shown in the status bar on opening, e.g. that pages fell back to
+    /// half-blocks.
+    pub notice: Option<String>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -29,27 +114,342 @@ pub enum PageLayout {
     Single,
     Dual,
     Triple,
+    /// Pages stacked vertically and scrolled smoothly across page boundaries.
+    Continuous,
+    /// Rows of pages, filled left to right.
+    Grid {
+        cols: u8,
+        rows: u8,
+    },
 }
 
+/// Largest grid side, past which pages are too small to make out.
+pub const MAX_GRID: u8 = 8;
+
 impl PageLayout {
+    #[must_use]
     pub const fn pages_across(self) -> usize {
         match self {
-            Self::Single => 1,
+            Self::Single | Self::Continuous => 1,
             Self::Dual => 2,
             Self::Triple => 3,
+            Self::Grid { cols, .. } => cols as usize,
+        }
+    }
+
+    #[must_use]
+    pub const fn rows(self) -> usize {
+        match self {
+            Self::Grid { rows, .. } => rows as usize,
+            _ => 1,
         }
     }
 
+    /// Pages on screen at once (continuous mode aside).
+    #[must_use]
+    pub const fn pages_shown(self) -> usize {
+        self.pages_across() * self.rows()
+    }
+
+    #[must_use]
     pub const fn cycle(self) -> Self {
         match self {
             Self::Single => Self::Dual,
             Self::Dual => Self::Triple,
-            Self::Triple => Self::Single,
+            Self::Triple => Self::Continuous,
+            Self::Continuous | Self::Grid { .. } => Self::Single,
+        }
+    }
+
+    /// A layout by name: `single`, `dual`, `triple` (or 1-3), `continuous`
+    /// or a grid like `3x2`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "single" | "1" => Some(Self::Single),
+            "dual" | "2" => Some(Self::Dual),
+            "triple" | "3" => Some(Self::Triple),
+            "continuous" => Some(Self::Continuous),
+            _ => Self::parse_grid(name),
+        }
+    }
+
+    /// Parse a grid size like `3x2` (columns by rows).
+    #[must_use]
+    pub fn parse_grid(s: &str) -> Option<Self> {
+        let (cols, rows) = s.split_once('x')?;
+        let (cols, rows) = (cols.parse().ok()?, rows.parse().ok()?);
+        let valid = 1..=MAX_GRID;
+        (valid.contains(&cols) && valid.contains(&rows)).then_some(Self::Grid { cols, rows })
+    }
+}
+
+/// Part of every page to show, for reading one column or half at a time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Lens {
+    Full,
+    /// Left of the gutter on two-column pages; the whole of other pages.
+    LeftColumn,
+    RightColumn,
+    TopHalf,
+    BottomHalf,
+}
+
+impl Lens {
+    #[must_use]
+    pub const fn cycle(self) -> Self {
+        match self {
+            Self::Full => Self::LeftColumn,
+            Self::LeftColumn => Self::RightColumn,
+            Self::RightColumn => Self::TopHalf,
+            Self::TopHalf => Self::BottomHalf,
+            Self::BottomHalf => Self::Full,
+        }
+    }
+
+    /// Whether the region depends on where the page's columns divide.
+    #[must_use]
+    pub const fn by_column(self) -> bool {
+        matches!(self, Self::LeftColumn | Self::RightColumn)
+    }
+
+    /// Status bar label; `None` for the whole page.
+    #[must_use]
+    pub const fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Full => None,
+            Self::LeftColumn => Some("LEFT COL"),
+            Self::RightColumn => Some("RIGHT COL"),
+            Self::TopHalf => Some("TOP"),
+            Self::BottomHalf => Some("BOTTOM"),
+        }
+    }
+}
+
+/// How carefully pages are rendered and scaled to the terminal, trading
+/// sharpness for speed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    /// No anti-aliasing and nearest-neighbour scaling.
+    Fast,
+    Balanced,
+    High,
+}
+
+impl Quality {
+    #[must_use]
+    pub const fn cycle(self) -> Self {
+        match self {
+            Self::Fast => Self::Balanced,
+            Self::Balanced => Self::High,
+            Self::High => Self::Fast,
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fast" => Some(Self::Fast),
+            "balanced" => Some(Self::Balanced),
+            "high" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Fast => "fast",
+            Self::Balanced => "balanced",
+            Self::High => "high",
+        }
+    }
+
+    /// Whether mupdf anti-aliases text and line art.
+    #[must_use]
+    pub const fn antialias(self) -> bool {
+        !matches!(self, Self::Fast)
+    }
+
+    /// Filter used to scale renders to the size of the page on screen.
+    #[must_use]
+    pub const fn filter(self) -> FilterType {
+        match self {
+            Self::Fast => FilterType::Nearest,
+            Self::Balanced => FilterType::Triangle,
+            Self::High => FilterType::CatmullRom,
+        }
+    }
+}
+
+/// The parts of the status bar that are shown. Prompts, flashed messages
+/// and the optional extras (cache status, clock) always are.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct StatusSegments {
+    /// The page indicator, e.g. `3/120`.
+    pub page: bool,
+    /// Zoom and fit mode.
+    pub zoom: bool,
+    pub layout: bool,
+    /// Everything else about the view: text mode, color mode, rotation,
+    /// crop, quality and the like.
+    pub mode: bool,
+    /// The key hints on the right.
+    pub keys: bool,
+}
+
+impl StatusSegments {
+    pub const FULL: Self = Self {
+        page: true,
+        zoom: true,
+        layout: true,
+        mode: true,
+        keys: true,
+    };
+
+    /// Nothing but `page/total`.
+    pub const MINIMAL: Self = Self {
+        page: true,
+        zoom: false,
+        layout: false,
+        mode: false,
+        keys: false,
+    };
+
+    pub const NONE: Self = Self {
+        page: false,
+        ..Self::MINIMAL
+    };
+
+    /// Turn on the segment called `name`; `false` if there is none.
+    pub fn enable(&mut self, name: &str) -> bool {
+        let segment = match name {
+            "page" => &mut self.page,
+            "zoom" => &mut self.zoom,
+            "layout" => &mut self.layout,
+            "mode" => &mut self.mode,
+            "keys" => &mut self.keys,
+            _ => return false,
+        };
+        *segment = true;
+        true
+    }
+}
+
+/// Which page dimension is scaled to fill the view at zoom 1.0.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FitMode {
+    /// Whole page visible.
+    Page,
+    /// Page width fills the view; pan vertically to reach the rest.
+    Width,
+    /// Page height fills the view; pan horizontally to reach the rest.
+    Height,
+}
+
+impl FitMode {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Page => "page",
+            Self::Width => "width",
+            Self::Height => "height",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "page" => Some(Self::Page),
+            "width" => Some(Self::Width),
+            "height" => Some(Self::Height),
+            _ => None,
+        }
+    }
+}
+
+/// How page colors are changed for reading, applied after rendering.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorMode {
+    Normal,
+    Night,
+    /// Night mode that leaves colorful pixels alone, so photos and
+    /// diagrams stay recognizable.
+    SmartNight,
+    /// Only the paper goes dark and everything printed on it is kept as
+    /// is, for colorful diagrams that inverting makes unreadable.
+    DarkPaper,
+    Grayscale,
+    Sepia,
+}
+
+impl ColorMode {
+    #[must_use]
+    pub const fn cycle(self) -> Self {
+        match self {
+            Self::Normal => Self::Night,
+            Self::Night => Self::SmartNight,
+            Self::SmartNight => Self::DarkPaper,
+            Self::DarkPaper => Self::Grayscale,
+            Self::Grayscale => Self::Sepia,
+            Self::Sepia => Self::Normal,
+        }
+    }
+
+    /// Whether pages come out light on dark.
+    #[must_use]
+    pub const fn is_dark(self) -> bool {
+        matches!(self, Self::Night | Self::SmartNight | Self::DarkPaper)
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Night => "night",
+            Self::SmartNight => "smart-night",
+            Self::DarkPaper => "dark-paper",
+            Self::Grayscale => "grayscale",
+            Self::Sepia => "sepia",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "normal" => Some(Self::Normal),
+            "night" => Some(Self::Night),
+            "smart-night" => Some(Self::SmartNight),
+            "dark-paper" => Some(Self::DarkPaper),
+            "grayscale" => Some(Self::Grayscale),
+            "sepia" => Some(Self::Sepia),
+            _ => None,
+        }
+    }
+
+    /// Status bar label; `None` for the default mode.
+    #[must_use]
+    pub const fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Normal => None,
+            Self::Night => Some("NIGHT"),
+            Self::SmartNight => Some("NIGHT+"),
+            Self::DarkPaper => Some("DARK BG"),
+            Self::Grayscale => Some("GRAY"),
+            Self::Sepia => Some("SEPIA"),
         }
     }
 }
 
+#[derive(Clone)]
 pub enum Message {
+    /// A digit of a count prefix for the next motion.
+    CountDigit(u8),
+    ClearCount,
+    /// The first key of a two-key motion such as `gg`, with `vim_motions`.
+    StartMotion(char),
+    NextTab,
+    PrevTab,
     Quit,
     NextPage,
     PrevPage,
@@ -58,29 +458,195 @@ pub enum Message {
     ZoomIn,
     ZoomOut,
     ZoomReset,
+    FitWidth,
+    FitHeight,
     ScrollUp,
     ScrollDown,
     ScrollLeft,
     ScrollRight,
+    /// Scroll to the top of the page.
+    PanTop,
+    /// Scroll to the bottom of the page.
+    PanBottom,
     CycleLayout,
     ToggleDarkMode,
+    CycleColorMode,
+    BrightnessUp,
+    BrightnessDown,
+    ContrastUp,
+    ContrastDown,
+    ResetAdjustments,
+    ToggleAutoCrop,
+    /// Show the next preset part of each page: a column, a half or all.
+    CycleLens,
     ToggleFullscreen,
+    /// Hide the status bar, except for prompts and messages.
+    ToggleStatusBar,
+    ToggleTextMode,
+    /// Show the page's extracted text beside the rendered page.
+    ToggleSplitText,
+    /// Scroll the extracted text, in text mode or beside the page.
+    TextScrollUp,
+    TextScrollDown,
+    /// Text mode: scroll down a screenful, or turn the page at its end.
+    TextPageDown,
+    ToggleLockView,
+    /// Keep pages at their current size on screen, or zoom relative to
+    /// the fit again.
+    TogglePinnedZoom,
+    ToggleCacheStatus,
+    /// Show render and cache statistics.
+    ToggleDebug,
+    /// Show the time and the estimated reading time left in the status bar.
+    ToggleClock,
+    /// Try rendering the failed pages on screen again.
+    RetryRender,
+    ToggleCoverOffset,
+    ToggleCompare,
+    /// Darken the pages beside the current one in multi-page layouts.
+    ToggleDimInactive,
+    /// Show where the view is on the page while zoomed in.
+    ToggleMinimap,
+    /// Draw lines between pages in multi-page and continuous layouts.
+    ToggleSeparators,
+    /// Start or pause turning pages on a timer.
+    ToggleAutoAdvance,
+    CycleQuality,
+    /// Move the focus to the other compare pane.
+    SwitchPane,
+    ToggleFields,
+    /// Show or hide annotations, for a clean view of the page.
+    ToggleAnnotations,
+    ToggleReflow,
+    /// Text mode: style headings, or show plain text.
+    ToggleTextStyles,
+    RotateCW,
+    RotateCCW,
+    ShowInfo,
+    ToggleThumbnails,
+    ThumbnailLeft,
+    ThumbnailRight,
+    ThumbnailUp,
+    ThumbnailDown,
+    ThumbnailSelect,
+    SetBookmark,
+    EnterBookmarkLabel,
+    BookmarkLabel(char),
+    ListBookmarks,
+    BookmarkUp,
+    BookmarkDown,
+    BookmarkSelect,
+    BookmarkDelete,
+    CloseBookmarks,
+    HistoryBack,
+    HistoryForward,
+    HideInfo,
     EnterGoto,
-    GotoInput(char),
-    GotoBackspace,
+    PromptInput(char),
+    PromptBackspace,
     GotoConfirm,
-    GotoCancel,
+    PromptCancel,
+    EnterCommand,
+    CommandComplete,
+    CommandConfirm,
+    /// Jump to a 0-based page, with an error if it doesn't exist.
+    GotoPage(usize),
+    SetRotation(u16),
+    SetLayout(PageLayout),
+    /// Save pages `.0..=.1` (0-based) as PNG files.
+    ExportPages(usize, usize),
+    PasswordInput(char),
+    PasswordBackspace,
+    PasswordConfirm,
+    CopyText,
+    /// Copy the page as an image.
+    CopyImage,
+    /// Copy a reference to the page, like `report.pdf#page=42`.
+    CopyLink,
+    /// Start or cancel selecting a rectangle of the page to copy.
+    ToggleSelection,
+    /// Move the selection by this many cells across and down.
+    MoveSelection(i16, i16),
+    /// Grow or shrink the selection by this many cells.
+    ResizeSelection(i16, i16),
+    /// Copy the text within the selection.
+    CopySelection,
+    NextLink,
+    PrevLink,
+    FollowLink,
+    WheelUp,
+    WheelDown,
+    Click(u16, u16),
+}
+
+/// Compare mode: two pages side by side, each with its own position and
+/// zoom. The focused pane uses the usual `App` fields; this holds the other.
+pub struct Compare {
+    pub(crate) other: PaneView,
+    pub(crate) focus_left: bool,
+    /// Layout to return to when compare mode ends.
+    layout: PageLayout,
+}
+
+/// Where a page was last drawn: its screen area and the part of the page
+/// shown there (`window` of the `content` box).
+pub struct PageArea {
+    pub(crate) page: usize,
+    pub(crate) area: Rect,
+    pub(crate) content: Region,
+    pub(crate) window: Region,
+}
+
+/// A rectangle of screen cells over a page, for copying the text under it.
+pub struct Selection {
+    pub(crate) page: usize,
+    pub(crate) rect: Rect,
+}
+
+/// Where the unfocused compare pane is looking.
+pub struct PaneView {
+    pub(crate) page: usize,
+    zoom: f32,
+    pan_x: f32,
+    pan_y: f32,
+}
+
+/// A position to return to with `HistoryBack`/`HistoryForward`.
+struct HistoryEntry {
+    page: usize,
+    text_scroll: usize,
 }
 
 struct RenderRequest {
+    /// The `render_epoch` it was asked for in; thumbnails never go stale.
+    epoch: u64,
     idx: usize,
     scale: f32,
+    rotation: u16,
+    antialias: bool,
+    annotations: bool,
+    thumbnail: bool,
+    /// Scale of a quick render to send first, for a page with nothing to
+    /// show yet.
+    preview: Option<f32>,
+}
+
+/// The text tesseract read off a page, or why it couldn't.
+struct OcrResult {
+    idx: usize,
+    text: Result<String, String>,
 }
 
 struct RenderResult {
     idx: usize,
     scale: f32,
-    img: DynamicImage,
+    rotation: u16,
+    antialias: bool,
+    annotations: bool,
+    thumbnail: bool,
+    /// A stand-in sent ahead of the render that was asked for.
+    preview: bool,
+    img: Result<DynamicImage, String>,
 }
 
 #[allow(clippy::struct_excessive_bools)]
@@ -89,133 +655,684 @@ pub struct App {
     pub(crate) picker: Picker,
     pub(crate) current_page: usize,
     pub(crate) page_count: usize,
+    /// Page numbers as printed, when the document defines them.
+    pub(crate) page_labels: PageLabels,
     pub(crate) zoom: f32,
+    pub(crate) fit: FitMode,
     pub(crate) pan_x: f32,
     pub(crate) pan_y: f32,
+    /// Continuous mode: how far the view has scrolled past the top of
+    /// `current_page`, as a fraction of one page plus its gap.
+    pub(crate) scroll_offset: f32,
     pub(crate) layout: PageLayout,
-    pub(crate) dark_mode: bool,
+    /// Dual layout pairs pages as book spreads: the cover alone, then 2-3,
+    /// 4-5, ...
+    pub(crate) cover_offset: bool,
+    pub(crate) compare: Option<Compare>,
+    pub(crate) color_mode: ColorMode,
+    pub(crate) paper: Paper,
+    pub(crate) quality: Quality,
+    /// Clockwise rotation applied to every page: 0, 90, 180 or 270.
+    pub(crate) rotation: u16,
+    pub(crate) brightness: i32,
+    pub(crate) contrast: f32,
+    pub(crate) autocrop: bool,
+    pub(crate) lens: Lens,
     pub(crate) fullscreen: bool,
+    /// Hidden, the bar only comes back over the bottom row for prompts and
+    /// messages; fullscreen hides it altogether.
+    pub(crate) show_status_bar: bool,
+    pub(crate) status_segments: StatusSegments,
+    /// Plain text for the status bar and around the pages; the pages keep
+    /// their colors.
+    pub(crate) no_color: bool,
+    /// Recognize the text of pages that have none to extract; turned off
+    /// again if tesseract fails.
+    ocr: bool,
+    /// The thread reading pages with tesseract, started when the first page
+    /// needs it.
+    ocr_worker: Option<(mpsc::Sender<usize>, Receiver<OcrResult>)>,
+    /// Pages sent to the OCR worker and not read yet.
+    ocr_pending: HashSet<usize>,
+    /// Whether the document seems to be scanned pages without text, once
+    /// checked.
+    scanned: Option<bool>,
+    /// Direction of the last pan, when it was and how many came before it
+    /// in quick succession, to speed up panning while a key is held.
+    pan_streak: Option<((i8, i8), Instant, i32)>,
+    /// Draw form field values over the pages.
+    pub(crate) show_fields: bool,
+    /// Render annotations and draw the text of notes over the pages.
+    pub(crate) show_annotations: bool,
+    /// Darken every page on screen but the current one.
+    pub(crate) dim_inactive: bool,
+    /// Outline the page in a corner with the part on screen marked, while
+    /// zoomed in.
+    pub(crate) show_minimap: bool,
+    /// Draw lines between pages, to tell white pages apart on a white
+    /// background.
+    pub(crate) show_separators: bool,
+    pub(crate) separator_width: u16,
+    pub(crate) separator_color: Option<[u8; 3]>,
+    /// Show the current page's extracted text instead of the rendered image.
+    pub(crate) text_mode: bool,
+    /// Show the rendered pages on the left and the extracted text of the
+    /// current one on the right.
+    pub(crate) split_text: bool,
+    /// The page the split view's text scroll belongs to.
+    split_text_page: usize,
+    /// Text mode: join lines into paragraphs rather than keeping the
+    /// original line breaks.
+    pub(crate) text_reflow: bool,
+    /// Text mode: show headings in bold and color, rather than all the
+    /// text alike.
+    pub(crate) text_styles: bool,
+    /// Text mode and the split view: first wrapped line shown.
+    pub(crate) text_scroll: usize,
+    /// Text mode: columns scrolled past on the left, for long unwrapped
+    /// lines.
+    pub(crate) text_hscroll: usize,
+    /// Where text mode was left on each page (`text_scroll`,
+    /// `text_hscroll`), picked up again on switching back there.
+    text_positions: HashMap<usize, (usize, usize)>,
+    /// Lines moved per scroll step in text mode.
+    text_scroll_step: usize,
+    /// Width and height text was last drawn in, to tell when scrolling
+    /// has reached the end of the page.
+    pub(crate) text_pane: (u16, u16),
     pub(crate) goto_mode: bool,
-    pub(crate) goto_input: String,
+    /// Pages whose last render failed, with the error. They aren't
+    /// requested again until retried.
+    pub(crate) failed_pages: HashMap<usize, String>,
+    /// Show how many pages are rendered in the status bar.
+    pub(crate) show_cache_status: bool,
+    /// Show render and cache statistics, for tuning and bug reports.
+    pub(crate) show_debug: bool,
+    /// Frames drawn since the document was opened.
+    pub(crate) frames_drawn: u64,
+    pub(crate) show_clock: bool,
+    /// When the clock in the status bar next changes.
+    clock_tick: Option<Instant>,
+    /// Step of the spinner on pages still loading.
+    pub(crate) spinner_frame: usize,
+    /// When the spinner next steps, while pages are loading.
+    spinner_tick: Option<Instant>,
+    /// When the terminal counts as done resizing, so pages are rendered for
+    /// the final size only.
+    resize_settles: Option<Instant>,
+    pub(crate) pace: ReadingPace,
+    /// When the next page turn is due, while advancing on a timer.
+    pub(crate) auto_advance_at: Option<Instant>,
+    pub(crate) advance_interval: Duration,
+    loop_pages: bool,
+    wrap_navigation: bool,
+    vim_motions: bool,
+    /// The first key of a motion, and when to give up waiting for the
+    /// second and take it alone.
+    pub(crate) pending_motion: Option<(char, Instant)>,
+    /// Space pauses and resumes the timer instead of turning the page, and
+    /// quitting takes a second press.
+    pub(crate) present: bool,
+    /// When quit was first pressed in present mode.
+    quit_armed: Option<Instant>,
+    /// Pages rendered ahead of and behind the visible ones.
+    preload: usize,
+    render_threads: usize,
+    /// Keep renders on disk between runs, up to this many bytes.
+    disk_cache: Option<u64>,
+    /// Renders (and exports) at this resolution rather than one derived
+    /// from the terminal's font size; pages are still fitted on screen.
+    dpi: Option<u32>,
+    /// Renders are this many times larger than fits the view, and scaled
+    /// down on screen.
+    scale_factor: f32,
+    /// Keep the zoomed-in region when changing pages instead of recentering.
+    pub(crate) lock_view: bool,
+    /// Absolute zoom: the screen pixels per point pages are kept at through
+    /// layout and terminal size changes, rather than a multiple of the fit.
+    pub(crate) pinned_zoom: Option<f32>,
+    /// Typing a `:` command.
+    pub(crate) command_mode: bool,
+    /// What has been typed at the goto or command prompt.
+    pub(crate) prompt_input: String,
+    pub(crate) password_mode: bool,
+    /// Named destination to jump to once the document is unlocked.
+    locked_dest: Option<String>,
+    pub(crate) status_message: Option<String>,
+    /// Screen areas of the links on the visible pages, rebuilt every draw.
+    pub(crate) link_hits: Vec<(Rect, LinkTarget)>,
+    pub(crate) selected_link: Option<usize>,
+    /// Where each visible page was drawn, rebuilt every draw.
+    pub(crate) page_areas: Vec<PageArea>,
+    pub(crate) selection: Option<Selection>,
+    /// Showing the thumbnail grid instead of the document.
+    pub(crate) thumbnails: bool,
+    pub(crate) thumbnail_selected: usize,
+    /// First grid row on screen.
+    pub(crate) thumbnail_scroll: usize,
+    /// The user's bookmarks, sorted by page.
+    pub(crate) bookmarks: Vec<Bookmark>,
+    /// Waiting for the label of a new bookmark.
+    pub(crate) bookmark_label_mode: bool,
+    /// Selected row while the bookmark list is open.
+    pub(crate) bookmark_list: Option<usize>,
+    /// Document metadata, while the info overlay is open.
+    pub(crate) info: Option<DocumentInfo>,
+    pub(crate) file_size: Option<u64>,
+    /// Count typed ahead of a motion, e.g. the 5 of `5l`.
+    pub(crate) pending_count: Option<usize>,
+    /// Names of all open documents, shown as tabs when there is more than one.
+    pub(crate) tab_names: Vec<String>,
+    /// This document's position among the tabs.
+    pub(crate) tab_index: usize,
+    /// Where the status bar's page indicator was last drawn, for clicks.
+    pub(crate) page_indicator: Option<Rect>,
+    status_expires: Option<Instant>,
+    back_stack: Vec<HistoryEntry>,
+    forward_stack: Vec<HistoryEntry>,
+    clipboard: Clipboard,
+    source: Source,
+    pdf: Box<dyn DocumentSource>,
+    watcher: Option<FileWatcher>,
+    password_input: String,
+    resume: bool,
+    session_key: Option<String>,
+    keymap: Keymap,
     term_cols: u16,
     term_rows: u16,
+    /// First page's size, standing in for pages not measured yet.
     page_bounds: (f32, f32),
-    render_tx: Sender<RenderRequest>,
+    /// Sizes of the pages measured so far, before the view rotation.
+    page_sizes: HashMap<usize, (f32, f32)>,
+    render_tx: queue::Sender<RenderRequest>,
     render_rx: Receiver<RenderResult>,
-    pending: HashSet<usize>,
+    /// Bumped whenever the pages on their way are no longer wanted, so the
+    /// workers skip them rather than render them for nothing.
+    render_epoch: Arc<AtomicU64>,
+    /// Pages being rendered, with the scale last asked for.
+    pending: HashMap<usize, f32>,
+    pending_thumbnails: HashSet<usize>,
+    tab_switch: Option<TabSwitch>,
     should_quit: bool,
 }
 
+/// Why [`App::run`] handed control back before quitting.
+#[derive(Clone, Copy)]
+enum TabSwitch {
+    Next,
+    Prev,
+}
+
 const PAN_STEP: f32 = 0.15;
+/// Pans in the same direction this close together come from a held key,
+/// and each grows the step by `PAN_ACCELERATION`, up to `MAX_PAN_STEP`.
+/// The first repeat comes after the keyboard's repeat delay, so a held key
+/// starts slow for precision.
+const PAN_REPEAT_WINDOW: Duration = Duration::from_millis(150);
+const PAN_ACCELERATION: f32 = 1.15;
+const MAX_PAN_STEP: f32 = 0.45;
 const ZOOM_STEP: f32 = 0.10;
+const CONTINUOUS_STEP: f32 = 0.10;
+/// Lines moved per scroll step in text mode, unless configured.
+pub const DEFAULT_TEXT_SCROLL: usize = 3;
+/// Largest configurable text scroll step.
+pub const MAX_TEXT_SCROLL: usize = 100;
+/// Columns between pages side by side with separators, unless configured,
+/// and the most that can be.
+pub const DEFAULT_SEPARATOR_WIDTH: u16 = 1;
+pub const MAX_SEPARATOR_WIDTH: u16 = 4;
+/// Columns moved per sideways scroll step in text mode.
+const TEXT_HSCROLL_STEP: usize = 8;
+const BRIGHTNESS_STEP: i32 = 10;
+/// Beyond this the page washes out to flat white or black.
+const BRIGHTNESS_LIMIT: i32 = 120;
+const CONTRAST_STEP: f32 = 10.0;
+/// -100% contrast is a uniform gray, so stop well short of it.
+const CONTRAST_LIMIT: f32 = 60.0;
+/// Auto-cropped pages are scaled up to fill the freed margins; render them a
+/// little larger so that stays sharp (margins are typically 10-20% a side).
+const AUTOCROP_OVERSAMPLE: f32 = 1.25;
+/// Half a page shown through the lens can fill up to twice the space.
+const LENS_OVERSAMPLE: f32 = 2.0;
+/// Size of the quick first render of a page there is nothing to show for
+/// yet, relative to the full one: a sixteenth of the pixels, so it arrives
+/// almost at once.
+const PREVIEW_FRACTION: f32 = 0.25;
+/// Width in cells of a page thumbnail in the overview grid.
+const THUMBNAIL_WIDTH: u16 = 16;
+/// Labels handed out, in order, to bookmarks set without one.
+const BOOKMARK_SLOTS: &str = "123456789abcdefghijklmnopqrstuvwxyz";
+/// Longest side of a page render in pixels. A large page at high zoom would
+/// otherwise take gigabytes (8000px square is already 192 MB of RGB).
+const MAX_RENDER_PX: f32 = 8000.0;
+/// Render scale for exported pages (144 dpi) unless `--dpi` is given.
+pub const EXPORT_SCALE: f32 = 2.0;
+pub const DEFAULT_PRELOAD: usize = 5;
+/// Every preloaded page is a full-size render held in memory, so keep the
+/// radius (and the thread count) within reason.
+pub const MAX_PRELOAD: usize = 50;
+pub const MAX_RENDER_THREADS: usize = 16;
+/// Counts go up to any page number a document could have, for `123456G`.
+const COUNT_LIMIT: usize = 9_999_999;
+/// Motions are repeated at most this many times, so a stray run of digits
+/// can't stall the loop.
+const REPEAT_LIMIT: usize = 9999;
+/// Positions kept in each direction of the navigation history.
+const HISTORY_LIMIT: usize = 100;
+/// Extra attempts at reopening a changed file before giving up, in case it
+/// was still being written when the watcher settled.
+const RELOAD_RETRIES: usize = 2;
+const RELOAD_RETRY_DELAY: Duration = Duration::from_millis(200);
+const FLASH_DURATION: Duration = Duration::from_millis(1500);
+/// How often the loading spinner steps.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+/// How long `g` or `z` waits for the second key of a motion, as in Vim.
+const MOTION_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long a first press of quit waits for the second in present mode.
+const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(1);
+/// How long the terminal size has to hold still before pages are rendered
+/// for it.
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(120);
+/// How often the view looks for pages the OCR worker has read.
+const OCR_POLL: Duration = Duration::from_millis(100);
+/// Pages checked for text to tell a scanned document from a digital one.
+const SCAN_SAMPLES: usize = 5;
+const SCANNED_HINT: &str =
+    "This looks like a scanned document; text mode is unavailable (try --ocr)";
+/// Blank rows between pages in continuous mode.
+pub const CONTINUOUS_GAP: u16 = 1;
+/// Smallest view pages are drawn in; below it a page is a few blurry cells,
+/// and rendering one is wasted work.
+const MIN_VIEW_COLS: u16 = 16;
+const MIN_VIEW_ROWS: u16 = 4;
 
 impl App {
+    #[allow(clippy::too_many_lines)]
     pub fn new(
-        path: &str,
+        source: Source,
         picker: Picker,
         term_cols: u16,
         term_rows: u16,
-        config: &AppConfig,
+        config: AppConfig,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let pdf = PdfDocument::open(path)?;
-        let page_count = pdf.page_count();
-        if page_count == 0 {
-            return Err("PDF has no pages".into());
-        }
-        let page_bounds = pdf.page_bounds(0).unwrap_or((612.0, 792.0));
-        drop(pdf);
-
-        let (req_tx, req_rx) = mpsc::channel::<RenderRequest>();
-        let (res_tx, res_rx) = mpsc::channel::<RenderResult>();
-        let shared_rx = Arc::new(Mutex::new(req_rx));
-
-        let num_threads = std::thread::available_parallelism()
-            .map(|n| n.get().min(4))
-            .unwrap_or(2);
-
-        for _ in 0..num_threads {
-            let rx = Arc::clone(&shared_rx);
-            let tx = res_tx.clone();
-            let p = path.to_string();
-            std::thread::spawn(move || {
-                let pdf = PdfDocument::open(&p).expect("render worker: failed to open PDF");
-                loop {
-                    let req = {
-                        let guard = rx.lock().unwrap();
-                        guard.recv()
-                    };
-                    match req {
-                        Ok(r) => {
-                            if let Ok(img) = pdf.render_page(r.idx, r.scale) {
-                                if tx
-                                    .send(RenderResult {
-                                        idx: r.idx,
-                                        scale: r.scale,
-                                        img,
-                                    })
-                                    .is_err()
-                                {
-                                    break;
-                                }
-                            }
-                        }
-                        Err(_) => break,
-                    }
-                }
-            });
-        }
-        drop(res_tx);
+        let (pdf, locked) = match document::open(&source, config.password.as_deref()) {
+            Ok(pdf) => (pdf, false),
+            Err(OpenError::PasswordRequired(pdf)) => {
+                (Box::new(pdf) as Box<dyn DocumentSource>, true)
+            }
+            Err(OpenError::Mupdf(e)) => return Err(source.open_failure(&e).into()),
+            Err(OpenError::Images(e)) => return Err(e.into()),
+        };
+
+        let render_epoch = Arc::new(AtomicU64::new(0));
+        let (page_count, page_bounds, render_tx, render_rx) = if locked {
+            // Workers are started once the password has been accepted
+            let (req_tx, _) = queue::channel();
+            let (_, res_rx) = mpsc::channel();
+            (0, (612.0, 792.0), req_tx, res_rx)
+        } else {
+            let (page_count, page_bounds) = document_info(pdf.as_ref())?;
+            let (req_tx, res_rx) = spawn_render_workers(
+                &source,
+                config.password.as_deref(),
+                config.render_threads,
+                config.disk_cache,
+                Arc::clone(&render_epoch),
+            );
+            (page_count, page_bounds, req_tx, res_rx)
+        };
+
+        let page_labels = if locked {
+            PageLabels::default()
+        } else {
+            pdf.page_labels().unwrap_or_default()
+        };
+
+        // Bookmarks are restored even with --no-resume; only the position isn't
+        let session_key = config
+            .session_key
+            .clone()
+            .or_else(|| source.path().and_then(session::document_key));
+        let (saved, bookmarks) = match session_key.as_deref().and_then(session::load) {
+            Some(mut s) => {
+                let bookmarks = std::mem::take(&mut s.bookmarks);
+                (config.resume.then_some(s), bookmarks)
+            }
+            None => (None, Vec::new()),
+        };
+        // A locked document's destinations are looked up once it's unlocked
+        let (dest_page, dest_error) = match &config.dest {
+            Some(name) if !locked => pdf.resolve_named_dest(name).map_or_else(
+                || (Some(0), Some(missing_dest(name))),
+                |page| (Some(page.min(page_count.saturating_sub(1))), None),
+            ),
+            _ => (None, None),
+        };
+        let start_page = dest_page
+            .or(config.start_page)
+            .or_else(|| saved.as_ref().map(|s| s.page))
+            .unwrap_or(0);
+        let layout = config
+            .layout
+            .or_else(|| saved.as_ref().map(|s| s.layout))
+            .unwrap_or(config.default_layout);
+        let fit = config
+            .fit
+            .or_else(|| saved.as_ref().map(|s| s.fit))
+            .unwrap_or(config.default_fit);
+        let color_mode = config
+            .color_mode
+            .or_else(|| saved.as_ref().map(|s| s.color_mode))
+            .unwrap_or(config.default_color_mode);
+        let rotation = saved.as_ref().map_or(0, |s| s.rotation);
+        let zoom = saved.map_or(1.0, |s| s.zoom.clamp(0.25, 4.0));
+
+        let watcher = match source.path() {
+            Some(path) if config.watch => Some(FileWatcher::new(path)?),
+            _ => None,
+        };
 
-        let start_page = config.start_page.min(page_count.saturating_sub(1));
+        // A wrong --password falls through to the interactive prompt
+        let status_message = dest_error
+            .or_else(|| {
+                (locked && config.password.is_some()).then(|| "Incorrect password".to_string())
+            })
+            .or_else(|| config.notice.clone());
 
-        Ok(Self {
-            cache: PageCache::new(),
+        let mut cache = PageCache::with_budget(config.cache_budget);
+        cache.set_paper(config.paper);
+        cache.set_filter(config.quality.filter());
+
+        let scroll = config.scroll;
+        let mut app = Self {
+            cache,
             picker,
-            current_page: start_page,
+            // Clamped again once a locked document is unlocked
+            current_page: if locked {
+                start_page
+            } else {
+                start_page.min(page_count.saturating_sub(1))
+            },
             page_count,
-            zoom: 1.0,
+            page_labels,
+            zoom,
+            fit,
             pan_x: 0.0,
-            pan_y: 0.0,
-            layout: config.layout,
-            dark_mode: config.dark_mode,
+            // Fit to width starts reading from the top of the page
+            pan_y: if fit == FitMode::Width { -1.0 } else { 0.0 },
+            scroll_offset: 0.0,
+            layout,
+            cover_offset: config.cover,
+            compare: None,
+            color_mode,
+            paper: config.paper,
+            quality: config.quality,
+            rotation,
+            brightness: 0,
+            contrast: 0.0,
+            autocrop: false,
+            lens: Lens::Full,
             fullscreen: config.fullscreen,
+            show_status_bar: config.show_status_bar,
+            no_color: config.no_color,
+            ocr: config.ocr,
+            ocr_worker: None,
+            ocr_pending: HashSet::new(),
+            scanned: None,
+            pan_streak: None,
+            status_segments: config.status_segments,
+            show_fields: false,
+            show_annotations: true,
+            dim_inactive: false,
+            show_minimap: false,
+            show_separators: config.separators,
+            separator_width: config.separator_width,
+            separator_color: config.separator_color,
+            text_mode: false,
+            split_text: false,
+            split_text_page: 0,
+            text_reflow: true,
+            text_styles: true,
+            text_scroll: 0,
+            text_hscroll: 0,
+            text_positions: HashMap::new(),
+            text_scroll_step: config.text_scroll,
+            text_pane: (0, 0),
             term_cols,
             term_rows,
             goto_mode: false,
-            goto_input: String::new(),
+            command_mode: false,
+            lock_view: false,
+            pinned_zoom: None,
+            auto_advance_at: config
+                .present
+                .then(|| Instant::now() + config.advance_interval),
+            advance_interval: config.advance_interval,
+            loop_pages: config.loop_pages,
+            wrap_navigation: config.wrap_navigation,
+            vim_motions: config.vim_motions,
+            pending_motion: None,
+            present: config.present,
+            quit_armed: None,
+            preload: config.preload,
+            render_threads: config.render_threads,
+            disk_cache: config.disk_cache,
+            dpi: config.dpi,
+            scale_factor: config.scale_factor,
+            show_cache_status: false,
+            show_debug: false,
+            frames_drawn: 0,
+            show_clock: false,
+            clock_tick: None,
+            spinner_frame: 0,
+            spinner_tick: None,
+            resize_settles: None,
+            pace: ReadingPace::new(0),
+            failed_pages: HashMap::new(),
+            prompt_input: String::new(),
+            password_mode: locked,
+            locked_dest: if locked { config.dest } else { None },
+            status_message,
+            status_expires: None,
+            back_stack: Vec::new(),
+            forward_stack: Vec::new(),
+            link_hits: Vec::new(),
+            page_areas: Vec::new(),
+            selection: None,
+            selected_link: None,
+            bookmarks,
+            bookmark_label_mode: false,
+            bookmark_list: None,
+            thumbnails: false,
+            thumbnail_selected: 0,
+            thumbnail_scroll: 0,
+            info: None,
+            file_size: None,
+            pending_count: None,
+            tab_names: Vec::new(),
+            tab_index: 0,
+            page_indicator: None,
+            clipboard: Clipboard::new(),
+            source,
+            pdf,
+            watcher,
+            password_input: String::new(),
+            resume: config.resume,
+            session_key,
+            keymap: config.keymap,
             page_bounds,
-            render_tx: req_tx,
-            render_rx: res_rx,
-            pending: HashSet::new(),
+            page_sizes: HashMap::new(),
+            render_tx,
+            render_rx,
+            render_epoch,
+            pending: HashMap::new(),
+            pending_thumbnails: HashSet::new(),
+            tab_switch: None,
             should_quit: false,
-        })
+        };
+        if let Some(scroll) = scroll {
+            app.scroll_to(scroll);
+        }
+        Ok(app)
+    }
+
+    /// Show the part `fraction` of the way down the current page: scroll
+    /// there in continuous mode, or pan there when the page is taller than
+    /// the view.
+    fn scroll_to(&mut self, fraction: f32) {
+        if self.layout == PageLayout::Continuous {
+            self.scroll_offset = 0.0;
+            self.scroll_continuous(fraction);
+        } else {
+            self.pan_y = fraction.mul_add(2.0, -1.0);
+        }
     }
 
-    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+    /// Show the documents as tabs, one at a time, until the user quits.
+    pub fn run_tabs(tabs: &mut [Self], terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let names: Vec<String> = tabs.iter().map(|tab| tab.source.name()).collect();
+        for (i, tab) in tabs.iter_mut().enumerate() {
+            tab.tab_names.clone_from(&names);
+            tab.tab_index = i;
+        }
+
+        let mut active = 0;
+        while let Some(switch) = tabs[active].run(terminal)? {
+            active = match switch {
+                TabSwitch::Next => (active + 1) % tabs.len(),
+                TabSwitch::Prev => (active + tabs.len() - 1) % tabs.len(),
+            };
+            // Resizes while the tab was in the background went elsewhere
+            let (cols, rows) = crossterm::terminal::size()?;
+            tabs[active].resize(cols, rows);
+            terminal.clear()?;
+        }
+
+        for tab in tabs.iter() {
+            tab.save_session();
+        }
+        Ok(())
+    }
+
+    /// Whether the whole document fits on one screen as it opens: a single
+    /// page, with nothing to scroll or pan to.
+    #[must_use]
+    pub fn fits_one_screen(&self) -> bool {
+        self.page_count == 1
+            && !self.text_mode
+            && !self.can_pan_vertically()
+            && !self.can_pan_horizontally()
+    }
+
+    /// Draw the page below the prompt, as `less -F` prints a short file,
+    /// and leave it in the scrollback rather than in a full-screen view.
+    pub fn print_inline(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let scale = self.render_scale(self.zoom, self.oriented_bounds());
+        let img = self
+            .pdf
+            .render_page(0, scale, self.rotation, self.show_annotations)?;
+        self.cache.insert_image(0, scale, img);
+        let (w, h) = self.cache.image_dims(0).ok_or("the page didn't render")?;
+        let cell = self.cell_area();
+        let font_size = self.picker.font_size();
+        let size = view::aligned_image_area(w, h, cell, font_size, self.zoom, view::HAlign::Left);
+
+        let mut terminal = ratatui::init_with_options(TerminalOptions {
+            viewport: Viewport::Inline(size.height),
+        });
+        let drawn = terminal.draw(|frame| {
+            let area = Rect::new(frame.area().x, frame.area().y, size.width, size.height);
+            let protocol = self.cache.get_protocol(
+                0,
+                self.color_mode,
+                self.zoom,
+                (self.pan_x, self.pan_y),
+                None,
+                false,
+                &self.picker,
+                area,
+            );
+            if let Some(protocol) = protocol {
+                frame.render_widget(RatatuiImage::new(protocol), area);
+            }
+            // Leave the prompt to come back below the page
+            frame.set_cursor_position((0, frame.area().bottom().saturating_sub(1)));
+        });
+        ratatui::restore();
+        drawn?;
+        println!();
+        Ok(())
+    }
+
+    /// Run the event loop until the user quits, returning `None`, or asks
+    /// for another tab.
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<Option<TabSwitch>> {
         self.request_visible_pages();
         let mut dirty = true;
 
-        while !self.should_quit {
+        while !self.should_quit && self.tab_switch.is_none() {
             if self.process_render_results() {
                 dirty = true;
             }
 
+            if self.process_ocr_results() {
+                dirty = true;
+            }
+
+            if self.watcher.as_mut().is_some_and(FileWatcher::poll) && !self.password_mode {
+                self.reload();
+                self.request_visible_pages();
+                dirty = true;
+            }
+
+            if self.auto_advance_at.is_some_and(|t| t <= Instant::now()) {
+                self.auto_advance();
+                self.request_visible_pages();
+                dirty = true;
+            }
+
+            if self.fire_timers() {
+                dirty = true;
+            }
+
             if dirty {
+                self.pace
+                    .note(self.dominant_page(), self.layout.pages_shown());
                 execute!(stdout(), BeginSynchronizedUpdate)?;
                 terminal.draw(|frame| view::draw(frame, self))?;
                 execute!(stdout(), EndSynchronizedUpdate)?;
+                self.frames_drawn += 1;
+                self.clock_tick = self.show_clock.then(next_minute);
                 dirty = false;
             }
 
             let has_pending = self.has_pending_visible();
+            self.schedule_spinner(has_pending);
             let needs_prewarm = !has_pending && self.has_nearby_unwarmed_protocol();
             let timeout = if has_pending {
                 Duration::from_millis(16)
             } else if needs_prewarm {
                 Duration::from_millis(1)
+            } else if !self.ocr_pending.is_empty() {
+                OCR_POLL
             } else {
                 Duration::from_secs(60)
             };
+            // Wake up in time to clear a flashed status message, turn the
+            // page, update the clock, give up on the second key of a motion,
+            // render for a new terminal size or pick up a change to the
+            // watched file
+            let wake = [
+                self.status_expires,
+                self.auto_advance_at,
+                self.clock_tick,
+                self.pending_motion.map(|(_, expires)| expires),
+                self.resize_settles,
+                self.watcher.as_ref().map(FileWatcher::next_check),
+            ];
+            let timeout = wake.into_iter().flatten().fold(timeout, |timeout, t| {
+                timeout.min(t.saturating_duration_since(Instant::now()))
+            });
 
             if event::poll(timeout)? {
                 // Drain ALL pending events before redrawing so held-key
@@ -223,21 +1340,19 @@ impl App {
                 loop {
                     match event::read()? {
                         Event::Key(key) if key.kind == KeyEventKind::Press => {
-                            let msg = if self.goto_mode {
-                                input::key_to_goto_message(key)
-                            } else {
-                                input::key_to_message(key)
-                            };
-                            if let Some(msg) = msg {
+                            if let Some(msg) = self.key_to_message(key) {
+                                self.update(msg);
+                                dirty = true;
+                            }
+                        }
+                        Event::Mouse(mouse) if !self.in_prompt() => {
+                            if let Some(msg) = input::mouse_to_message(mouse) {
                                 self.update(msg);
                                 dirty = true;
                             }
                         }
                         Event::Resize(cols, rows) => {
-                            self.term_cols = cols;
-                            self.term_rows = rows;
-                            self.cache.clear();
-                            self.pending.clear();
+                            self.resize(cols, rows);
                             dirty = true;
                         }
                         _ => {}
@@ -249,267 +1364,2472 @@ impl App {
                 }
                 if dirty {
                     self.request_visible_pages();
-                    self.cache.evict_distant(self.current_page, 15);
+                    // Compared pages can be far apart; the budget still applies
+                    if self.compare.is_none() {
+                        let keep = (self.preload_radius() * 3)
+                            .max(self.layout.pages_shown())
+                            .min(self.page_count);
+                        self.cache.evict_distant(self.current_page, keep);
+                    }
                 }
             } else if needs_prewarm {
                 self.prewarm_one_nearby_protocol();
             }
         }
 
-        Ok(())
+        if self.tab_switch.is_some() {
+            // Only keep what's on screen, so switching back is instant
+            let keep = self.layout.pages_shown();
+            self.cache.evict_distant(self.current_page, keep);
+            self.cache.clear_thumbnails();
+        }
+        Ok(self.tab_switch.take())
+    }
+
+    /// Adopt a new terminal size. Pages rendered for the old size stay on
+    /// screen, scaled, until their re-render at the new size lands.
+    fn resize(&mut self, cols: u16, rows: u16) {
+        if (cols, rows) == (self.term_cols, self.term_rows) {
+            return;
+        }
+        self.term_cols = cols;
+        self.term_rows = rows;
+        // The page moves under a selection made in screen cells
+        self.selection = None;
+        self.resize_settles = Some(Instant::now() + RESIZE_DEBOUNCE);
+    }
+
+    /// Route a key press to whichever prompt or overlay has focus.
+    fn key_to_message(&self, key: KeyEvent) -> Option<Message> {
+        if self.password_mode {
+            input::key_to_password_message(key)
+        } else if self.info.is_some() {
+            Some(Message::HideInfo)
+        } else if self.goto_mode {
+            input::key_to_goto_message(key)
+        } else if self.command_mode {
+            input::key_to_command_message(key)
+        } else if self.compare.is_some() && key.code == KeyCode::Tab {
+            Some(Message::SwitchPane)
+        } else if self.present && key.code == KeyCode::Char(' ') {
+            Some(Message::ToggleAutoAdvance)
+        } else if self.bookmark_label_mode {
+            input::key_to_bookmark_label_message(key)
+        } else if self.bookmark_list.is_some() {
+            input::key_to_bookmark_list_message(key)
+        } else if self.thumbnails {
+            input::key_to_thumbnail_message(key)
+        } else if self.selection.is_some() {
+            input::key_to_selection_message(key)
+        } else if self.text_mode && key.code == KeyCode::Char(' ') && self.keymap.get(key).is_none()
+        {
+            Some(Message::TextPageDown)
+        } else if let Some((first, _)) = self.pending_motion {
+            input::key_to_motion_message(first, key).or_else(|| {
+                input::key_to_message(key, &self.keymap, self.pending_count.is_some(), false)
+            })
+        } else {
+            input::key_to_message(
+                key,
+                &self.keymap,
+                self.pending_count.is_some(),
+                self.vim_motions,
+            )
+        }
+    }
+
+    /// Whether a prompt or overlay is taking the input, so mouse events
+    /// shouldn't reach the page underneath.
+    fn in_prompt(&self) -> bool {
+        self.password_mode
+            || self.goto_mode
+            || self.command_mode
+            || self.thumbnails
+            || self.info.is_some()
+            || self.bookmark_label_mode
+            || self.bookmark_list.is_some()
+            || self.selection.is_some()
+    }
+
+    fn save_session(&self) {
+        let Some(key) = self.session_key.as_deref() else {
+            return;
+        };
+        if self.password_mode {
+            return;
+        }
+        let state = if self.resume {
+            SessionState {
+                page: self.dominant_page(),
+                zoom: self.zoom,
+                layout: self.compare.as_ref().map_or(self.layout, |c| c.layout),
+                fit: self.fit,
+                rotation: self.rotation,
+                color_mode: self.color_mode,
+                bookmarks: self.bookmarks.clone(),
+            }
+        } else {
+            // Keep whatever position was saved before; only bookmarks change
+            match session::load(key) {
+                Some(saved) => SessionState {
+                    bookmarks: self.bookmarks.clone(),
+                    ..saved
+                },
+                None if self.bookmarks.is_empty() => return,
+                None => SessionState {
+                    bookmarks: self.bookmarks.clone(),
+                    ..SessionState::default()
+                },
+            }
+        };
+        // Losing the reading position isn't worth failing the exit over
+        let _ = session::save(key, &state);
     }
 
-    /// Usable row count (subtracts 1 for the status bar unless fullscreen).
+    /// Usable row count (subtracts 1 for the status bar unless it's hidden).
     fn usable_rows(&self) -> u16 {
-        if self.fullscreen {
+        if self.fullscreen || !self.show_status_bar {
             self.term_rows
         } else {
             self.term_rows.saturating_sub(1)
         }
     }
 
+    /// Whether the view is too small to draw pages in, which only text mode
+    /// still does.
+    pub(crate) fn too_small(&self) -> bool {
+        !self.text_mode && (self.term_cols < MIN_VIEW_COLS || self.usable_rows() < MIN_VIEW_ROWS)
+    }
+
     fn process_render_results(&mut self) -> bool {
-        let current_scale = self.render_scale();
         let mut received = false;
 
         while let Ok(r) = self.render_rx.try_recv() {
-            self.pending.remove(&r.idx);
-            if (r.scale - current_scale).abs() < 0.01 {
-                self.cache.insert_image(r.idx, r.scale, r.img);
+            // Renders requested before a rotation, quality or annotation
+            // change are stale
+            let current = r.rotation == self.rotation
+                && r.antialias == self.quality.antialias()
+                && r.annotations == self.show_annotations;
+            if r.preview {
+                // The full render stays pending; a failed preview is
+                // reported by it too
+                if let (true, Ok(img)) = (current, r.img) {
+                    self.cache.insert_preview(r.idx, r.scale, img);
+                    received = true;
+                }
+                continue;
+            }
+            if r.thumbnail {
+                self.pending_thumbnails.remove(&r.idx);
+            } else if self
+                .pending
+                .get(&r.idx)
+                .is_some_and(|&s| same_scale(s, r.scale))
+            {
+                // Unless the page has been asked for again at another scale
+                self.pending.remove(&r.idx);
+            }
+            let img = match r.img {
+                Ok(img) => img,
+                Err(e) => {
+                    self.failed_pages.insert(r.idx, e);
+                    received = true;
+                    continue;
+                }
+            };
+            if r.thumbnail {
+                if current {
+                    self.cache.insert_thumbnail(r.idx, img);
+                    received = true;
+                }
+            } else if current
+                && (same_scale(r.scale, self.wanted_scale(r.idx))
+                    || self.cache.image_scale(r.idx).is_none_or(|s| s < r.scale))
+            {
+                // A render at an outdated scale still beats a blank page, or
+                // a blurrier one, until the right one arrives
+                self.cache.insert_image(r.idx, r.scale, img);
                 received = true;
             }
         }
 
-        if received {
-            let n = self.layout.pages_across();
-            let per_page_width = self.term_cols / n as u16;
-            let usable = self.usable_rows();
+        // Continuous mode clips protocols per scroll position, so prewarming
+        // full-page protocols would only be thrown away.
+        if received && !self.thumbnails && self.layout != PageLayout::Continuous {
+            let n = self.layout.pages_shown();
+            let page_area = self.cell_area();
 
             // Pre-warm protocols for visible pages + a few ahead for smooth navigation
             let prewarm_start = self.current_page;
-            let prewarm_end = (self.current_page + n + 3).min(self.page_count);
+            let prewarm_end =
+                (self.current_page + n + self.preload_radius().min(3)).min(self.page_count);
             for idx in prewarm_start..prewarm_end {
-                let Some((w, h)) = self.cache.image_dims(idx) else {
-                    continue;
-                };
-                let page_area = Rect::new(0, 0, per_page_width, usable);
-                let render_area = view::aligned_image_area(
-                    w,
-                    h,
-                    page_area,
-                    self.picker.font_size(),
-                    self.zoom,
-                    view::HAlign::Center,
-                );
-                self.cache.get_protocol(
-                    idx,
-                    self.dark_mode,
-                    self.zoom,
-                    (self.pan_x, self.pan_y),
-                    &self.picker,
-                    render_area,
-                );
+                self.prewarm_protocol(idx, page_area);
             }
         }
         received
     }
 
-    fn has_pending_visible(&self) -> bool {
-        let scale = self.render_scale();
-        let n = self.layout.pages_across();
-        (0..n).any(|i| {
-            let idx = self.current_page + i;
-            idx < self.page_count && !self.cache.has_image_at_scale(idx, scale)
-        })
+    /// Act on the timers that have run out: flashed messages, the clock,
+    /// a motion waiting for its second key, a finished resize and the
+    /// loading spinner. Returns whether the screen needs redrawing.
+    fn fire_timers(&mut self) -> bool {
+        let now = Instant::now();
+        let message_expired = self.status_expires.is_some_and(|t| t <= now);
+        if message_expired {
+            self.status_message = None;
+            self.status_expires = None;
+        }
+        let motion_expired = match self.pending_motion {
+            Some((key, t)) if t <= now => {
+                self.update(input::lone_motion_message(key));
+                self.request_visible_pages();
+                true
+            }
+            _ => false,
+        };
+        let resized = self.resize_settles.is_some_and(|t| t <= now);
+        if resized {
+            self.resize_settles = None;
+            self.cache.invalidate_protocols();
+            self.cancel_renders();
+            self.request_visible_pages();
+        }
+        let spinner_turned = self.spinner_tick.is_some_and(|t| t <= now);
+        if spinner_turned {
+            self.spinner_frame = self.spinner_frame.wrapping_add(1);
+            self.spinner_tick = None;
+        }
+        message_expired
+            || motion_expired
+            || resized
+            || spinner_turned
+            || self.clock_tick.is_some_and(|t| t <= now)
     }
 
-    pub fn render_scale(&self) -> f32 {
-        let (fw, fh) = self.picker.font_size();
-        let pages_across = self.layout.pages_across() as f64;
-        let area_px_w = (f64::from(self.term_cols) / pages_across) * f64::from(fw);
-        let area_px_h = f64::from(self.usable_rows()) * f64::from(fh);
-
-        let (page_w, page_h) = self.page_bounds;
-        let fit = (area_px_w / f64::from(page_w)).min(area_px_h / f64::from(page_h)) as f32;
-        // Render at higher resolution when zoomed in so cropping stays sharp
-        fit * self.zoom.max(1.0)
+    /// Keep the spinner turning while something on screen is loading,
+    /// except in present mode where it would only distract.
+    fn schedule_spinner(&mut self, has_pending: bool) {
+        if !has_pending || self.present {
+            self.spinner_tick = None;
+        } else if self.spinner_tick.is_none() {
+            self.spinner_tick = Some(Instant::now() + SPINNER_INTERVAL);
+        }
     }
 
-    fn request_visible_pages(&mut self) {
-        let scale = self.render_scale();
-        let n = self.layout.pages_across();
-
-        for i in 0..n {
-            let idx = self.current_page + i;
-            if idx < self.page_count {
-                self.request_page(idx, scale);
-            }
+    fn has_pending_visible(&mut self) -> bool {
+        if self.thumbnails {
+            return self.visible_thumbnails().any(|idx| {
+                !self.cache.has_thumbnail(idx) && !self.failed_pages.contains_key(&idx)
+            });
+        }
+        if self.text_mode {
+            return false;
         }
+        self.on_screen().into_iter().any(|idx| {
+            let scale = self.wanted_scale(idx);
+            !self.cache.has_image_at_scale(idx, scale) && !self.failed_pages.contains_key(&idx)
+        })
+    }
 
-        let visible_end = self.current_page + n;
-        for offset in 0..5 {
-            let ahead = visible_end + offset;
-            if ahead < self.page_count {
-                self.request_page(ahead, scale);
-            }
-            if let Some(behind) = self.current_page.checked_sub(offset + 1) {
-                self.request_page(behind, scale);
-            }
+    /// The pages on screen, including the other pane's in compare mode.
+    fn on_screen(&self) -> Vec<usize> {
+        if let Some(c) = &self.compare {
+            return vec![self.current_page, c.other.page];
         }
+        let end = (self.current_page + self.visible_pages()).min(self.page_count);
+        (self.current_page..end).collect()
     }
 
-    /// Check if any nearby page has a cached image but no protocol yet.
-    fn has_nearby_unwarmed_protocol(&self) -> bool {
-        let n = self.layout.pages_across();
-        let start = self.current_page.saturating_sub(5);
-        let end = (self.current_page + n + 5).min(self.page_count);
-        (start..end).any(|idx| {
-            self.cache.image_dims(idx).is_some() && !self.cache.has_protocol(idx, self.dark_mode)
-        })
+    /// First page's size in points as displayed, i.e. after rotation.
+    fn oriented_bounds(&self) -> (f32, f32) {
+        self.oriented(self.page_bounds)
     }
 
-    /// Generate one protocol for a nearby page during idle time.
-    fn prewarm_one_nearby_protocol(&mut self) {
-        let n = self.layout.pages_across();
-        let per_page_width = self.term_cols / n as u16;
-        let usable = self.usable_rows();
+    /// A page size turned by the view rotation.
+    const fn oriented(&self, (w, h): (f32, f32)) -> (f32, f32) {
+        if matches!(self.rotation, 90 | 270) {
+            (h, w)
+        } else {
+            (w, h)
+        }
+    }
 
-        // Prioritise pages ahead, then behind
-        let start = self.current_page;
-        let end = (self.current_page + n + 5).min(self.page_count);
-        let behind_start = self.current_page.saturating_sub(5);
+    /// Size in points of page `idx` as displayed. Pages can differ from the
+    /// first, e.g. a landscape table in a portrait paper.
+    pub(crate) fn page_size(&mut self, idx: usize) -> (f32, f32) {
+        let size = *self
+            .page_sizes
+            .entry(idx)
+            .or_insert_with(|| self.pdf.page_bounds(idx).unwrap_or(self.page_bounds));
+        self.oriented(size)
+    }
 
-        for idx in (start..end).chain(behind_start..self.current_page) {
-            if self.cache.image_dims(idx).is_some() && !self.cache.has_protocol(idx, self.dark_mode)
-            {
-                let (w, h) = self.cache.image_dims(idx).unwrap();
-                let page_area = Rect::new(0, 0, per_page_width, usable);
-                let render_area = view::aligned_image_area(
-                    w,
-                    h,
-                    page_area,
-                    self.picker.font_size(),
-                    self.zoom,
-                    view::HAlign::Center,
-                );
-                self.cache.get_protocol(
-                    idx,
-                    self.dark_mode,
-                    self.zoom,
-                    (self.pan_x, self.pan_y),
-                    &self.picker,
-                    render_area,
-                );
-                return;
-            }
-        }
+    /// The scale `idx` should be rendered at to fit the view. The other
+    /// compare pane has a zoom of its own.
+    fn wanted_scale(&mut self, idx: usize) -> f32 {
+        let zoom = match &self.compare {
+            Some(c) if idx == c.other.page && idx != self.current_page => c.other.zoom,
+            _ => self.zoom,
+        };
+        let size = self.page_size(idx);
+        self.render_scale(zoom, size)
     }
 
-    fn request_page(&mut self, idx: usize, scale: f32) {
-        if !self.cache.has_image_at_scale(idx, scale)
-            && !self.pending.contains(&idx)
-            && self.render_tx.send(RenderRequest { idx, scale }).is_ok()
-        {
-            self.pending.insert(idx);
+    /// Scale to render a `page_w` x `page_h` point page at, capped so the
+    /// render stays within `MAX_RENDER_PX`; past that the page is scaled up
+    /// on screen instead.
+    fn render_scale(&self, zoom: f32, (page_w, page_h): (f32, f32)) -> f32 {
+        let cap = MAX_RENDER_PX / page_w.max(page_h).max(1.0);
+        self.uncapped_render_scale(zoom, (page_w, page_h)).min(cap)
+    }
+
+    fn uncapped_render_scale(&self, zoom: f32, page: (f32, f32)) -> f32 {
+        if let Some(dpi) = self.dpi {
+            return dpi_scale(dpi) * zoom.max(1.0);
         }
+        let mut fit = self.fit_scale(page);
+        if self.autocrop {
+            fit *= AUTOCROP_OVERSAMPLE;
+        }
+        if self.lens != Lens::Full {
+            fit *= LENS_OVERSAMPLE;
+        }
+        fit *= self.scale_factor;
+        // Render at higher resolution when zoomed in so cropping stays sharp
+        fit * zoom.max(1.0)
     }
 
-    fn reset_pan(&mut self) {
-        self.pan_x = 0.0;
-        self.pan_y = 0.0;
+    /// Screen pixels per point that fit a page into its share of the view.
+    fn fit_scale(&self, (page_w, page_h): (f32, f32)) -> f32 {
+        let (fw, fh) = self.picker.font_size();
+        let pages_across = self.layout.pages_across() as f64;
+        let rows = self.layout.rows() as f64;
+        let area_px_w = (f64::from(self.page_cols()) / pages_across) * f64::from(fw);
+        let area_px_h = (f64::from(self.usable_rows()) / rows) * f64::from(fh);
+
+        let fit_w = area_px_w / f64::from(page_w);
+        let fit_h = area_px_h / f64::from(page_h);
+        (match self.fit {
+            FitMode::Page => fit_w.min(fit_h),
+            FitMode::Width => fit_w,
+            FitMode::Height => fit_h,
+        }) as f32
     }
 
-    fn update(&mut self, msg: Message) {
-        match msg {
-            Message::Quit => self.should_quit = true,
+    /// Fraction of a `dims`-sized page visible in `area` along each axis at
+    /// `zoom`, before panning. In fit-width/height mode the fitted dimension
+    /// spans the area and the other overflows.
+    fn window_size(&self, zoom: f32, dims: (u32, u32), area: Rect) -> (f32, f32) {
+        let (fw, fh) = self.picker.font_size();
+        let area_w = f32::from(area.width) * f32::from(fw);
+        let area_h = f32::from(area.height) * f32::from(fh);
+        let (img_w, img_h) = (dims.0 as f32, dims.1 as f32);
 
-            Message::NextPage => {
-                let max = self.page_count.saturating_sub(1);
-                self.current_page = (self.current_page + 1).min(max);
-            }
-            Message::PrevPage => {
-                self.current_page = self.current_page.saturating_sub(1);
+        let (w, h) = if area_w == 0.0 || area_h == 0.0 || img_w == 0.0 || img_h == 0.0 {
+            (1.0, 1.0)
+        } else {
+            match self.fit {
+                FitMode::Page => (1.0, 1.0),
+                FitMode::Width => (1.0, (area_h * img_w / (area_w * img_h)).min(1.0)),
+                FitMode::Height => ((area_w * img_h / (area_h * img_w)).min(1.0), 1.0),
             }
-            Message::FirstPage => {
-                self.current_page = 0;
+        };
+        let zoom = zoom.max(1.0);
+        (w / zoom, h / zoom)
+    }
+
+    /// In fit-width/height mode, the slice of a `dims`-sized page image that
+    /// fills `area`, with `pan` picking which part shows. `None` when the
+    /// whole page fits.
+    #[must_use]
+    pub fn fit_clip(&self, dims: (u32, u32), area: Rect) -> Option<Region> {
+        if self.fit == FitMode::Page {
+            return None;
+        }
+        let (w, h) = self.window_size(self.zoom, dims, area);
+        if w >= 1.0 && h >= 1.0 {
+            return None;
+        }
+        Some(Region {
+            x: pan_offset(self.pan_x, w),
+            y: pan_offset(self.pan_y, h),
+            w,
+            h,
+        })
+    }
+
+    /// Keep a pinned zoom at the size the page was shown at now that it is
+    /// zoomed differently.
+    fn repin_zoom(&mut self) {
+        if self.pinned_zoom.is_some() {
+            self.pinned_zoom = Some(self.fit_scale(self.oriented_bounds()) * self.zoom);
+        }
+    }
+
+    /// Re-derive a pinned zoom from the fit, which a layout, fit mode or
+    /// terminal size change may have moved, so the page keeps its size.
+    fn keep_pinned_zoom(&mut self) {
+        let Some(pinned) = self.pinned_zoom else {
+            return;
+        };
+        let fit = self.fit_scale(self.oriented_bounds());
+        if fit <= 0.0 {
+            return;
+        }
+        let zoom = (pinned / fit).clamp(0.25, 4.0);
+        if (zoom - self.zoom).abs() > 0.001 {
+            self.set_zoom(zoom);
+        }
+    }
+
+    /// Change the zoom, re-deriving the pan so the content under the center
+    /// of the view stays there.
+    fn set_zoom(&mut self, zoom: f32) {
+        let old_zoom = self.zoom;
+        self.zoom = zoom;
+        self.cancel_renders();
+
+        let (w, h) = self.oriented_bounds();
+        let dims = (w as u32, h as u32);
+        let area = self.cell_area();
+        let (old_w, old_h) = self.window_size(old_zoom, dims, area);
+        let (new_w, new_h) = self.window_size(zoom, dims, area);
+        self.pan_x = refocus_pan(self.pan_x, old_w, new_w);
+        self.pan_y = refocus_pan(self.pan_y, old_h, new_h);
+    }
+
+    /// The space one page gets in the current layout.
+    fn cell_area(&self) -> Rect {
+        let cols = self.layout.pages_across() as u16;
+        let rows = self.layout.rows() as u16;
+        Rect::new(0, 0, self.page_cols() / cols, self.usable_rows() / rows)
+    }
+
+    /// Columns given to the pages, the left half in the split view.
+    pub(crate) fn page_cols(&self) -> u16 {
+        if self.split_text && !self.text_mode {
+            self.term_cols / 2
+        } else {
+            self.term_cols
+        }
+    }
+
+    /// The page shown in the split view's text pane, starting its text
+    /// from the top whenever that changes.
+    pub(crate) fn split_text_page(&mut self) -> usize {
+        let page = self.dominant_page();
+        if page != self.split_text_page {
+            self.split_text_page = page;
+            self.text_scroll = 0;
+        }
+        page
+    }
+
+    /// Whether the current page overflows the view vertically, leaving
+    /// more of it above/below to pan to.
+    fn can_pan_vertically(&self) -> bool {
+        self.visible_window().1 < 1.0
+    }
+
+    fn can_pan_horizontally(&self) -> bool {
+        self.visible_window().0 < 1.0
+    }
+
+    /// Fraction of the current page on screen along each axis.
+    fn visible_window(&self) -> (f32, f32) {
+        let dims = self.cache.image_dims(self.current_page).unwrap_or_else(|| {
+            let (w, h) = self.oriented_bounds();
+            (w as u32, h as u32)
+        });
+        self.window_size(self.zoom, dims, self.cell_area())
+    }
+
+    /// Whether dual layout is pairing pages as book spreads.
+    fn book_spreads(&self) -> bool {
+        self.cover_offset && self.layout == PageLayout::Dual && self.compare.is_none()
+    }
+
+    /// Number of pages shown starting at `current_page`: the cover of a
+    /// book stands alone.
+    pub(crate) fn spread_len(&self) -> usize {
+        if self.book_spreads() && self.current_page == 0 {
+            1
+        } else {
+            self.layout.pages_shown()
+        }
+    }
+
+    /// Estimated time to read the pages after the ones on screen.
+    pub(crate) fn reading_time_left(&self) -> Option<Duration> {
+        let after = self.dominant_page() + self.spread_len();
+        self.pace.remaining(self.page_count.saturating_sub(after))
+    }
+
+    /// Whether `idx` is drawn dimmed beside the current page. The unfocused
+    /// compare pane is dimmed by the view itself.
+    pub(crate) fn dims_page(&self, idx: usize) -> bool {
+        self.dim_inactive
+            && self.compare.is_none()
+            && self.layout.pages_shown() > 1
+            && idx != self.current_page
+    }
+
+    /// The first page of the spread containing `page`.
+    fn spread_start(&self, page: usize) -> usize {
+        if self.book_spreads() && page > 0 {
+            page - (page - 1) % 2
+        } else {
+            page
+        }
+    }
+
+    /// Number of pages on screen starting at `current_page`, including any
+    /// partially visible neighbours in continuous mode.
+    fn visible_pages(&self) -> usize {
+        if self.layout != PageLayout::Continuous {
+            return self.spread_len();
+        }
+        let pitch = f32::from(self.continuous_page_size().1 + CONTINUOUS_GAP);
+        let bottom = self
+            .scroll_offset
+            .mul_add(pitch, f32::from(self.usable_rows()));
+        ((bottom / pitch).ceil() as usize).max(1)
+    }
+
+    /// Size in cells of one page in continuous mode. Pages fit the viewport
+    /// (or just its width in fit-width mode) at zoom 1.0 and grow with zoom,
+    /// overflowing horizontally past the edges.
+    #[must_use]
+    pub fn continuous_page_size(&self) -> (u16, u16) {
+        let (w, h) = self.oriented_bounds();
+        let rows = if self.fit == FitMode::Width {
+            u16::MAX
+        } else {
+            self.usable_rows()
+        };
+        let area = Rect::new(0, 0, self.page_cols(), rows);
+        let fit = view::aligned_image_area(
+            w as u32,
+            h as u32,
+            area,
+            self.picker.font_size(),
+            1.0,
+            view::HAlign::Center,
+        );
+        let width = (f32::from(fit.width) * self.zoom).round().max(1.0) as u16;
+        let height = (f32::from(fit.height) * self.zoom).round().max(1.0) as u16;
+        (width, height)
+    }
+
+    /// The page covering the most rows of the viewport in continuous mode.
+    #[must_use]
+    pub fn dominant_page(&self) -> usize {
+        if self.text_mode || self.layout != PageLayout::Continuous {
+            return self.current_page;
+        }
+        let page_h = i32::from(self.continuous_page_size().1);
+        let pitch = page_h + i32::from(CONTINUOUS_GAP);
+        let view_h = i32::from(self.usable_rows());
+        let mut top = -(self.scroll_offset * pitch as f32).round() as i32;
+        let mut best = (self.current_page, 0);
+        let mut idx = self.current_page;
+        while idx < self.page_count && top < view_h {
+            let visible = (top + page_h).min(view_h) - top.max(0);
+            if visible > best.1 {
+                best = (idx, visible);
+            }
+            top += pitch;
+            idx += 1;
+        }
+        best.0
+    }
+
+    /// Scroll by `delta` pages in continuous mode, crossing page boundaries.
+    fn scroll_continuous(&mut self, delta: f32) {
+        let mut offset = self.scroll_offset + delta;
+        while offset >= 1.0 && self.current_page + 1 < self.page_count {
+            self.current_page += 1;
+            offset -= 1.0;
+        }
+        while offset < 0.0 && self.current_page > 0 {
+            self.current_page -= 1;
+            offset += 1.0;
+        }
+
+        // On the last page, stop once its bottom edge reaches the viewport's
+        let max = if self.current_page + 1 == self.page_count {
+            let page_h = self.continuous_page_size().1;
+            let overflow = page_h.saturating_sub(self.usable_rows());
+            f32::from(overflow) / f32::from(page_h + CONTINUOUS_GAP)
+        } else {
+            1.0
+        };
+        self.scroll_offset = offset.clamp(0.0, max);
+    }
+
+    /// Pages preloaded on each side of the visible ones: a short document
+    /// is preloaded whole, not `preload` pages past its ends.
+    fn preload_radius(&self) -> usize {
+        self.preload.min(self.page_count)
+    }
+
+    fn request_visible_pages(&mut self) {
+        // Sizes in the middle of dragging the window aren't worth rendering
+        if self.resize_settles.is_some() || self.too_small() {
+            return;
+        }
+        self.keep_pinned_zoom();
+        if self.thumbnails {
+            // One row past the bottom so scrolling down reveals finished ones
+            let (cols, _) = self.thumbnail_grid();
+            let end = self.visible_thumbnails().end;
+            for idx in self.visible_thumbnails().start..(end + cols).min(self.page_count) {
+                self.request_thumbnail(idx);
+            }
+            return;
+        }
+        // Pages asked for earlier may be on screen now, and pages that were
+        // on screen may have been paged past
+        let on_screen = self.on_screen();
+        self.render_tx
+            .reprioritize(|r| !r.thumbnail && on_screen.contains(&r.idx));
+        for idx in on_screen {
+            let scale = self.wanted_scale(idx);
+            self.request_page(idx, scale, true);
+        }
+
+        let visible_end = self.current_page + self.visible_pages();
+        for offset in 0..self.preload_radius() {
+            let ahead = visible_end + offset;
+            if ahead < self.page_count {
+                let scale = self.wanted_scale(ahead);
+                self.request_page(ahead, scale, false);
+            }
+            if let Some(behind) = self.current_page.checked_sub(offset + 1) {
+                let scale = self.wanted_scale(behind);
+                self.request_page(behind, scale, false);
+            }
+        }
+    }
+
+    /// Check if any nearby page has a cached image but no protocol yet.
+    fn has_nearby_unwarmed_protocol(&self) -> bool {
+        if self.thumbnails || self.text_mode || self.layout == PageLayout::Continuous {
+            return false;
+        }
+        let n = self.layout.pages_shown();
+        let start = self.current_page.saturating_sub(self.preload_radius());
+        let end = (self.current_page + n + self.preload_radius()).min(self.page_count);
+        (start..end).any(|idx| {
+            self.cache.image_dims(idx).is_some() && !self.cache.has_protocol(idx, self.color_mode)
+        })
+    }
+
+    /// Generate one protocol for a nearby page during idle time.
+    fn prewarm_one_nearby_protocol(&mut self) {
+        let n = self.layout.pages_shown();
+        let page_area = self.cell_area();
+
+        // Prioritise pages ahead, then behind
+        let start = self.current_page;
+        let end = (self.current_page + n + self.preload_radius()).min(self.page_count);
+        let behind_start = self.current_page.saturating_sub(self.preload_radius());
+
+        for idx in (start..end).chain(behind_start..self.current_page) {
+            if self.cache.image_dims(idx).is_some()
+                && !self.cache.has_protocol(idx, self.color_mode)
+            {
+                self.prewarm_protocol(idx, page_area);
+                return;
+            }
+        }
+    }
+
+    /// Build the protocol `view::draw` will need for a page drawn in
+    /// `page_area`, if its image has been rendered.
+    fn prewarm_protocol(&mut self, idx: usize, page_area: Rect) {
+        let Some((w, h)) = self.cache.image_dims(idx) else {
+            return;
+        };
+        let clip = self.fit_clip((w, h), page_area);
+        let render_area = if clip.is_some() {
+            page_area
+        } else {
+            view::aligned_image_area(
+                w,
+                h,
+                page_area,
+                self.picker.font_size(),
+                self.zoom,
+                view::HAlign::Center,
+            )
+        };
+        self.cache.get_protocol(
+            idx,
+            self.color_mode,
+            self.zoom,
+            (self.pan_x, self.pan_y),
+            clip,
+            self.dims_page(idx),
+            &self.picker,
+            render_area,
+        );
+    }
+
+    /// Ask for `idx` at `scale`, unless it is cached or on its way. A page
+    /// `on_screen` goes ahead of those rendered in advance, and gets a quick
+    /// blurry render first if it has no image yet.
+    fn request_page(&mut self, idx: usize, scale: f32, on_screen: bool) {
+        let preview =
+            (on_screen && self.cache.image_dims(idx).is_none()).then_some(scale * PREVIEW_FRACTION);
+        if !self.cache.has_image_at_scale(idx, scale)
+            && !self
+                .pending
+                .get(&idx)
+                .is_some_and(|&s| same_scale(s, scale))
+            && !self.failed_pages.contains_key(&idx)
+            && self
+                .render_tx
+                .send(
+                    RenderRequest {
+                        epoch: self.render_epoch.load(Ordering::Relaxed),
+                        idx,
+                        scale,
+                        rotation: self.rotation,
+                        antialias: self.quality.antialias(),
+                        annotations: self.show_annotations,
+                        thumbnail: false,
+                        preview,
+                    },
+                    on_screen,
+                )
+                .is_ok()
+        {
+            self.pending.insert(idx, scale);
+        }
+    }
+
+    fn request_thumbnail(&mut self, idx: usize) {
+        if !self.cache.has_thumbnail(idx)
+            && !self.pending_thumbnails.contains(&idx)
+            && !self.failed_pages.contains_key(&idx)
+            && self
+                .render_tx
+                .send(
+                    RenderRequest {
+                        epoch: self.render_epoch.load(Ordering::Relaxed),
+                        idx,
+                        scale: self.thumbnail_scale(),
+                        rotation: self.rotation,
+                        antialias: self.quality.antialias(),
+                        annotations: self.show_annotations,
+                        thumbnail: true,
+                        preview: None,
+                    },
+                    true,
+                )
+                .is_ok()
+        {
+            self.pending_thumbnails.insert(idx);
+        }
+    }
+
+    /// Scale that renders a page exactly as wide as its thumbnail.
+    fn thumbnail_scale(&self) -> f32 {
+        let px = f32::from(THUMBNAIL_WIDTH) * f32::from(self.picker.font_size().0);
+        px / self.oriented_bounds().0
+    }
+
+    /// Size in cells of a thumbnail image, from the first page's shape.
+    #[must_use]
+    pub fn thumbnail_size(&self) -> (u16, u16) {
+        let (fw, fh) = self.picker.font_size();
+        let (w, h) = self.oriented_bounds();
+        let px_h = f32::from(THUMBNAIL_WIDTH) * f32::from(fw) * h / w;
+        let rows = (px_h / f32::from(fh)).ceil().max(1.0) as u16;
+        (THUMBNAIL_WIDTH, rows)
+    }
+
+    /// Thumbnail grid columns and fully visible rows. Each cell holds a
+    /// one-cell border around the image plus a page-number row.
+    #[must_use]
+    pub fn thumbnail_grid(&self) -> (usize, usize) {
+        let (w, h) = self.thumbnail_size();
+        let cols = (self.term_cols / (w + 2)).max(1);
+        let rows = (self.usable_rows() / (h + 3)).max(1);
+        (usize::from(cols), usize::from(rows))
+    }
+
+    #[must_use]
+    pub fn visible_thumbnails(&self) -> std::ops::Range<usize> {
+        let (cols, rows) = self.thumbnail_grid();
+        let start = (self.thumbnail_scroll * cols).min(self.page_count);
+        start..(start + cols * rows).min(self.page_count)
+    }
+
+    /// Move the thumbnail selection by `delta` pages and scroll the grid to
+    /// keep it on screen.
+    fn move_thumbnail(&mut self, delta: isize) {
+        let max = self.page_count.saturating_sub(1);
+        self.thumbnail_selected = self
+            .thumbnail_selected
+            .saturating_add_signed(delta)
+            .min(max);
+        self.scroll_thumbnail_into_view();
+    }
+
+    pub(crate) fn scroll_thumbnail_into_view(&mut self) {
+        let (cols, rows) = self.thumbnail_grid();
+        let row = self.thumbnail_selected / cols;
+        if row < self.thumbnail_scroll {
+            self.thumbnail_scroll = row;
+        } else if row >= self.thumbnail_scroll + rows {
+            self.thumbnail_scroll = row + 1 - rows;
+        }
+    }
+
+    /// Scroll to the top or bottom of the current page, or of the text in
+    /// text mode.
+    fn pan_to_edge(&mut self, bottom: bool) {
+        if self.text_mode {
+            // Clamped to the end of the text when drawn
+            self.text_scroll = if bottom { usize::MAX } else { 0 };
+        } else if self.layout == PageLayout::Continuous {
+            self.scroll_offset = 0.0;
+            if bottom {
+                let page_h = self.continuous_page_size().1;
+                let overflow = page_h.saturating_sub(self.usable_rows());
+                self.scroll_continuous(f32::from(overflow) / f32::from(page_h + CONTINUOUS_GAP));
+            }
+        } else if self.can_pan_vertically() {
+            self.pan_y = if bottom { 1.0 } else { -1.0 };
+        }
+    }
+
+    /// The furthest text mode can scroll on the current page, as last
+    /// drawn; `None` before its text has been wrapped.
+    fn text_bottom(&self) -> Option<usize> {
+        let (width, height) = self.text_pane;
+        let lines = self
+            .cache
+            .wrapped_text(self.current_page, width, self.text_reflow)?;
+        Some(lines.len().saturating_sub(usize::from(height)))
+    }
+
+    /// Scroll text mode down by `lines`, or from the end of the page on to
+    /// the top of the next one.
+    fn scroll_text_down(&mut self, lines: usize) {
+        let next = self.current_page + self.spread_len();
+        let at_bottom = self
+            .text_bottom()
+            .is_some_and(|bottom| self.text_scroll >= bottom);
+        if at_bottom && next < self.page_count {
+            self.jump_to(next);
+        } else {
+            // Clamped to the end of the text when drawn
+            self.text_scroll = self.text_scroll.saturating_add(lines);
+        }
+    }
+
+    /// Scroll text mode up by `lines`, or from the top of the page back to
+    /// the end of the previous one.
+    fn scroll_text_up(&mut self, lines: usize) {
+        if self.text_scroll == 0 && self.current_page > 0 {
+            let step = if self.book_spreads() { 2 } else { 1 };
+            self.jump_to(self.current_page.saturating_sub(step));
+            self.text_scroll = usize::MAX;
+        } else {
+            self.text_scroll = self.text_scroll.saturating_sub(lines);
+        }
+    }
+
+    /// How far to pan in `dir`: `PAN_STEP`, growing while the pans come in
+    /// a quick run in one direction. A pause or a turn starts over.
+    fn pan_step(&mut self, dir: (i8, i8)) -> f32 {
+        let now = Instant::now();
+        let streak = match self.pan_streak {
+            Some((last_dir, last, n)) if last_dir == dir && now - last < PAN_REPEAT_WINDOW => n + 1,
+            _ => 0,
+        };
+        self.pan_streak = Some((dir, now, streak));
+        (PAN_STEP * PAN_ACCELERATION.powi(streak)).min(MAX_PAN_STEP)
+    }
+
+    fn reset_pan(&mut self) {
+        self.pan_x = 0.0;
+        self.pan_y = 0.0;
+    }
+
+    /// Pages and thumbnails being rendered.
+    pub(crate) fn pending_count(&self) -> usize {
+        self.pending.len() + self.pending_thumbnails.len()
+    }
+
+    /// Forget the pages on their way, to be asked for again as they are
+    /// wanted now, and have the workers skip the old requests.
+    fn cancel_renders(&mut self) {
+        self.pending.clear();
+        self.render_epoch.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Show a message in the status bar for a moment.
+    fn flash(&mut self, msg: impl Into<String>) {
+        self.status_message = Some(msg.into());
+        self.status_expires = Some(Instant::now() + FLASH_DURATION);
+    }
+
+    /// Extract a page's text on first use and keep it in the cache. With
+    /// `ocr`, a page without any is sent to tesseract instead, and has no
+    /// text until it has been read; see [`Self::recognizing`].
+    pub(crate) fn ensure_page_text(&mut self, page_idx: usize) -> Option<&str> {
+        if self.cache.page_text(page_idx).is_none() {
+            if self.recognizing(page_idx) {
+                return None;
+            }
+            let text = self.pdf.extract_text(page_idx).ok();
+            if self.ocr && text.as_deref().is_none_or(|t| t.trim().is_empty()) {
+                self.recognize_page(page_idx);
+                return None;
+            }
+            self.cache.insert_text(page_idx, text?);
+        }
+        self.cache.page_text(page_idx)
+    }
+
+    /// Whether tesseract is still reading the page.
+    pub(crate) fn recognizing(&self, page_idx: usize) -> bool {
+        self.ocr_pending.contains(&page_idx)
+    }
+
+    /// Have the OCR worker read a page, starting it if need be. That takes
+    /// seconds a page, so it happens off the UI thread.
+    fn recognize_page(&mut self, page_idx: usize) {
+        let (tx, _) = self
+            .ocr_worker
+            .get_or_insert_with(|| spawn_ocr_worker(&self.source, self.pdf.password()));
+        if tx.send(page_idx).is_ok() {
+            self.ocr_pending.insert(page_idx);
+        }
+    }
+
+    /// Put the text the OCR worker has read into the cache. A failure is
+    /// shown and turns OCR off, rather than being retried on every frame.
+    /// Returns whether anything arrived.
+    fn process_ocr_results(&mut self) -> bool {
+        let Some((_, rx)) = &self.ocr_worker else {
+            return false;
+        };
+        let results: Vec<OcrResult> = rx.try_iter().collect();
+        let received = !results.is_empty();
+        for r in results {
+            self.ocr_pending.remove(&r.idx);
+            match r.text {
+                Ok(text) => self.cache.insert_text(r.idx, text),
+                Err(e) => {
+                    self.ocr = false;
+                    self.ocr_worker = None;
+                    self.ocr_pending.clear();
+                    self.flash(format!("OCR failed: {e}"));
+                    break;
+                }
+            }
+        }
+        received
+    }
+
+    /// Whether the document looks like scans without a text layer: none of
+    /// a few pages spread through it has any text. Checked once, and never
+    /// with `ocr`, which reads such pages.
+    fn looks_scanned(&mut self) -> bool {
+        if self.ocr {
+            return false;
+        }
+        if let Some(scanned) = self.scanned {
+            return scanned;
+        }
+        let samples = SCAN_SAMPLES.min(self.page_count);
+        let scanned = samples > 0
+            && (0..samples).all(|i| {
+                let idx = i * self.page_count / samples;
+                self.pdf
+                    .extract_text(idx)
+                    .map_or(true, |t| t.trim().is_empty())
+            });
+        self.scanned = Some(scanned);
+        scanned
+    }
+
+    /// Wrap a page's text to `width` columns for text mode, once per width.
+    pub(crate) fn ensure_wrapped_text(&mut self, page_idx: usize, width: u16) -> Option<&[Row]> {
+        let reflow = self.text_reflow;
+        if self.cache.wrapped_text(page_idx, width, reflow).is_none() {
+            let headings = self.pdf.headings(page_idx).unwrap_or_default();
+            let text = self.ensure_page_text(page_idx)?;
+            let lines = text::wrap(text, usize::from(width), reflow, &headings);
+            self.cache
+                .insert_wrapped_text(page_idx, width, reflow, lines);
+        }
+        self.cache.wrapped_text(page_idx, width, reflow)
+    }
+
+    /// Load a page's links on first use and keep them in the cache.
+    pub(crate) fn ensure_page_links(&mut self, page_idx: usize) -> &[LinkInfo] {
+        if self.cache.page_links(page_idx).is_none() {
+            let links = self.pdf.links(page_idx).unwrap_or_default();
+            self.cache.insert_links(page_idx, links);
+        }
+        self.cache.page_links(page_idx).unwrap_or_default()
+    }
+
+    /// Load a page's form fields on first use and keep them in the cache.
+    pub(crate) fn ensure_page_fields(&mut self, page_idx: usize) -> &[FieldInfo] {
+        if self.cache.page_fields(page_idx).is_none() {
+            let fields = self.pdf.form_fields(page_idx).unwrap_or_default();
+            self.cache.insert_fields(page_idx, fields);
+        }
+        self.cache.page_fields(page_idx).unwrap_or_default()
+    }
+
+    /// Load a page's notes on first use and keep them in the cache.
+    pub(crate) fn ensure_page_notes(&mut self, page_idx: usize) -> &[NoteInfo] {
+        if self.cache.page_notes(page_idx).is_none() {
+            let notes = self.pdf.notes(page_idx).unwrap_or_default();
+            self.cache.insert_notes(page_idx, notes);
+        }
+        self.cache.page_notes(page_idx).unwrap_or_default()
+    }
+
+    fn cycle_link(&mut self, forward: bool) {
+        let count = self.link_hits.len();
+        if count == 0 {
+            self.flash("No links on screen");
+            return;
+        }
+        self.selected_link = Some(match (self.selected_link, forward) {
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+            (None, true) => 0,
+            (None, false) => count - 1,
+        });
+    }
+
+    fn follow_link(&mut self, target: LinkTarget) {
+        match target {
+            LinkTarget::Page(page) if page < self.page_count => self.jump_with_history(page),
+            LinkTarget::Page(_) => self.flash("Link points outside the document"),
+            LinkTarget::Uri(uri) => match open_uri(&uri) {
+                Ok(()) => self.flash(format!("Opened {uri}")),
+                Err(e) => self.flash(format!("Could not open {uri}: {e}")),
+            },
+        }
+    }
+
+    fn click(&mut self, col: u16, row: u16) {
+        let hit = |r: &Rect| r.contains((col, row).into());
+        if self.page_indicator.as_ref().is_some_and(hit) {
+            self.update(Message::EnterGoto);
+        } else if let Some(i) = self.link_hits.iter().position(|(r, _)| hit(r)) {
+            self.selected_link = Some(i);
+            self.follow_link(self.link_hits[i].1.clone());
+        }
+    }
+
+    fn copy_page_text(&mut self) {
+        let page = self.dominant_page();
+        let Some(text) = self.ensure_page_text(page).map(str::to_owned) else {
+            if self.recognizing(page) {
+                self.flash("Still recognizing the page's text, try again in a moment");
+            } else {
+                self.flash("Could not extract text");
+            }
+            return;
+        };
+        if text.trim().is_empty() {
+            self.flash("No text on this page");
+            return;
+        }
+        match self.clipboard.copy_text(&text) {
+            Ok(()) => self.flash("Copied"),
+            Err(e) => self.flash(format!("Copy failed: {e}")),
+        }
+    }
+
+    /// The document's name without its extension, for naming saved images.
+    fn file_stem(&self) -> String {
+        let name = self.source.name();
+        std::path::Path::new(&name)
+            .file_stem()
+            .map_or_else(|| name.clone(), |s| s.to_string_lossy().into_owned())
+    }
+
+    /// Copy the page at export resolution, for pasting a figure elsewhere.
+    fn copy_page_image(&mut self) {
+        let page = self.dominant_page();
+        let scale = self.dpi.map_or(EXPORT_SCALE, dpi_scale);
+        let img = match self
+            .pdf
+            .render_page(page, scale, self.rotation, self.show_annotations)
+        {
+            Ok(img) => img,
+            Err(e) => {
+                self.flash(format!("Could not render page {}: {e}", page + 1));
+                return;
+            }
+        };
+        let stem = self.file_stem();
+        match self
+            .clipboard
+            .copy_image(&img, &format!("tpdf-{stem}-{}.png", page + 1))
+        {
+            Ok(ImageCopy::Bitmap) => self.flash(format!("Copied page {} as an image", page + 1)),
+            Ok(ImageCopy::Path(path)) => {
+                self.flash(format!("Saved {}; copied its path", path.display()));
+            }
+            Err(e) => self.flash(format!("Copy failed: {e}")),
+        }
+    }
+
+    /// Put a selection box over the middle of the page on screen, or take
+    /// it away.
+    fn toggle_selection(&mut self) {
+        if self.selection.take().is_some() {
+            return;
+        }
+        if self.text_mode {
+            self.flash("Selection works on the rendered page; copy text mode with y");
+            return;
+        }
+        let page = self.dominant_page();
+        let Some(area) = self
+            .page_areas
+            .iter()
+            .find(|a| a.page == page)
+            .map(|a| a.area)
+        else {
+            self.flash("No page on screen to select from");
+            return;
+        };
+        let (w, h) = ((area.width / 2).max(1), (area.height / 4).max(1));
+        self.selection = Some(Selection {
+            page,
+            rect: Rect::new(
+                area.x + (area.width - w) / 2,
+                area.y + (area.height - h) / 2,
+                w,
+                h,
+            ),
+        });
+        self.flash("Arrows move, Shift+arrows resize, y copies, Esc cancels");
+    }
+
+    /// The screen area of the page being selected from.
+    fn selection_area(&self) -> Option<(&Selection, &PageArea)> {
+        let sel = self.selection.as_ref()?;
+        let area = self.page_areas.iter().find(|a| a.page == sel.page)?;
+        Some((sel, area))
+    }
+
+    fn move_selection(&mut self, dx: i16, dy: i16) {
+        let Some((sel, page)) = self.selection_area() else {
+            return;
+        };
+        let (rect, area) = (sel.rect, page.area);
+        let x = rect
+            .x
+            .saturating_add_signed(dx)
+            .clamp(area.x, area.right().saturating_sub(rect.width));
+        let y = rect
+            .y
+            .saturating_add_signed(dy)
+            .clamp(area.y, area.bottom().saturating_sub(rect.height));
+        if let Some(sel) = &mut self.selection {
+            sel.rect = Rect::new(x, y, rect.width, rect.height);
+        }
+    }
+
+    fn resize_selection(&mut self, dw: i16, dh: i16) {
+        let Some((sel, page)) = self.selection_area() else {
+            return;
+        };
+        let (rect, area) = (sel.rect, page.area);
+        let w = rect
+            .width
+            .saturating_add_signed(dw)
+            .clamp(1, area.right().saturating_sub(rect.x).max(1));
+        let h = rect
+            .height
+            .saturating_add_signed(dh)
+            .clamp(1, area.bottom().saturating_sub(rect.y).max(1));
+        if let Some(sel) = &mut self.selection {
+            sel.rect = Rect::new(rect.x, rect.y, w, h);
+        }
+    }
+
+    /// Copy the text under the selection, mapping its cells back onto the
+    /// page: the reverse of how link areas are placed on screen.
+    fn copy_selection(&mut self) {
+        let Some((sel, page)) = self.selection_area() else {
+            self.selection = None;
+            return;
+        };
+        let area = page.area;
+        let rect = sel.rect.intersection(area);
+        let on_screen = Region {
+            x: f32::from(rect.x.saturating_sub(area.x)) / f32::from(area.width),
+            y: f32::from(rect.y.saturating_sub(area.y)) / f32::from(area.height),
+            w: f32::from(rect.width) / f32::from(area.width),
+            h: f32::from(rect.height) / f32::from(area.height),
+        };
+        let region = on_screen
+            .inside(page.window)
+            .inside(page.content)
+            .rotated((360 - self.rotation) % 360);
+        let idx = sel.page;
+        self.selection = None;
+
+        let text = match self.pdf.text_in_region(idx, region) {
+            Ok(text) => text,
+            Err(e) => {
+                self.flash(format!("Could not extract text: {e}"));
+                return;
+            }
+        };
+        if text.trim().is_empty() {
+            self.flash("No text in the selection");
+            return;
+        }
+        match self.clipboard.copy_text(&text) {
+            Ok(()) => self.flash("Copied"),
+            Err(e) => self.flash(format!("Copy failed: {e}")),
+        }
+    }
+
+    fn adjust_brightness(&mut self, delta: i32) {
+        self.brightness = (self.brightness + delta).clamp(-BRIGHTNESS_LIMIT, BRIGHTNESS_LIMIT);
+        self.cache.set_adjustments(self.brightness, self.contrast);
+    }
+
+    fn adjust_contrast(&mut self, delta: f32) {
+        self.contrast = (self.contrast + delta).clamp(-CONTRAST_LIMIT, CONTRAST_LIMIT);
+        self.cache.set_adjustments(self.brightness, self.contrast);
+    }
+
+    /// Switch to `fit`, or back to whole-page fit if it is already active.
+    fn set_fit(&mut self, fit: FitMode) {
+        self.fit = if self.fit == fit { FitMode::Page } else { fit };
+        self.cancel_renders();
+        self.reset_pan();
+        // Start reading from the top of the page
+        if self.fit == FitMode::Width {
+            self.pan_y = -1.0;
+        }
+    }
+
+    /// Turn every page clockwise by `degrees`. Rendered pages change shape,
+    /// so everything rendered so far is dropped.
+    fn rotate(&mut self, degrees: u16) {
+        self.rotation = (self.rotation + degrees) % 360;
+        self.cache.clear();
+        self.cache.clear_thumbnails();
+        self.cancel_renders();
+        self.pending_thumbnails.clear();
+        self.reset_pan();
+        self.scroll_offset = 0.0;
+    }
+
+    /// The first free slot label for a new bookmark on the current page, or
+    /// `None` (with a message) if there is nothing to do.
+    fn next_bookmark_slot(&mut self) -> Option<char> {
+        let page = self.dominant_page();
+        if let Some(b) = self.bookmarks.iter().find(|b| b.page == page) {
+            self.flash(format!("Page already bookmarked as '{}'", b.label));
+            return None;
+        }
+        let free = BOOKMARK_SLOTS
+            .chars()
+            .find(|&c| self.bookmarks.iter().all(|b| b.label != c));
+        if free.is_none() {
+            self.flash("No free bookmark slots");
+        }
+        free
+    }
+
+    /// Bookmark the current page under `label`. Reusing a label moves it.
+    fn set_bookmark(&mut self, label: char) {
+        let page = self.dominant_page();
+        self.bookmarks.retain(|b| b.label != label);
+        self.bookmarks.push(Bookmark { label, page });
+        self.bookmarks.sort_by_key(|b| (b.page, b.label));
+        self.save_session();
+        self.flash(format!("Bookmarked page {} as '{label}'", page + 1));
+    }
+
+    fn move_bookmark_selection(&mut self, down: bool) {
+        let last = self.bookmarks.len().saturating_sub(1);
+        self.bookmark_list = self.bookmark_list.map(|i| {
+            if down {
+                (i + 1).min(last)
+            } else {
+                i.saturating_sub(1)
+            }
+        });
+    }
+
+    fn here(&self) -> HistoryEntry {
+        HistoryEntry {
+            page: self.dominant_page(),
+            text_scroll: self.text_scroll,
+        }
+    }
+
+    /// Jump to `page`, remembering where we were for `HistoryBack`.
+    fn jump_with_history(&mut self, page: usize) {
+        if page != self.dominant_page() {
+            let here = self.here();
+            push_history(&mut self.back_stack, here);
+            self.forward_stack.clear();
+        }
+        self.jump_to(page);
+    }
+
+    /// Step through the history: `back` pops from the back stack, saving the
+    /// current position on the forward stack, and vice versa.
+    fn step_history(&mut self, back: bool) {
+        let from = if back {
+            &mut self.back_stack
+        } else {
+            &mut self.forward_stack
+        };
+        let Some(entry) = from.pop() else {
+            self.flash(if back {
+                "Already at oldest position"
+            } else {
+                "Already at newest position"
+            });
+            return;
+        };
+        let here = self.here();
+        let to = if back {
+            &mut self.forward_stack
+        } else {
+            &mut self.back_stack
+        };
+        push_history(to, here);
+        self.jump_to(entry.page.min(self.page_count.saturating_sub(1)));
+        self.text_scroll = entry.text_scroll;
+    }
+
+    fn jump_to(&mut self, page: usize) {
+        let page = self.spread_start(page);
+        if page != self.current_page && !self.lock_view {
+            self.reset_pan();
+        }
+        self.current_page = page;
+        self.scroll_offset = 0.0;
+        self.text_scroll = 0;
+        self.text_hscroll = 0;
+        self.selected_link = None;
+    }
+
+    /// Turn `count` pages, or spreads when the cover stands alone, in one
+    /// jump, wrapping around the ends with `wrap_navigation`.
+    fn step_pages(&mut self, forward: bool, count: usize) {
+        if self.page_count == 0 {
+            return;
+        }
+        // Spreads are numbered 0 for the cover, then 1 for pages 1-2, ...
+        let book = self.book_spreads();
+        let spread_of = |page: usize| if book { page.div_ceil(2) } else { page };
+        let stops = spread_of(self.page_count - 1) + 1;
+        let here = spread_of(self.current_page).min(stops - 1);
+        let (stop, wrapped) = match (forward, self.wrap_navigation) {
+            (true, true) => ((here + count) % stops, here + count >= stops),
+            (true, false) => ((here + count).min(stops - 1), false),
+            (false, true) => ((here + stops - count % stops) % stops, count > here),
+            (false, false) => (here.saturating_sub(count), false),
+        };
+        self.jump_to(if book && stop > 0 { stop * 2 - 1 } else { stop });
+        if wrapped {
+            self.flash(if forward {
+                "Back to the first page"
+            } else {
+                "Wrapped to the last page"
+            });
+        }
+    }
+
+    /// Apply a pending count to `msg`. Motions are repeated, or jump to the
+    /// page given by the count; anything else just drops it.
+    fn update_counted(&mut self, msg: Message, count: usize) {
+        match msg {
+            Message::NextPage => self.step_pages(true, count),
+            Message::PrevPage => self.step_pages(false, count),
+            Message::ScrollUp | Message::ScrollDown => {
+                for _ in 0..count.min(REPEAT_LIMIT) {
+                    self.update(msg.clone());
+                    // Counted steps are all the same size
+                    self.pan_streak = None;
+                }
+            }
+            Message::FirstPage | Message::LastPage => {
+                self.jump_with_history(count.clamp(1, self.page_count) - 1);
+            }
+            _ => self.update(msg),
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn update(&mut self, msg: Message) {
+        if !matches!(msg, Message::StartMotion(_)) {
+            self.pending_motion = None;
+        }
+        if !matches!(msg, Message::Quit) {
+            self.quit_armed = None;
+        }
+        if let Some(count) = self.pending_count.take() {
+            if !matches!(msg, Message::CountDigit(_) | Message::StartMotion(_)) {
+                self.update_counted(msg, count);
+                return;
+            }
+            self.pending_count = Some(count);
+        }
+
+        match msg {
+            Message::CountDigit(d) => {
+                let count = self.pending_count.unwrap_or(0) * 10 + usize::from(d);
+                self.pending_count = Some(count.min(COUNT_LIMIT));
+            }
+            Message::ClearCount => self.pending_count = None,
+            Message::StartMotion(key) => {
+                self.pending_motion = Some((key, Instant::now() + MOTION_TIMEOUT));
+            }
+
+            Message::Quit
+                if self.present
+                    && self
+                        .quit_armed
+                        .is_none_or(|at| at.elapsed() > QUIT_CONFIRM_WINDOW) =>
+            {
+                // A stray key shouldn't end a talk
+                self.quit_armed = Some(Instant::now());
+                self.flash("Press q again to quit");
+            }
+            Message::Quit => self.should_quit = true,
+
+            Message::NextTab if self.tab_names.len() > 1 => {
+                self.tab_switch = Some(TabSwitch::Next);
+            }
+            Message::PrevTab if self.tab_names.len() > 1 => {
+                self.tab_switch = Some(TabSwitch::Prev);
+            }
+            Message::NextTab | Message::PrevTab => {}
+
+            Message::NextPage => self.step_pages(true, 1),
+            Message::PrevPage => self.step_pages(false, 1),
+            Message::FirstPage => {
+                self.jump_with_history(0);
             }
             Message::LastPage => {
-                self.current_page = self.page_count.saturating_sub(1);
+                self.jump_with_history(self.page_count.saturating_sub(1));
             }
 
             Message::ZoomIn => {
-                self.zoom = (self.zoom + ZOOM_STEP).min(4.0);
-                self.pending.clear();
-                self.reset_pan();
+                self.set_zoom((self.zoom + ZOOM_STEP).min(4.0));
+                self.repin_zoom();
             }
             Message::ZoomOut => {
-                self.zoom = (self.zoom - ZOOM_STEP).max(0.25);
-                self.pending.clear();
-                self.reset_pan();
+                self.set_zoom((self.zoom - ZOOM_STEP).max(0.25));
+                self.repin_zoom();
             }
             Message::ZoomReset => {
                 self.zoom = 1.0;
-                self.pending.clear();
+                self.cancel_renders();
                 self.reset_pan();
+                self.repin_zoom();
+            }
+            Message::TogglePinnedZoom => {
+                self.pinned_zoom = if self.pinned_zoom.is_some() {
+                    None
+                } else {
+                    Some(self.fit_scale(self.oriented_bounds()) * self.zoom)
+                };
+                self.flash(if self.pinned_zoom.is_some() {
+                    "Zoom pinned: pages keep this size"
+                } else {
+                    "Zoom follows the fit"
+                });
+            }
+            Message::ToggleCoverOffset => {
+                self.cover_offset = !self.cover_offset;
+                self.current_page = self.spread_start(self.current_page);
+                self.cache.invalidate_protocols();
+                if self.layout != PageLayout::Dual {
+                    self.flash(if self.cover_offset {
+                        "Cover page on (applies to 2-up layout)"
+                    } else {
+                        "Cover page off"
+                    });
+                }
+            }
+            Message::ToggleCompare => self.toggle_compare(),
+            Message::ToggleDimInactive => self.dim_inactive = !self.dim_inactive,
+            Message::ToggleSeparators => {
+                self.show_separators = !self.show_separators;
+                // Side by side, pages get a little narrower
+                self.cache.invalidate_protocols();
+            }
+            Message::ToggleMinimap => {
+                self.show_minimap = !self.show_minimap;
+                self.flash(if self.show_minimap {
+                    "Minimap on while part of the page is off screen"
+                } else {
+                    "Minimap off"
+                });
+            }
+            Message::CycleQuality => self.set_quality(self.quality.cycle()),
+            Message::ToggleAutoAdvance => {
+                if self.auto_advance_at.take().is_some() {
+                    self.flash("Paused");
+                } else {
+                    self.auto_advance_at = Some(Instant::now() + self.advance_interval);
+                    let secs = self.advance_interval.as_secs();
+                    self.flash(format!("Next page every {secs}s"));
+                }
+            }
+            Message::SwitchPane => match &mut self.compare {
+                Some(c) => {
+                    c.focus_left = !c.focus_left;
+                    self.swap_panes();
+                    self.selected_link = None;
+                }
+                None => self.flash("Not comparing pages"),
+            },
+            Message::RetryRender => {
+                let visible = self.on_screen();
+                let before = self.failed_pages.len();
+                self.failed_pages.retain(|idx, _| !visible.contains(idx));
+                if self.failed_pages.len() == before {
+                    self.flash("No failed page to retry");
+                }
+            }
+            Message::ToggleCacheStatus => self.show_cache_status = !self.show_cache_status,
+            Message::ToggleDebug => self.show_debug = !self.show_debug,
+            Message::ToggleClock => self.show_clock = !self.show_clock,
+            Message::ToggleLockView => {
+                self.lock_view = !self.lock_view;
+                self.flash(if self.lock_view {
+                    "View locked across pages"
+                } else {
+                    "View unlocked"
+                });
             }
+            Message::FitWidth => self.set_fit(FitMode::Width),
+            Message::FitHeight => self.set_fit(FitMode::Height),
 
             Message::ScrollUp => {
-                if self.zoom > 1.0 {
-                    self.pan_y = (self.pan_y - PAN_STEP).max(-1.0);
+                if self.text_mode {
+                    self.scroll_text_up(self.text_scroll_step);
+                } else if self.layout == PageLayout::Continuous {
+                    self.scroll_continuous(-CONTINUOUS_STEP);
+                } else if self.can_pan_vertically() {
+                    self.pan_y = (self.pan_y - self.pan_step((0, -1))).max(-1.0);
                 }
             }
             Message::ScrollDown => {
-                if self.zoom > 1.0 {
-                    self.pan_y = (self.pan_y + PAN_STEP).min(1.0);
+                if self.text_mode {
+                    self.scroll_text_down(self.text_scroll_step);
+                } else if self.layout == PageLayout::Continuous {
+                    self.scroll_continuous(CONTINUOUS_STEP);
+                } else if self.can_pan_vertically() {
+                    self.pan_y = (self.pan_y + self.pan_step((0, 1))).min(1.0);
                 }
             }
+            Message::PanTop => self.pan_to_edge(false),
+            Message::PanBottom => self.pan_to_edge(true),
             Message::ScrollLeft => {
-                if self.zoom > 1.0 {
-                    self.pan_x = (self.pan_x - PAN_STEP).max(-1.0);
+                if self.text_mode {
+                    self.text_hscroll = self.text_hscroll.saturating_sub(TEXT_HSCROLL_STEP);
+                } else if self.can_pan_horizontally() {
+                    self.pan_x = (self.pan_x - self.pan_step((-1, 0))).max(-1.0);
                 }
             }
             Message::ScrollRight => {
-                if self.zoom > 1.0 {
-                    self.pan_x = (self.pan_x + PAN_STEP).min(1.0);
+                // Clamped to the longest line when drawn
+                if self.text_mode {
+                    self.text_hscroll += TEXT_HSCROLL_STEP;
+                } else if self.can_pan_horizontally() {
+                    self.pan_x = (self.pan_x + self.pan_step((1, 0))).min(1.0);
                 }
             }
 
             Message::CycleLayout => {
+                self.compare = None;
                 self.layout = self.layout.cycle();
+                self.current_page = self.spread_start(self.current_page);
+                self.scroll_offset = 0.0;
                 self.cache.invalidate_protocols();
+                self.cancel_renders();
+            }
+            Message::ToggleDarkMode => {
+                self.color_mode = if self.color_mode.is_dark() {
+                    ColorMode::Normal
+                } else {
+                    ColorMode::Night
+                };
+            }
+            Message::CycleColorMode => self.color_mode = self.color_mode.cycle(),
+
+            Message::BrightnessUp => self.adjust_brightness(BRIGHTNESS_STEP),
+            Message::BrightnessDown => self.adjust_brightness(-BRIGHTNESS_STEP),
+            Message::ContrastUp => self.adjust_contrast(CONTRAST_STEP),
+            Message::ContrastDown => self.adjust_contrast(-CONTRAST_STEP),
+            Message::ToggleAutoCrop => {
+                self.autocrop = !self.autocrop;
+                self.cache.set_autocrop(self.autocrop);
+                self.cancel_renders();
+            }
+            Message::CycleLens => {
+                self.lens = self.lens.cycle();
+                self.cache.set_lens(self.lens);
+                self.cancel_renders();
+                self.reset_pan();
+            }
+            Message::ResetAdjustments => {
+                self.brightness = 0;
+                self.contrast = 0.0;
+                self.cache.set_adjustments(0, 0.0);
             }
-            Message::ToggleDarkMode => self.dark_mode = !self.dark_mode,
             Message::ToggleFullscreen => {
                 self.fullscreen = !self.fullscreen;
+                // The old renders stand in until the resized ones arrive
+                self.cache.invalidate_protocols();
+                self.cancel_renders();
+            }
+            Message::ToggleStatusBar => {
+                self.show_status_bar = !self.show_status_bar;
+                self.cache.invalidate_protocols();
+                self.cancel_renders();
+            }
+
+            Message::ToggleSplitText | Message::ToggleTextMode
+                if !self.ocr && !self.pdf.has_text() =>
+            {
+                self.flash("No text in this document");
+            }
+            Message::ToggleTextMode if !self.text_mode && self.looks_scanned() => {
+                self.flash(SCANNED_HINT);
+            }
+            Message::ToggleSplitText if !self.split_text && self.looks_scanned() => {
+                self.flash(SCANNED_HINT);
+            }
+            Message::ToggleSplitText => {
+                self.split_text = !self.split_text;
+                self.text_scroll = 0;
+                // Pages are re-rendered for their new width, like on a resize
+                self.cache.invalidate_protocols();
+                self.cancel_renders();
+            }
+            Message::TextScrollUp if self.text_mode => self.scroll_text_up(self.text_scroll_step),
+            Message::TextScrollDown if self.text_mode => {
+                self.scroll_text_down(self.text_scroll_step);
+            }
+            Message::TextScrollUp => {
+                self.text_scroll = self.text_scroll.saturating_sub(self.text_scroll_step);
+            }
+            // Clamped to the end of the text when drawn
+            Message::TextScrollDown => {
+                self.text_scroll = self.text_scroll.saturating_add(self.text_scroll_step);
+            }
+            Message::TextPageDown if self.text_mode => {
+                // One line of the last screen stays for context
+                let screen = usize::from(self.text_pane.1).saturating_sub(1).max(1);
+                self.scroll_text_down(screen);
+            }
+            Message::TextPageDown => self.update(Message::NextPage),
+            Message::ToggleTextMode => {
+                let page = self.dominant_page();
+                if self.text_mode {
+                    self.text_positions
+                        .insert(page, (self.text_scroll, self.text_hscroll));
+                }
+                self.text_mode = !self.text_mode;
+                self.jump_to(page);
+                if self.text_mode {
+                    if let Some(&(scroll, hscroll)) = self.text_positions.get(&page) {
+                        self.text_scroll = scroll;
+                        self.text_hscroll = hscroll;
+                    }
+                }
+            }
+            Message::ToggleFields => self.show_fields = !self.show_fields,
+            Message::ToggleAnnotations => {
+                self.show_annotations = !self.show_annotations;
                 self.cache.clear();
-                self.pending.clear();
+                self.cache.clear_thumbnails();
+                self.cancel_renders();
+                self.pending_thumbnails.clear();
+                self.flash(if self.show_annotations {
+                    "Annotations shown"
+                } else {
+                    "Annotations hidden"
+                });
+            }
+            Message::ToggleReflow => {
+                self.text_reflow = !self.text_reflow;
+                self.text_positions.clear();
+                self.text_scroll = 0;
+                self.text_hscroll = 0;
+            }
+            Message::ToggleTextStyles => {
+                self.text_styles = !self.text_styles;
+                self.flash(if self.text_styles {
+                    "Headings styled"
+                } else {
+                    "Plain text"
+                });
+            }
+
+            Message::RotateCW => self.rotate(90),
+            Message::RotateCCW => self.rotate(270),
+
+            Message::ShowInfo => {
+                self.info = Some(self.pdf.metadata());
+                self.file_size = self.source.size();
+            }
+            Message::HideInfo => self.info = None,
+
+            Message::ToggleThumbnails => {
+                self.thumbnails = !self.thumbnails;
+                if self.thumbnails {
+                    self.thumbnail_selected = self.dominant_page();
+                    self.scroll_thumbnail_into_view();
+                }
+            }
+            Message::ThumbnailLeft => self.move_thumbnail(-1),
+            Message::ThumbnailRight => self.move_thumbnail(1),
+            Message::ThumbnailUp => {
+                let cols = self.thumbnail_grid().0 as isize;
+                self.move_thumbnail(-cols);
+            }
+            Message::ThumbnailDown => {
+                let cols = self.thumbnail_grid().0 as isize;
+                self.move_thumbnail(cols);
+            }
+            Message::ThumbnailSelect => {
+                self.thumbnails = false;
+                self.jump_with_history(self.thumbnail_selected);
             }
 
             Message::EnterGoto => {
                 self.goto_mode = true;
-                self.goto_input.clear();
+                self.prompt_input.clear();
             }
-            Message::GotoInput(c) => {
-                if self.goto_input.len() < 10 {
-                    self.goto_input.push(c);
+            Message::PromptInput(c) => {
+                let limit = if self.command_mode { 64 } else { 16 };
+                if self.prompt_input.len() < limit {
+                    self.prompt_input.push(c);
                 }
             }
-            Message::GotoBackspace => {
-                self.goto_input.pop();
+            Message::PromptBackspace => {
+                self.prompt_input.pop();
             }
             Message::GotoConfirm => {
-                if let Ok(page) = self.goto_input.parse::<usize>() {
-                    if page >= 1 && page <= self.page_count {
-                        self.current_page = page - 1;
-                    }
-                }
                 self.goto_mode = false;
-                self.goto_input.clear();
+                let input = std::mem::take(&mut self.prompt_input);
+                let input = input.trim();
+                // A printed page label wins over the physical page number
+                let page = self
+                    .page_labels
+                    .page_for_label(input, self.page_count)
+                    .or_else(|| {
+                        input
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|n| (1..=self.page_count).contains(n))
+                            .map(|n| n - 1)
+                    });
+                match page {
+                    Some(page) => self.jump_with_history(page),
+                    None if !input.is_empty() => self.flash(format!("No page '{input}'")),
+                    None => {}
+                }
             }
-            Message::GotoCancel => {
+            Message::PromptCancel => {
                 self.goto_mode = false;
-                self.goto_input.clear();
+                self.command_mode = false;
+                self.prompt_input.clear();
+            }
+
+            Message::EnterCommand => {
+                self.command_mode = true;
+                self.prompt_input.clear();
+            }
+            Message::CommandComplete => {
+                if let Some(completed) = command::complete(&self.prompt_input) {
+                    self.prompt_input = completed;
+                }
+            }
+            Message::CommandConfirm => {
+                self.command_mode = false;
+                let input = std::mem::take(&mut self.prompt_input);
+                match command::parse(&input) {
+                    // Typed out, so it needs no confirming even when presenting
+                    Ok(Message::Quit) => self.should_quit = true,
+                    Ok(msg) => self.update(msg),
+                    Err(e) => self.flash(e),
+                }
             }
+            Message::GotoPage(page) => {
+                if page < self.page_count {
+                    self.jump_with_history(page);
+                } else {
+                    self.flash(format!("No page {} (of {})", page + 1, self.page_count));
+                }
+            }
+            Message::SetRotation(degrees) => self.rotate((degrees + 360 - self.rotation) % 360),
+            Message::SetLayout(layout) => {
+                self.compare = None;
+                self.layout = layout;
+                self.current_page = self.spread_start(self.current_page);
+                self.scroll_offset = 0.0;
+                self.cache.invalidate_protocols();
+            }
+            Message::ExportPages(first, last) => self.export_pages(first, last),
+
+            Message::PasswordInput(c) => self.password_input.push(c),
+            Message::PasswordBackspace => {
+                self.password_input.pop();
+            }
+            Message::PasswordConfirm => self.unlock(),
+
+            Message::CopyText => self.copy_page_text(),
+            Message::CopyImage => self.copy_page_image(),
+            Message::CopyLink => {
+                // The fragment PDF viewers and browsers open at a page
+                let link = format!("{}#page={}", self.source.name(), self.dominant_page() + 1);
+                match self.clipboard.copy_text(&link) {
+                    Ok(()) => self.flash(format!("Copied {link}")),
+                    Err(e) => self.flash(format!("Copy failed: {e}")),
+                }
+            }
+            Message::ToggleSelection => self.toggle_selection(),
+            Message::MoveSelection(dx, dy) => self.move_selection(dx, dy),
+            Message::ResizeSelection(dw, dh) => self.resize_selection(dw, dh),
+            Message::CopySelection => self.copy_selection(),
+
+            // The wheel scrolls when there is something to scroll, otherwise turns pages
+            Message::WheelUp => {
+                let msg = if self.text_mode
+                    || self.layout == PageLayout::Continuous
+                    || self.can_pan_vertically()
+                {
+                    Message::ScrollUp
+                } else {
+                    Message::PrevPage
+                };
+                self.update(msg);
+            }
+            Message::WheelDown => {
+                let msg = if self.text_mode
+                    || self.layout == PageLayout::Continuous
+                    || self.can_pan_vertically()
+                {
+                    Message::ScrollDown
+                } else {
+                    Message::NextPage
+                };
+                self.update(msg);
+            }
+            Message::Click(col, row) => self.click(col, row),
+
+            Message::SetBookmark => {
+                if let Some(label) = self.next_bookmark_slot() {
+                    self.set_bookmark(label);
+                }
+            }
+            Message::EnterBookmarkLabel => self.bookmark_label_mode = true,
+            Message::BookmarkLabel(label) => {
+                self.bookmark_label_mode = false;
+                self.set_bookmark(label);
+            }
+            Message::ListBookmarks => {
+                let page = self.dominant_page();
+                let nearest = self.bookmarks.iter().rposition(|b| b.page <= page);
+                self.bookmark_list = Some(nearest.unwrap_or(0));
+            }
+            Message::BookmarkUp => self.move_bookmark_selection(false),
+            Message::BookmarkDown => self.move_bookmark_selection(true),
+            Message::BookmarkSelect => {
+                let selected = self.bookmark_list.and_then(|i| self.bookmarks.get(i));
+                if let Some(&Bookmark { page, .. }) = selected {
+                    self.bookmark_list = None;
+                    self.jump_with_history(page.min(self.page_count.saturating_sub(1)));
+                }
+            }
+            Message::BookmarkDelete => {
+                if let Some(i) = self.bookmark_list.filter(|&i| i < self.bookmarks.len()) {
+                    self.bookmarks.remove(i);
+                    self.bookmark_list = Some(i.min(self.bookmarks.len().saturating_sub(1)));
+                    self.save_session();
+                }
+            }
+            Message::CloseBookmarks => {
+                self.bookmark_label_mode = false;
+                self.bookmark_list = None;
+            }
+
+            Message::HistoryBack => self.step_history(true),
+            Message::HistoryForward => self.step_history(false),
+
+            Message::NextLink => self.cycle_link(true),
+            Message::PrevLink => self.cycle_link(false),
+            Message::FollowLink => {
+                if let Some((_, target)) = self.selected_link.and_then(|i| self.link_hits.get(i)) {
+                    self.follow_link(target.clone());
+                }
+            }
+        }
+    }
+
+    /// Switch render quality. Pages are only rendered again if that changes
+    /// the anti-aliasing; otherwise rescaling them with the new filter does.
+    fn set_quality(&mut self, quality: Quality) {
+        if quality.antialias() != self.quality.antialias() {
+            self.cache.clear();
+            self.cache.clear_thumbnails();
+            self.cancel_renders();
+            self.pending_thumbnails.clear();
+        }
+        self.quality = quality;
+        self.cache.set_filter(quality.filter());
+        self.flash(format!("Quality: {}", quality.name()));
+    }
+
+    /// Turn the page because the auto-advance timer ran out, stopping at
+    /// the end unless looping.
+    fn auto_advance(&mut self) {
+        // Hold off while a prompt or overlay is up
+        if self.in_prompt() {
+            self.auto_advance_at = Some(Instant::now() + self.advance_interval);
+            return;
+        }
+        let last = self.page_count.saturating_sub(1);
+        if self.current_page + self.spread_len() > last {
+            if !self.loop_pages {
+                self.auto_advance_at = None;
+                self.flash("End of document");
+                return;
+            }
+            self.jump_to(0);
+        } else {
+            self.update(Message::NextPage);
+        }
+        self.auto_advance_at = Some(Instant::now() + self.advance_interval);
+    }
+
+    /// Split the view into two independent panes, the current page pinned
+    /// on the left and the focus on the right, or go back to the old layout.
+    fn toggle_compare(&mut self) {
+        if let Some(c) = self.compare.take() {
+            self.layout = c.layout;
+            self.current_page = self.spread_start(self.current_page);
+            self.cache.invalidate_protocols();
+            return;
+        }
+        let page = self.dominant_page();
+        self.compare = Some(Compare {
+            other: PaneView {
+                page,
+                zoom: self.zoom,
+                pan_x: self.pan_x,
+                pan_y: self.pan_y,
+            },
+            focus_left: false,
+            layout: self.layout,
+        });
+        self.layout = PageLayout::Dual;
+        self.cache.invalidate_protocols();
+        self.jump_to((page + 1).min(self.page_count.saturating_sub(1)));
+    }
+
+    /// Trade the focused compare pane's position for the other pane's. The
+    /// view draws the unfocused pane by swapping it in and back out.
+    pub(crate) fn swap_panes(&mut self) {
+        let Some(c) = &mut self.compare else {
+            return;
+        };
+        let other = &mut c.other;
+        std::mem::swap(&mut self.current_page, &mut other.page);
+        std::mem::swap(&mut self.zoom, &mut other.zoom);
+        std::mem::swap(&mut self.pan_x, &mut other.pan_x);
+        std::mem::swap(&mut self.pan_y, &mut other.pan_y);
+        self.cache.swap_panes();
+    }
+
+    /// Try the typed password against the locked document and, on success,
+    /// load it and start the render workers.
+    fn unlock(&mut self) {
+        let password = std::mem::take(&mut self.password_input);
+        if !self.pdf.authenticate(&password) {
+            self.status_message = Some("Incorrect password".into());
+            return;
+        }
+
+        match document_info(self.pdf.as_ref()) {
+            Ok((page_count, page_bounds)) => {
+                self.page_count = page_count;
+                self.page_bounds = page_bounds;
+                self.page_labels = self.pdf.page_labels().unwrap_or_default();
+                self.current_page = self.current_page.min(page_count - 1);
+            }
+            Err(e) => {
+                self.status_message = Some(e.to_string());
+                self.should_quit = true;
+                return;
+            }
+        }
+
+        (self.render_tx, self.render_rx) = spawn_render_workers(
+            &self.source,
+            Some(&password),
+            self.render_threads,
+            None,
+            Arc::clone(&self.render_epoch),
+        );
+        self.password_mode = false;
+        self.status_message = None;
+
+        if let Some(name) = self.locked_dest.take() {
+            if let Some(page) = self.pdf.resolve_named_dest(&name) {
+                self.current_page = page.min(self.page_count - 1);
+            } else {
+                self.current_page = 0;
+                self.flash(missing_dest(&name));
+            }
+        }
+    }
+
+    /// Render pages `first..=last` to PNG files in the working directory,
+    /// named after the document.
+    fn export_pages(&mut self, first: usize, last: usize) {
+        if last >= self.page_count {
+            self.flash(format!("No page {} (of {})", last + 1, self.page_count));
+            return;
+        }
+        let stem = self.file_stem();
+
+        let scale = self.dpi.map_or(EXPORT_SCALE, dpi_scale);
+        for idx in first..=last {
+            let file = format!("{stem}-{}.png", idx + 1);
+            let saved = self
+                .pdf
+                .render_page(idx, scale, self.rotation, self.show_annotations)
+                .map_err(|e| e.to_string())
+                .and_then(|img| img.save(&file).map_err(|e| e.to_string()));
+            if let Err(e) = saved {
+                self.flash(format!("Export failed at page {}: {e}", idx + 1));
+                return;
+            }
+        }
+        let count = last - first + 1;
+        self.flash(format!(
+            "Exported {count} page{} to {stem}-*.png",
+            if count == 1 { "" } else { "s" }
+        ));
+    }
+
+    /// Reopen the document after it changed on disk, keeping the reading
+    /// position where the new page count allows.
+    fn reload(&mut self) {
+        let password = self.pdf.password().map(str::to_string);
+        let open = || -> Result<_, Box<dyn std::error::Error>> {
+            let pdf = match document::open(&self.source, password.as_deref()) {
+                Ok(pdf) => pdf,
+                Err(OpenError::PasswordRequired(_)) => return Err("password not accepted".into()),
+                Err(OpenError::Mupdf(e)) => return Err(self.source.open_failure(&e).into()),
+                Err(OpenError::Images(e)) => return Err(e.into()),
+            };
+            let info = document_info(pdf.as_ref())?;
+            Ok((pdf, info))
+        };
+
+        let mut result = open();
+        for _ in 0..RELOAD_RETRIES {
+            if result.is_ok() {
+                break;
+            }
+            std::thread::sleep(RELOAD_RETRY_DELAY);
+            result = open();
+        }
+        let (pdf, (page_count, page_bounds)) = match result {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.flash(format!("Reload failed: {e}"));
+                return;
+            }
+        };
+
+        self.page_labels = pdf.page_labels().unwrap_or_default();
+        self.pdf = pdf;
+        self.page_count = page_count;
+        self.page_bounds = page_bounds;
+        self.page_sizes.clear();
+        self.text_positions.clear();
+        self.current_page = self.current_page.min(page_count - 1);
+        if let Some(c) = &mut self.compare {
+            c.other.page = c.other.page.min(page_count - 1);
+        }
+        self.thumbnail_selected = self.thumbnail_selected.min(page_count - 1);
+        self.selected_link = None;
+        if self.file_size.is_some() {
+            self.file_size = self.source.size();
+        }
+        self.cache.reset();
+        self.failed_pages.clear();
+        self.cancel_renders();
+        self.pending_thumbnails.clear();
+        // The OCR worker reads the old file; a new one starts when needed
+        self.ocr_worker = None;
+        self.ocr_pending.clear();
+        (self.render_tx, self.render_rx) = spawn_render_workers(
+            &self.source,
+            password.as_deref(),
+            self.render_threads,
+            self.disk_cache,
+            Arc::clone(&self.render_epoch),
+        );
+        self.flash("Reloaded");
+    }
+}
+
+/// Render scale for a resolution in dots per inch; PDF units are points.
+#[must_use]
+pub fn dpi_scale(dpi: u32) -> f32 {
+    dpi as f32 / 72.0
+}
+
+fn missing_dest(name: &str) -> String {
+    format!("No destination '{name}', starting at page 1")
+}
+
+/// When the wall clock next reaches a whole minute.
+fn next_minute() -> Instant {
+    let second = u64::from(chrono::Local::now().second());
+    Instant::now() + Duration::from_secs(60 - second.min(59))
+}
+
+fn push_history(stack: &mut Vec<HistoryEntry>, entry: HistoryEntry) {
+    if stack.len() == HISTORY_LIMIT {
+        stack.remove(0);
+    }
+    stack.push(entry);
+}
+
+/// Hand an external link to the platform's default opener.
+fn open_uri(uri: &str) -> io::Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(opener)
+        .arg(uri)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+fn document_info(
+    pdf: &dyn DocumentSource,
+) -> Result<(usize, (f32, f32)), Box<dyn std::error::Error>> {
+    let page_count = pdf.page_count();
+    if page_count == 0 {
+        return Err("Document has no pages".into());
+    }
+    let page_bounds = pdf.page_bounds(0).unwrap_or((612.0, 792.0));
+    Ok((page_count, page_bounds))
+}
+
+/// Render threads used unless configured: one per core, up to 4.
+#[must_use]
+pub fn default_render_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get().min(4))
+        .unwrap_or(2)
+}
+
+/// Start a thread that reads pages with tesseract one at a time, rendering
+/// them from its own handle on the document.
+fn spawn_ocr_worker(
+    source: &Source,
+    password: Option<&str>,
+) -> (mpsc::Sender<usize>, Receiver<OcrResult>) {
+    let (req_tx, req_rx) = mpsc::channel::<usize>();
+    let (res_tx, res_rx) = mpsc::channel::<OcrResult>();
+    let source = source.clone();
+    let pw = password.map(str::to_string);
+    std::thread::spawn(move || {
+        let pdf = document::open(&source, pw.as_deref()).map_err(|e| e.to_string());
+        for idx in req_rx {
+            let text = pdf.as_ref().map_err(Clone::clone).and_then(|pdf| {
+                pdf.render_page(idx, OCR_SCALE, 0, false)
+                    .and_then(|img| ocr::recognize(&img))
+                    .map_err(|e| e.to_string())
+            });
+            if res_tx.send(OcrResult { idx, text }).is_err() {
+                break;
+            }
+        }
+    });
+    (req_tx, res_rx)
+}
+
+/// Start `threads` workers rendering pages of `source`, checking the disk
+/// cache of `disk_cache` bytes first if it is on. Encrypted documents are
+/// never written to disk. Page requests from before the current `epoch`
+/// are dropped unrendered.
+fn spawn_render_workers(
+    source: &Source,
+    password: Option<&str>,
+    threads: usize,
+    disk_cache: Option<u64>,
+    epoch: Arc<AtomicU64>,
+) -> (queue::Sender<RenderRequest>, Receiver<RenderResult>) {
+    let (req_tx, req_rx) = queue::channel::<RenderRequest>();
+    let (res_tx, res_rx) = mpsc::channel::<RenderResult>();
+    let disk = disk_cache
+        .filter(|_| password.is_none())
+        .and_then(|budget| DiskCache::open(source, budget))
+        .map(Arc::new);
+
+    for _ in 0..threads {
+        let rx = req_rx.clone();
+        let tx = res_tx.clone();
+        let source = source.clone();
+        let pw = password.map(str::to_string);
+        let disk = disk.clone();
+        let epoch = Arc::clone(&epoch);
+        std::thread::spawn(move || {
+            let Ok(pdf) = document::open(&source, pw.as_deref()) else {
+                panic!("render worker: failed to open document");
+            };
+            while let Some(r) = rx.recv() {
+                if !r.thumbnail && r.epoch != epoch.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let key = RenderKey {
+                    idx: r.idx,
+                    scale: r.scale,
+                    rotation: r.rotation,
+                    antialias: r.antialias,
+                    annotations: r.annotations,
+                };
+                let stored = disk.as_ref().and_then(|d| d.load(&key));
+                let hit = stored.is_some();
+                if let (Some(scale), false) = (r.preview, hit) {
+                    pdf::set_antialiasing(r.antialias);
+                    if let Ok(img) = pdf.render_page(r.idx, scale, r.rotation, r.annotations) {
+                        let result = RenderResult {
+                            idx: r.idx,
+                            scale,
+                            rotation: r.rotation,
+                            antialias: r.antialias,
+                            annotations: r.annotations,
+                            thumbnail: false,
+                            preview: true,
+                            img: Ok(img),
+                        };
+                        if tx.send(result).is_err() {
+                            break;
+                        }
+                    }
+                }
+                let img = stored.map_or_else(
+                    || {
+                        pdf::set_antialiasing(r.antialias);
+                        pdf.render_page(r.idx, r.scale, r.rotation, r.annotations)
+                            .map_err(|e| e.to_string())
+                    },
+                    Ok,
+                );
+                // Store after sending, so the page isn't held up by
+                // PNG encoding
+                let to_store = match (&disk, &img) {
+                    (Some(_), Ok(img)) if !hit => Some(img.clone()),
+                    _ => None,
+                };
+                let result = RenderResult {
+                    idx: r.idx,
+                    scale: r.scale,
+                    rotation: r.rotation,
+                    antialias: r.antialias,
+                    annotations: r.annotations,
+                    thumbnail: r.thumbnail,
+                    preview: false,
+                    img,
+                };
+                if tx.send(result).is_err() {
+                    break;
+                }
+                if let (Some(disk), Some(img)) = (&disk, to_store) {
+                    disk.store(&key, &img);
+                }
+            }
+        });
+    }
+
+    (req_tx, res_rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+
+    /// Pages in the synthetic document, as many as a large scanned archive.
+    const HUGE_PAGE_COUNT: usize = 100_000;
+
+    fn config() -> AppConfig {
+        AppConfig {
+            color_mode: None,
+            default_color_mode: ColorMode::Normal,
+            fullscreen: false,
+            start_page: None,
+            dest: None,
+            scroll: None,
+            fit: None,
+            default_fit: FitMode::Page,
+            layout: None,
+            default_layout: PageLayout::Single,
+            cover: false,
+            paper: Paper::default(),
+            quality: Quality::High,
+            show_status_bar: true,
+            status_segments: StatusSegments::FULL,
+            no_color: false,
+            ocr: false,
+            preload: DEFAULT_PRELOAD,
+            render_threads: 1,
+            disk_cache: None,
+            dpi: None,
+            scale_factor: 1.0,
+            password: None,
+            resume: false,
+            session_key: None,
+            cache_budget: 0,
+            watch: false,
+            present: false,
+            advance_interval: Duration::from_secs(5),
+            loop_pages: false,
+            wrap_navigation: false,
+            vim_motions: false,
+            text_scroll: DEFAULT_TEXT_SCROLL,
+            separators: false,
+            separator_width: DEFAULT_SEPARATOR_WIDTH,
+            separator_color: None,
+            keymap: Keymap::default(),
+            notice: None,
+        }
+    }
+
+    /// A comic archive of `HUGE_PAGE_COUNT` one-pixel pages, opened in an
+    /// 80x24 terminal.
+    fn huge_app(config: AppConfig) -> App {
+        let mut page = Vec::new();
+        DynamicImage::new_luma8(1, 1)
+            .write_to(&mut Cursor::new(&mut page), image::ImageFormat::Png)
+            .unwrap();
+        let options =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..HUGE_PAGE_COUNT {
+            archive.start_file(format!("{i:06}.png"), options).unwrap();
+            archive.write_all(&page).unwrap();
         }
+        let source = Source::Memory {
+            data: archive.finish().unwrap().into_inner().into(),
+            magic: "cbz".to_string(),
+        };
+        let app = App::new(source, Picker::halfblocks(), 80, 24, config);
+        app.unwrap_or_else(|e| panic!("couldn't open the archive: {e}"))
+    }
+
+    fn type_count(app: &mut App, count: usize) {
+        for d in count.to_string().bytes() {
+            app.update(Message::CountDigit(d - b'0'));
+        }
+    }
+
+    fn goto(app: &mut App, input: &str) {
+        app.update(Message::EnterGoto);
+        for c in input.chars() {
+            app.update(Message::PromptInput(c));
+        }
+        app.update(Message::GotoConfirm);
+    }
+
+    #[test]
+    fn counted_motion_jumps_across_a_huge_document() {
+        let mut app = huge_app(config());
+        assert_eq!(app.page_count, HUGE_PAGE_COUNT);
+
+        type_count(&mut app, 99_999);
+        app.update(Message::NextPage);
+        assert_eq!(app.current_page, HUGE_PAGE_COUNT - 1);
+        type_count(&mut app, 1_000_000);
+        app.update(Message::PrevPage);
+        assert_eq!(app.current_page, 0);
+        type_count(&mut app, 50_000);
+        app.update(Message::LastPage);
+        assert_eq!(app.current_page, 49_999);
+    }
+
+    #[test]
+    fn counted_motion_wraps_once_past_the_end() {
+        let mut app = huge_app(AppConfig {
+            wrap_navigation: true,
+            ..config()
+        });
+        type_count(&mut app, HUGE_PAGE_COUNT + 1);
+        app.update(Message::NextPage);
+        assert_eq!(app.current_page, 1);
+        type_count(&mut app, 3);
+        app.update(Message::PrevPage);
+        assert_eq!(app.current_page, HUGE_PAGE_COUNT - 2);
+    }
+
+    #[test]
+    fn goto_reaches_any_page_of_a_huge_document() {
+        let mut app = huge_app(config());
+        goto(&mut app, "99999");
+        assert_eq!(app.current_page, 99_998);
+        goto(&mut app, "100001");
+        assert_eq!(app.current_page, 99_998);
+        goto(&mut app, "1");
+        assert_eq!(app.current_page, 0);
+    }
+
+    #[test]
+    fn prewarm_stays_near_the_current_page() {
+        let mut app = huge_app(config());
+        goto(&mut app, "50000");
+        app.request_visible_pages();
+        let radius = app.preload_radius();
+        assert!(app.pending.len() <= 1 + 2 * radius);
+        assert!(app
+            .pending
+            .keys()
+            .all(|&idx| idx.abs_diff(app.current_page) <= radius));
+        // Nothing has been rendered yet, so there's nothing to prewarm
+        assert!(!app.has_nearby_unwarmed_protocol());
+        app.prewarm_one_nearby_protocol();
     }
 }
@@ -4,78 +4,537 @@ use image::DynamicImage;
 use ratatui::layout::Rect;
 use ratatui_image::{picker::Picker, protocol::Protocol, FilterType, Resize};
 
+use crate::app::{ColorMode, Lens};
+use crate::dark::{self, Paper};
+use crate::pdf::{self, FieldInfo, LinkInfo, NoteInfo, Region};
+use crate::text::Row;
+
+/// How far a pixel may stray from the page background and still count as
+/// margin when auto-cropping (absorbs scan noise and JPEG artifacts).
+const AUTOCROP_THRESHOLD: u8 = 24;
+
+/// Memory budget when none is given.
+pub const DEFAULT_BUDGET: usize = 256 * 1024 * 1024;
+
+/// Budget eviction never goes below this many pages, so the pages on screen
+/// in multi-page layouts don't evict each other.
+const MIN_RESIDENT_PAGES: usize = 3;
+
+/// How much of its brightness a dimmed page keeps.
+const DIM_FACTOR: f32 = 0.55;
+
+/// A page's protocol with what it was built for.
+struct CachedProtocol {
+    protocol: Protocol,
+    clip: Option<Region>,
+    dim: bool,
+    /// Approximate size in bytes.
+    bytes: usize,
+}
+
+/// The protocols of the unfocused compare pane, set aside with the zoom
+/// and pan they were built at while the focused pane is drawn.
+struct PaneProtocols {
+    protocols: HashMap<(usize, ColorMode), CachedProtocol>,
+    zoom: f32,
+    pan: (f32, f32),
+}
+
+/// Rendered pages and what is derived from them.
+///
+/// That is color-mode variants, terminal image protocols, text, links and
+/// thumbnails. Renders are kept within a byte budget, dropping the least
+/// recently used pages first.
 pub struct PageCache {
     images: HashMap<usize, DynamicImage>,
     image_scales: HashMap<usize, f32>,
-    inverted: HashMap<usize, DynamicImage>,
-    protocols: HashMap<(usize, bool), Protocol>,
+    /// Color-mode variants of `images` (inverted, grayscale, ...), built on demand.
+    filtered: HashMap<(usize, ColorMode), DynamicImage>,
+    protocols: HashMap<(usize, ColorMode), CachedProtocol>,
+    texts: HashMap<usize, String>,
+    /// Text mode lines per page, tagged with the width and reflow setting
+    /// they were wrapped for.
+    wrapped: HashMap<usize, (u16, bool, Vec<Row>)>,
+    links: HashMap<usize, Vec<LinkInfo>>,
+    fields: HashMap<usize, Vec<FieldInfo>>,
+    notes: HashMap<usize, Vec<NoteInfo>>,
+    /// Small renders for the overview grid, independent of zoom and layout.
+    thumbnails: HashMap<usize, DynamicImage>,
+    thumbnail_protocols: HashMap<(usize, ColorMode), (Protocol, Rect)>,
+    /// Content bounding boxes, only maintained while auto-crop is on.
+    bboxes: HashMap<usize, Region>,
+    autocrop: bool,
+    lens: Lens,
+    /// Where each page's columns divide, while the lens shows one of them.
+    gutters: HashMap<usize, Option<f32>>,
     current_zoom: f32,
     current_pan: (f32, f32),
+    other_pane: PaneProtocols,
+    brightness: i32,
+    contrast: f32,
+    paper: Paper,
+    /// Filter used to scale images to the size of their protocol.
+    filter: FilterType,
+    /// Approximate bytes the rendered data may occupy before the least
+    /// recently used pages are dropped.
+    budget: usize,
+    /// Approximate bytes held by the images and everything derived from
+    /// them, kept up to date as they come and go.
+    used: usize,
+    /// Last access time of each page, in `clock` ticks.
+    last_used: HashMap<usize, u64>,
+    clock: u64,
 }
 
 impl PageCache {
-    pub fn new() -> Self {
+    /// An empty cache that holds about `budget` bytes of rendered data.
+    #[must_use]
+    pub fn with_budget(budget: usize) -> Self {
         Self {
             images: HashMap::new(),
             image_scales: HashMap::new(),
-            inverted: HashMap::new(),
+            filtered: HashMap::new(),
             protocols: HashMap::new(),
+            texts: HashMap::new(),
+            wrapped: HashMap::new(),
+            links: HashMap::new(),
+            fields: HashMap::new(),
+            notes: HashMap::new(),
+            thumbnails: HashMap::new(),
+            thumbnail_protocols: HashMap::new(),
+            bboxes: HashMap::new(),
+            autocrop: false,
+            lens: Lens::Full,
+            gutters: HashMap::new(),
             current_zoom: 1.0,
             current_pan: (0.0, 0.0),
+            other_pane: PaneProtocols {
+                protocols: HashMap::new(),
+                zoom: 1.0,
+                pan: (0.0, 0.0),
+            },
+            brightness: 0,
+            contrast: 0.0,
+            paper: Paper::default(),
+            filter: FilterType::CatmullRom,
+            budget,
+            used: 0,
+            last_used: HashMap::new(),
+            clock: 0,
         }
     }
 
+    /// Forget everything about the document, e.g. after it was reloaded,
+    /// keeping only the view settings.
+    pub fn reset(&mut self) {
+        *self = Self {
+            autocrop: self.autocrop,
+            lens: self.lens,
+            current_zoom: self.current_zoom,
+            current_pan: self.current_pan,
+            brightness: self.brightness,
+            contrast: self.contrast,
+            paper: self.paper,
+            filter: self.filter,
+            ..Self::with_budget(self.budget)
+        };
+    }
+
+    /// Drop all rendered data. Extracted text, links, form fields and notes don't
+    /// depend on the render size, so they are kept.
     pub fn clear(&mut self) {
         self.images.clear();
         self.image_scales.clear();
-        self.inverted.clear();
+        self.bboxes.clear();
+        self.gutters.clear();
+        self.filtered.clear();
         self.protocols.clear();
+        self.other_pane.protocols.clear();
+        self.used = 0;
+    }
+
+    /// Set the brightness/contrast applied before the color mode, dropping
+    /// every derived image if they changed.
+    pub fn set_adjustments(&mut self, brightness: i32, contrast: f32) {
+        if self.brightness != brightness || (self.contrast - contrast).abs() > f32::EPSILON {
+            self.brightness = brightness;
+            self.contrast = contrast;
+            self.retain_filtered(|_| false);
+            self.invalidate_protocols();
+        }
+    }
+
+    /// Set the paper colors the color modes map white and black to.
+    pub fn set_paper(&mut self, paper: Paper) {
+        if self.paper != paper {
+            self.paper = paper;
+            self.retain_filtered(|_| false);
+            self.invalidate_protocols();
+            self.thumbnail_protocols.clear();
+        }
+    }
+
+    pub fn set_filter(&mut self, filter: FilterType) {
+        if self.filter != filter {
+            self.filter = filter;
+            self.invalidate_protocols();
+            self.thumbnail_protocols.clear();
+        }
+    }
+
+    pub fn set_autocrop(&mut self, enabled: bool) {
+        if self.autocrop == enabled {
+            return;
+        }
+        self.autocrop = enabled;
+        self.invalidate_protocols();
+        self.bboxes.clear();
+        if enabled {
+            for (&idx, img) in &self.images {
+                self.bboxes
+                    .insert(idx, pdf::content_bbox(img, AUTOCROP_THRESHOLD));
+            }
+        }
+    }
+
+    pub fn set_lens(&mut self, lens: Lens) {
+        if self.lens == lens {
+            return;
+        }
+        self.lens = lens;
+        self.invalidate_protocols();
+        if lens.by_column() {
+            for (&idx, img) in &self.images {
+                self.gutters
+                    .entry(idx)
+                    .or_insert_with(|| pdf::column_gutter(img, AUTOCROP_THRESHOLD));
+            }
+        }
+    }
+
+    /// The part of the page being displayed, when auto-crop or the lens
+    /// leave some out: the lens region of the cropped page.
+    #[must_use]
+    pub fn content_box(&self, page_idx: usize) -> Option<Region> {
+        let bbox = self.bboxes.get(&page_idx).copied();
+        let half = |x, y, w, h| Region { x, y, w, h };
+        let lens = match self.lens {
+            Lens::Full => return bbox,
+            Lens::TopHalf => half(0.0, 0.0, 1.0, 0.5),
+            Lens::BottomHalf => half(0.0, 0.5, 1.0, 0.5),
+            Lens::LeftColumn | Lens::RightColumn => {
+                // Single-column pages are shown whole
+                let Some(&Some(gutter)) = self.gutters.get(&page_idx) else {
+                    return bbox;
+                };
+                let outer = bbox.unwrap_or(Region::FULL);
+                let split = ((gutter - outer.x) / outer.w).clamp(0.0, 1.0);
+                if self.lens == Lens::LeftColumn {
+                    half(0.0, 0.0, split, 1.0)
+                } else {
+                    half(split, 0.0, 1.0 - split, 1.0)
+                }
+            }
+        };
+        Some(lens.inside(bbox.unwrap_or(Region::FULL)))
     }
 
     pub fn invalidate_protocols(&mut self) {
-        self.protocols.clear();
+        self.retain_protocols(|_| false);
+    }
+
+    /// Trade the protocols built for the focused compare pane for the other
+    /// pane's, so each pane keeps its own while they differ in zoom or pan.
+    pub fn swap_panes(&mut self) {
+        let other = &mut self.other_pane;
+        std::mem::swap(&mut self.protocols, &mut other.protocols);
+        std::mem::swap(&mut self.current_zoom, &mut other.zoom);
+        std::mem::swap(&mut self.current_pan, &mut other.pan);
+    }
+
+    /// Keep the protocols of the pages `keep` picks out, in both compare
+    /// panes, dropping the rest.
+    fn retain_protocols(&mut self, keep: impl Fn(usize) -> bool) {
+        let used = &mut self.used;
+        let mut retain = |(k, _): &(usize, ColorMode), p: &mut CachedProtocol| {
+            let kept = keep(*k);
+            if !kept {
+                *used -= p.bytes;
+            }
+            kept
+        };
+        self.protocols.retain(&mut retain);
+        self.other_pane.protocols.retain(retain);
+    }
+
+    /// Keep the color-mode variants of the pages `keep` picks out.
+    fn retain_filtered(&mut self, keep: impl Fn(usize) -> bool) {
+        let used = &mut self.used;
+        self.filtered.retain(|&(k, _), img| {
+            let kept = keep(k);
+            if !kept {
+                *used -= img.as_bytes().len();
+            }
+            kept
+        });
+    }
+
+    /// Drop the protocols of one page, in both compare panes.
+    fn forget_protocols(&mut self, page_idx: usize) {
+        self.retain_protocols(|k| k != page_idx);
+    }
+
+    #[must_use]
+    pub fn has_protocol(&self, page_idx: usize, color_mode: ColorMode) -> bool {
+        self.protocols.contains_key(&(page_idx, color_mode))
+    }
+
+    fn touch(&mut self, page_idx: usize) {
+        self.clock += 1;
+        self.last_used.insert(page_idx, self.clock);
     }
 
-    pub fn has_protocol(&self, page_idx: usize, dark_mode: bool) -> bool {
-        self.protocols.contains_key(&(page_idx, dark_mode))
+    /// Drop least recently used pages until the cache fits its budget.
+    fn enforce_budget(&mut self) {
+        if self.used <= self.budget {
+            return;
+        }
+        self.last_used.retain(|k, _| self.images.contains_key(k));
+
+        let mut by_age: Vec<(u64, usize)> = self.last_used.iter().map(|(&k, &t)| (t, k)).collect();
+        by_age.sort_unstable();
+        let evictable = by_age.len().saturating_sub(MIN_RESIDENT_PAGES);
+        for &(_, idx) in &by_age[..evictable] {
+            if self.used <= self.budget {
+                break;
+            }
+            self.remove_page(idx);
+        }
+    }
+
+    fn remove_page(&mut self, page_idx: usize) {
+        if let Some(img) = self.images.remove(&page_idx) {
+            self.used -= img.as_bytes().len();
+        }
+        self.image_scales.remove(&page_idx);
+        self.bboxes.remove(&page_idx);
+        self.gutters.remove(&page_idx);
+        self.retain_filtered(|k| k != page_idx);
+        self.forget_protocols(page_idx);
+        self.last_used.remove(&page_idx);
     }
 
     /// Drop cached data for pages far from the current view.
     pub fn evict_distant(&mut self, current_page: usize, keep_range: usize) {
         let min = current_page.saturating_sub(keep_range);
         let max = current_page + keep_range;
-        self.images.retain(|&k, _| k >= min && k <= max);
+        let used = &mut self.used;
+        self.images.retain(|&k, img| {
+            let kept = k >= min && k <= max;
+            if !kept {
+                *used -= img.as_bytes().len();
+            }
+            kept
+        });
         self.image_scales.retain(|&k, _| k >= min && k <= max);
-        self.inverted.retain(|&k, _| k >= min && k <= max);
-        self.protocols.retain(|&(k, _), _| k >= min && k <= max);
+        self.retain_filtered(|k| k >= min && k <= max);
+        self.bboxes.retain(|&k, _| k >= min && k <= max);
+        self.gutters.retain(|&k, _| k >= min && k <= max);
+        self.retain_protocols(|k| k >= min && k <= max);
+    }
+
+    /// Number of pages with a rendered image in memory.
+    #[must_use]
+    pub fn cached_count(&self) -> usize {
+        self.images.len()
     }
 
+    /// Number of terminal image protocols built for the pages.
+    #[must_use]
+    pub fn protocol_count(&self) -> usize {
+        self.protocols.len() + self.other_pane.protocols.len()
+    }
+
+    /// Number of color-mode variants (inverted, grayscale, ...) kept.
+    #[must_use]
+    pub fn filtered_count(&self) -> usize {
+        self.filtered.len()
+    }
+
+    /// Approximate bytes of rendered data held, and the budget they are
+    /// kept within.
+    #[must_use]
+    pub fn memory(&self) -> (usize, usize) {
+        (self.used, self.budget)
+    }
+
+    /// The scale the page's image was rendered at, if it has one.
+    #[must_use]
+    pub fn image_scale(&self, page_idx: usize) -> Option<f32> {
+        self.image_scales.get(&page_idx).copied()
+    }
+
+    /// Whether the page's image was rendered at `scale`. Only decides
+    /// whether to re-render: an image at any scale is still shown, stretched,
+    /// until the sharp one replaces it.
+    #[must_use]
     pub fn has_image_at_scale(&self, page_idx: usize, scale: f32) -> bool {
         self.image_scales
             .get(&page_idx)
-            .is_some_and(|s| (s - scale).abs() < 0.01)
+            .is_some_and(|&s| same_scale(s, scale))
     }
 
     pub fn insert_image(&mut self, page_idx: usize, scale: f32, img: DynamicImage) {
-        self.protocols.remove(&(page_idx, false));
-        self.protocols.remove(&(page_idx, true));
-        self.inverted.remove(&page_idx);
-        self.images.insert(page_idx, img);
+        self.forget_protocols(page_idx);
+        self.retain_filtered(|k| k != page_idx);
+        if self.autocrop {
+            self.bboxes
+                .insert(page_idx, pdf::content_bbox(&img, AUTOCROP_THRESHOLD));
+        }
+        if self.lens.by_column() {
+            self.gutters
+                .insert(page_idx, pdf::column_gutter(&img, AUTOCROP_THRESHOLD));
+        } else {
+            self.gutters.remove(&page_idx);
+        }
+        self.used += img.as_bytes().len();
+        if let Some(old) = self.images.insert(page_idx, img) {
+            self.used -= old.as_bytes().len();
+        }
         self.image_scales.insert(page_idx, scale);
+        self.touch(page_idx);
+        self.enforce_budget();
     }
 
-    pub fn image_dims(&self, page_idx: usize) -> Option<(u32, u32)> {
-        self.images
+    /// Insert a quick low-resolution render to show until the full one
+    /// arrives. It never replaces a sharper image, which may have come in
+    /// first from another worker.
+    pub fn insert_preview(&mut self, page_idx: usize, scale: f32, img: DynamicImage) {
+        if self.image_scale(page_idx).is_some_and(|s| s >= scale) {
+            return;
+        }
+        self.insert_image(page_idx, scale, img);
+    }
+
+    pub fn page_text(&self, page_idx: usize) -> Option<&str> {
+        self.texts.get(&page_idx).map(String::as_str)
+    }
+
+    pub fn insert_text(&mut self, page_idx: usize, text: String) {
+        self.texts.insert(page_idx, text);
+    }
+
+    /// A page's text as wrapped for `width` columns, if that has been done.
+    #[must_use]
+    pub fn wrapped_text(&self, page_idx: usize, width: u16, reflow: bool) -> Option<&[Row]> {
+        self.wrapped
+            .get(&page_idx)
+            .filter(|(w, r, _)| *w == width && *r == reflow)
+            .map(|(_, _, lines)| lines.as_slice())
+    }
+
+    pub fn insert_wrapped_text(
+        &mut self,
+        page_idx: usize,
+        width: u16,
+        reflow: bool,
+        lines: Vec<Row>,
+    ) {
+        self.wrapped.insert(page_idx, (width, reflow, lines));
+    }
+
+    #[must_use]
+    pub fn has_thumbnail(&self, page_idx: usize) -> bool {
+        self.thumbnails.contains_key(&page_idx)
+    }
+
+    pub fn insert_thumbnail(&mut self, page_idx: usize, img: DynamicImage) {
+        self.thumbnail_protocols.retain(|&(k, _), _| k != page_idx);
+        self.thumbnails.insert(page_idx, img);
+    }
+
+    pub fn clear_thumbnails(&mut self) {
+        self.thumbnails.clear();
+        self.thumbnail_protocols.clear();
+    }
+
+    #[must_use]
+    pub fn thumbnail_dims(&self, page_idx: usize) -> Option<(u32, u32)> {
+        self.thumbnails
             .get(&page_idx)
             .map(|img| (img.width(), img.height()))
     }
 
+    /// Protocol for a page's thumbnail in `color_mode`, sized for `area`.
+    pub fn get_thumbnail_protocol(
+        &mut self,
+        page_idx: usize,
+        color_mode: ColorMode,
+        picker: &Picker,
+        area: Rect,
+    ) -> Option<&Protocol> {
+        let key = (page_idx, color_mode);
+        let stale = self
+            .thumbnail_protocols
+            .get(&key)
+            .is_none_or(|(_, a)| (a.width, a.height) != (area.width, area.height));
+        if stale {
+            let img = self.thumbnails.get(&page_idx)?;
+            let img = dark::apply(color_mode, img, self.paper).unwrap_or_else(|| img.clone());
+            let protocol = picker
+                .new_protocol(img, area, Resize::Fit(Some(self.filter)))
+                .ok()?;
+            self.thumbnail_protocols.insert(key, (protocol, area));
+        }
+        self.thumbnail_protocols.get(&key).map(|(p, _)| p)
+    }
+
+    pub fn page_links(&self, page_idx: usize) -> Option<&[LinkInfo]> {
+        self.links.get(&page_idx).map(Vec::as_slice)
+    }
+
+    pub fn insert_links(&mut self, page_idx: usize, links: Vec<LinkInfo>) {
+        self.links.insert(page_idx, links);
+    }
+
+    pub fn page_fields(&self, page_idx: usize) -> Option<&[FieldInfo]> {
+        self.fields.get(&page_idx).map(Vec::as_slice)
+    }
+
+    pub fn insert_fields(&mut self, page_idx: usize, fields: Vec<FieldInfo>) {
+        self.fields.insert(page_idx, fields);
+    }
+
+    pub fn page_notes(&self, page_idx: usize) -> Option<&[NoteInfo]> {
+        self.notes.get(&page_idx).map(Vec::as_slice)
+    }
+
+    pub fn insert_notes(&mut self, page_idx: usize, notes: Vec<NoteInfo>) {
+        self.notes.insert(page_idx, notes);
+    }
+
+    /// Size of the displayed image, i.e. after any auto-crop and lens.
+    #[must_use]
+    pub fn image_dims(&self, page_idx: usize) -> Option<(u32, u32)> {
+        let img = self.images.get(&page_idx)?;
+        let (w, h) = (img.width(), img.height());
+        Some(self.content_box(page_idx).map_or((w, h), |b| {
+            (
+                (w as f32 * b.w).round() as u32,
+                (h as f32 * b.h).round() as u32,
+            )
+        }))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn get_protocol(
         &mut self,
         page_idx: usize,
-        dark_mode: bool,
+        color_mode: ColorMode,
         zoom: f32,
         pan: (f32, f32),
+        clip: Option<Region>,
+        dim: bool,
         picker: &Picker,
         area: Rect,
     ) -> Option<&Protocol> {
@@ -85,52 +544,173 @@ impl PageCache {
                 || (self.current_pan.1 - pan.1).abs() > f32::EPSILON);
 
         if zoom_changed || pan_changed {
-            self.protocols.clear();
+            self.used -= self.protocols.drain().map(|(_, p)| p.bytes).sum::<usize>();
             self.current_zoom = zoom;
             self.current_pan = pan;
         }
 
-        let key = (page_idx, dark_mode);
-        if !self.protocols.contains_key(&key) {
-            let base_img = if dark_mode {
-                if !self.inverted.contains_key(&page_idx) {
-                    let normal = self.images.get(&page_idx)?;
-                    let mut inv = normal.clone();
-                    inv.invert();
-                    self.inverted.insert(page_idx, inv);
+        let key = (page_idx, color_mode);
+        self.touch(page_idx);
+        if self
+            .protocols
+            .get(&key)
+            .is_none_or(|p| p.clip != clip || p.dim != dim)
+        {
+            // Zoomed in, only the part on screen needs filtering, so it is
+            // cropped first unless the whole page has been filtered already.
+            // Dark paper looks for the paper along the page's edges, which a
+            // crop doesn't have, so it always filters the whole page.
+            let filter_first = zoom <= 1.0
+                || color_mode == ColorMode::DarkPaper
+                || self.filtered.contains_key(&key);
+            if filter_first && !self.filtered.contains_key(&key) {
+                let normal = self.images.get(&page_idx)?;
+                if let Some(filtered) = self.filter(color_mode, normal) {
+                    self.used += filtered.as_bytes().len();
+                    self.filtered.insert(key, filtered);
                 }
-                self.inverted.get(&page_idx)?
+            }
+            let base_img = match self.filtered.get(&key) {
+                Some(img) => img,
+                None => self.images.get(&page_idx)?,
+            };
+            let cropped = self
+                .content_box(page_idx)
+                .map(|content| crop_clip(base_img, content));
+            let base_img = cropped.as_ref().unwrap_or(base_img);
+
+            let img = match clip {
+                Some(clip) => crop_clip(base_img, clip),
+                None if zoom > 1.0 => crop_with_pan(base_img, zoom, pan.0, pan.1),
+                None => base_img.clone(),
+            };
+            let img = if filter_first {
+                img
             } else {
-                self.images.get(&page_idx)?
+                self.filter(color_mode, &img).unwrap_or(img)
             };
+            let img = if dim { dimmed(&img) } else { img };
 
-            let img = if zoom > 1.0 {
-                crop_with_pan(base_img, zoom, pan.0, pan.1)
+            // A cropped page is smaller than the area it was rendered for, so
+            // let it scale up to fill the space freed by the margins or lens
+            let resize = if self.autocrop || self.lens != Lens::Full {
+                Resize::Scale(Some(self.filter))
             } else {
-                base_img.clone()
+                Resize::Fit(Some(self.filter))
             };
+            // Protocols hold at most one RGBA copy of the area they fill
+            let (fw, fh) = picker.font_size();
+            let bytes = usize::from(area.width)
+                * usize::from(fw)
+                * usize::from(area.height)
+                * usize::from(fh)
+                * 4;
+            let protocol = picker.new_protocol(img, area, resize).ok()?;
+            self.used += bytes;
+            let old = self.protocols.insert(
+                key,
+                CachedProtocol {
+                    protocol,
+                    clip,
+                    dim,
+                    bytes,
+                },
+            );
+            if let Some(old) = old {
+                self.used -= old.bytes;
+            }
+            self.enforce_budget();
+        }
+        self.protocols.get(&key).map(|p| &p.protocol)
+    }
 
-            let protocol = picker
-                .new_protocol(img, area, Resize::Fit(Some(FilterType::CatmullRom)))
-                .ok()?;
-            self.protocols.insert(key, protocol);
+    /// `img` adjusted and recolored for `color_mode`, or `None` when that
+    /// leaves it as it is. Every step but dark paper works pixel by pixel,
+    /// so filtering a crop gives the same pixels as cropping the filtered
+    /// page; dark paper picks its paper color from the whole image.
+    fn filter(&self, color_mode: ColorMode, img: &DynamicImage) -> Option<DynamicImage> {
+        let adjusted = self.adjust(img);
+        dark::apply(color_mode, adjusted.as_ref().unwrap_or(img), self.paper).or(adjusted)
+    }
+
+    /// Brightness/contrast-adjusted copy of `img`, or `None` when neutral.
+    fn adjust(&self, img: &DynamicImage) -> Option<DynamicImage> {
+        if self.brightness == 0 && self.contrast == 0.0 {
+            return None;
         }
-        self.protocols.get(&key)
+        Some(img.brighten(self.brightness).adjust_contrast(self.contrast))
     }
 }
 
-/// Crop a viewport-sized portion of the image for zoom, offset by pan.
-/// `pan_x`/`pan_y` range: `-1.0` (top/left) to `1.0` (bottom/right), `0.0` = center.
-fn crop_with_pan(img: &DynamicImage, zoom: f32, pan_x: f32, pan_y: f32) -> DynamicImage {
-    let (w, h) = (img.width(), img.height());
-    let crop_w = (w as f32 / zoom).round().max(1.0) as u32;
-    let crop_h = (h as f32 / zoom).round().max(1.0) as u32;
+/// A darkened copy of `img`, for pages drawn beside the current one.
+fn dimmed(img: &DynamicImage) -> DynamicImage {
+    let mut rgb = img.to_rgb8();
+    for px in rgb.pixels_mut() {
+        for c in &mut px.0 {
+            *c = (f32::from(*c) * DIM_FACTOR) as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(rgb)
+}
 
-    let max_x = w.saturating_sub(crop_w);
-    let max_y = h.saturating_sub(crop_h);
+/// Crop the fractional region described by `clip` out of the image.
+fn crop_clip(img: &DynamicImage, clip: Region) -> DynamicImage {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let x = (clip.x * w).round() as u32;
+    let y = (clip.y * h).round() as u32;
+    let crop_w = (clip.w * w).round().max(1.0) as u32;
+    let crop_h = (clip.h * h).round().max(1.0) as u32;
 
-    let x = (pan_x.mul_add(0.5, 0.5) * max_x as f32).round() as u32;
-    let y = (pan_y.mul_add(0.5, 0.5) * max_y as f32).round() as u32;
+    let x = x.min(img.width().saturating_sub(crop_w));
+    let y = y.min(img.height().saturating_sub(crop_h));
+    img.crop_imm(x, y, crop_w, crop_h)
+}
+
+/// Whether two render scales are close enough to count as the same,
+/// relative to their size so tiny thumbnails and large zooms compare alike.
+#[must_use]
+pub fn same_scale(a: f32, b: f32) -> bool {
+    (a - b).abs() <= 0.005 * a.max(b)
+}
+
+/// The part of the page `crop_with_pan` keeps, as a fraction of the page.
+#[must_use]
+pub fn pan_window(zoom: f32, pan: (f32, f32)) -> Region {
+    if zoom <= 1.0 {
+        return Region::FULL;
+    }
+    let size = 1.0 / zoom;
+    Region {
+        x: pan_offset(pan.0, size),
+        y: pan_offset(pan.1, size),
+        w: size,
+        h: size,
+    }
+}
 
-    img.crop_imm(x.min(max_x), y.min(max_y), crop_w.max(1), crop_h.max(1))
+/// Start of a window covering `size` of the page along one axis, for a pan
+/// from `-1.0` (top/left edge) to `1.0` (bottom/right edge).
+#[must_use]
+pub fn pan_offset(pan: f32, size: f32) -> f32 {
+    pan.mul_add(0.5, 0.5) * (1.0 - size)
+}
+
+/// The pan that keeps the center of a window of `old_size` in place once
+/// the window becomes `new_size`, clamped so the window stays on the page.
+#[must_use]
+pub fn refocus_pan(pan: f32, old_size: f32, new_size: f32) -> f32 {
+    if new_size >= 1.0 {
+        return 0.0;
+    }
+    let center = pan_offset(pan, old_size) + old_size / 2.0;
+    let start = center - new_size / 2.0;
+    (start / (1.0 - new_size))
+        .mul_add(2.0, -1.0)
+        .clamp(-1.0, 1.0)
+}
+
+/// Crop a viewport-sized portion of the image for zoom, offset by pan.
+/// `pan_x`/`pan_y` range: `-1.0` (top/left) to `1.0` (bottom/right), `0.0` = center.
+fn crop_with_pan(img: &DynamicImage, zoom: f32, pan_x: f32, pan_y: f32) -> DynamicImage {
+    crop_clip(img, pan_window(zoom, (pan_x, pan_y)))
 }
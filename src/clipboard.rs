@@ -0,0 +1,105 @@
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use image::DynamicImage;
+
+/// System clipboard with an OSC 52 fallback for when no backend is reachable
+/// (e.g. over SSH), in which case the terminal itself sets the clipboard.
+pub struct Clipboard {
+    // Kept alive for the whole session: on X11 the copied contents vanish
+    // as soon as the owning handle is dropped.
+    backend: Option<arboard::Clipboard>,
+    tried_backend: bool,
+}
+
+/// How [`Clipboard::copy_image`] got the image across.
+pub enum ImageCopy {
+    Bitmap,
+    /// Saved to this file, whose path was copied.
+    Path(PathBuf),
+}
+
+impl Clipboard {
+    pub const fn new() -> Self {
+        Self {
+            backend: None,
+            tried_backend: false,
+        }
+    }
+
+    pub fn copy_text(&mut self, text: &str) -> io::Result<()> {
+        if let Some(backend) = self.backend() {
+            if backend.set_text(text).is_ok() {
+                return Ok(());
+            }
+        }
+        write_osc52(text)
+    }
+
+    /// Put `img` on the clipboard as a bitmap. Where that isn't possible
+    /// (no clipboard backend, or one without images) it is saved as `name`
+    /// under `~/.cache/tpdf/copies` and the file's path is copied instead.
+    /// That directory is the user's own, unlike the shared temp directory,
+    /// where anyone could plant a link at a predictable name.
+    pub fn copy_image(&mut self, img: &DynamicImage, name: &str) -> io::Result<ImageCopy> {
+        if let Some(backend) = self.backend() {
+            let rgba = img.to_rgba8();
+            let data = arboard::ImageData {
+                width: rgba.width() as usize,
+                height: rgba.height() as usize,
+                bytes: Cow::Owned(rgba.into_raw()),
+            };
+            if backend.set_image(data).is_ok() {
+                return Ok(ImageCopy::Bitmap);
+            }
+        }
+        let dir = dirs::cache_dir()
+            .ok_or_else(|| io::Error::other("no cache directory to save the image in"))?
+            .join("tpdf")
+            .join("copies");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        img.save(&path).map_err(io::Error::other)?;
+        self.copy_text(&path.to_string_lossy())?;
+        Ok(ImageCopy::Path(path))
+    }
+
+    /// Connect to the system clipboard on first use rather than at startup.
+    fn backend(&mut self) -> Option<&mut arboard::Clipboard> {
+        if !self.tried_backend {
+            self.tried_backend = true;
+            self.backend = arboard::Clipboard::new().ok();
+        }
+        self.backend.as_mut()
+    }
+}
+
+fn write_osc52(text: &str) -> io::Result<()> {
+    let mut out = io::stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()
+}
+
+/// Standard base64 with padding, enough for OSC 52 without pulling in a crate.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
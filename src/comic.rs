@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use image::DynamicImage;
+use zip::ZipArchive;
+
+use crate::document::DocumentSource;
+use crate::images::{self, natural_key};
+use crate::pdf::{DocumentInfo, Source};
+
+/// How RAR archives start, to tell them apart from broken zips.
+const RAR_SIGNATURE: &[u8] = b"Rar!\x1a\x07";
+
+trait Archive: Read + Seek {}
+
+impl<T: Read + Seek> Archive for T {}
+
+/// A comic book archive: a zip or RAR of page images, one image per page
+/// in file name order. `.cbz` and `.cbr` are told apart by their contents,
+/// since many `.cbr` files are zips renamed.
+pub struct ComicDocument {
+    pages: Pages,
+}
+
+enum Pages {
+    Zip {
+        archive: RefCell<ZipArchive<Box<dyn Archive>>>,
+        /// Indices of the image entries of the archive, in page order.
+        entries: Vec<usize>,
+    },
+    Rar {
+        tool: RarTool,
+        source: Source,
+        /// Names of the image entries of the archive, in page order.
+        entries: Vec<String>,
+    },
+}
+
+/// A program that reads RAR archives, run rather than linked since RAR
+/// decoders are C++ with a license of their own: `unrar`, or the `bsdtar`
+/// that comes with libarchive.
+#[derive(Clone, Copy)]
+enum RarTool {
+    Unrar,
+    Bsdtar,
+}
+
+/// Whether `name`, a file name or the magic of an in-memory document, is a
+/// comic archive.
+#[must_use]
+pub fn is_comic(name: &str) -> bool {
+    let ext = Path::new(name)
+        .extension()
+        .map_or_else(|| name.to_string(), |e| e.to_string_lossy().into_owned());
+    matches!(ext.to_ascii_lowercase().as_str(), "cbz" | "cbr")
+}
+
+impl ComicDocument {
+    /// Open the archive and list its pages.
+    ///
+    /// # Errors
+    ///
+    /// When it can't be read, isn't a zip or RAR archive, has no images,
+    /// or is a RAR with neither `unrar` nor `bsdtar` installed.
+    pub fn open(source: &Source) -> Result<Self, Box<dyn Error>> {
+        let mut reader: Box<dyn Archive> = match source {
+            Source::File(path) => Box::new(BufReader::new(File::open(path)?)),
+            Source::Memory { data, .. } => Box::new(Cursor::new(Arc::clone(data))),
+        };
+        let mut signature = [0; RAR_SIGNATURE.len()];
+        if reader.read_exact(&mut signature).is_ok() && signature == RAR_SIGNATURE {
+            return Self::open_rar(source);
+        }
+        reader.rewind()?;
+
+        let mut archive =
+            ZipArchive::new(reader).map_err(|e| format!("not a comic archive ({e})"))?;
+        let mut pages = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i)?;
+            if is_page(entry.name()) && !entry.is_dir() {
+                pages.push((natural_key(entry.name()), i));
+            }
+        }
+        if pages.is_empty() {
+            return Err("no images in the archive".into());
+        }
+        pages.sort();
+        Ok(Self {
+            pages: Pages::Zip {
+                archive: RefCell::new(archive),
+                entries: pages.into_iter().map(|(_, i)| i).collect(),
+            },
+        })
+    }
+
+    /// List a RAR archive with the first of the tools that is installed.
+    /// `unrar` can't read stdin, so piped archives need `bsdtar`.
+    fn open_rar(source: &Source) -> Result<Self, Box<dyn Error>> {
+        let tools: &[RarTool] = match source {
+            Source::File(_) => &[RarTool::Unrar, RarTool::Bsdtar],
+            Source::Memory { .. } => &[RarTool::Bsdtar],
+        };
+        for &tool in tools {
+            let listing = match tool.run(source, None) {
+                Err(e) if is_not_found(e.as_ref()) => continue,
+                listing => listing?,
+            };
+            let mut pages: Vec<_> = String::from_utf8_lossy(&listing)
+                .lines()
+                .filter(|name| is_page(name))
+                .map(|name| (natural_key(name), name.to_string()))
+                .collect();
+            if pages.is_empty() {
+                return Err("no images in the archive".into());
+            }
+            pages.sort();
+            return Ok(Self {
+                pages: Pages::Rar {
+                    tool,
+                    source: source.clone(),
+                    entries: pages.into_iter().map(|(_, name)| name).collect(),
+                },
+            });
+        }
+        Err(match source {
+            Source::File(_) => "RAR archives (.cbr) need unrar or bsdtar installed",
+            Source::Memory { .. } => "RAR archives (.cbr) on stdin need bsdtar installed",
+        }
+        .into())
+    }
+
+    /// The undecoded image of a page.
+    fn read(&self, page_idx: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let missing = || format!("no page {}", page_idx + 1);
+        match &self.pages {
+            Pages::Zip { archive, entries } => {
+                let entry = *entries.get(page_idx).ok_or_else(missing)?;
+                let mut archive = archive.borrow_mut();
+                let mut file = archive.by_index(entry)?;
+                let mut data = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut data)?;
+                Ok(data)
+            }
+            Pages::Rar {
+                tool,
+                source,
+                entries,
+            } => {
+                let entry = entries.get(page_idx).ok_or_else(missing)?;
+                tool.run(source, Some(entry))
+            }
+        }
+    }
+}
+
+impl RarTool {
+    /// List the archive's entries, one name per line, or with `entry`,
+    /// print that entry's contents.
+    fn run(self, source: &Source, entry: Option<&str>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let archive = match source {
+            Source::File(path) => OsStr::new(path),
+            Source::Memory { .. } => OsStr::new("-"),
+        };
+        let mut command = match self {
+            Self::Unrar => {
+                let mut command = Command::new("unrar");
+                // -inul keeps unrar's banner out of the printed entry
+                match entry {
+                    Some(_) => command.args(["p", "-inul", "--"]),
+                    None => command.args(["lb", "--"]),
+                };
+                command
+            }
+            Self::Bsdtar => {
+                let mut command = Command::new("bsdtar");
+                command.arg(if entry.is_some() { "-xOf" } else { "-tf" });
+                command
+            }
+        };
+        command.arg(archive).args(entry);
+
+        let data = match source {
+            Source::Memory { data, .. } => Some(Arc::clone(data)),
+            Source::File(_) => None,
+        };
+        let mut child = command
+            .stdin(if data.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // Fed from another thread, so a full stdout pipe can't stall both
+        let feeder = child.stdin.take().zip(data).map(|(mut stdin, data)| {
+            std::thread::spawn(move || {
+                let _ = stdin.write_all(&data);
+            })
+        });
+        let output = child.wait_with_output()?;
+        if let Some(feeder) = feeder {
+            let _ = feeder.join();
+        }
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .rfind(|l| !l.trim().is_empty())
+                .unwrap_or("couldn't read the RAR archive");
+            return Err(reason.to_string().into());
+        }
+        Ok(output.stdout)
+    }
+}
+
+fn is_not_found(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+impl DocumentSource for ComicDocument {
+    fn page_count(&self) -> usize {
+        match &self.pages {
+            Pages::Zip { entries, .. } => entries.len(),
+            Pages::Rar { entries, .. } => entries.len(),
+        }
+    }
+
+    fn page_bounds(&self, page_idx: usize) -> Result<(f32, f32), Box<dyn Error>> {
+        images::image_size(&self.read(page_idx)?)
+    }
+
+    fn render_page(
+        &self,
+        page_idx: usize,
+        scale: f32,
+        rotation: u16,
+        _annotations: bool,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        images::render_image(&self.read(page_idx)?, scale, rotation)
+    }
+
+    fn metadata(&self) -> DocumentInfo {
+        DocumentInfo {
+            version: Some("Comic archive".to_string()),
+            ..DocumentInfo::default()
+        }
+    }
+}
+
+/// Whether an archive entry is a page, rather than metadata, a thumbnail
+/// from the Finder or something else that came along.
+fn is_page(name: &str) -> bool {
+    let path = Path::new(name);
+    let hidden = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+    images::is_image(path) && !hidden && !name.starts_with("__MACOSX/")
+}
@@ -0,0 +1,101 @@
+use crate::app::{Message, PageLayout};
+use crate::config;
+
+/// Commands of the `:` prompt that take an argument. Any action name from the
+/// `[keys]` config table also works as a command, without one.
+const COMMANDS: &[&str] = &["goto", "rotate", "layout", "export"];
+
+/// Parse a command line typed after `:` into the message it stands for.
+pub fn parse(input: &str) -> Result<Message, String> {
+    let mut words = input.split_whitespace();
+    let Some(name) = words.next() else {
+        return Err("Empty command".into());
+    };
+    let arg = words.next();
+    if words.next().is_some() {
+        return Err(format!("Too many arguments for '{name}'"));
+    }
+
+    match (name, arg) {
+        // A bare number jumps there, like Vim's `:42`
+        (n, None) if n.parse::<usize>().is_ok() => parse_page(n).map(Message::GotoPage),
+        ("goto" | "g", Some(page)) => parse_page(page).map(Message::GotoPage),
+        ("rotate", Some(degrees)) => parse_rotation(degrees).map(Message::SetRotation),
+        ("layout", Some(layout)) => parse_layout(layout).map(Message::SetLayout),
+        ("export", Some(range)) => {
+            parse_range(range).map(|(first, last)| Message::ExportPages(first, last))
+        }
+        ("q" | "quit", None) => Ok(Message::Quit),
+        (name, None) if COMMANDS.contains(&name) => Err(format!("'{name}' needs an argument")),
+        (name, None) => {
+            config::action_message(name).ok_or_else(|| format!("Unknown command '{name}'"))
+        }
+        (name, Some(_)) => Err(format!("Unknown command '{name}'")),
+    }
+}
+
+/// Complete the command name being typed, as far as it is unambiguous.
+pub fn complete(input: &str) -> Option<String> {
+    if input.contains(char::is_whitespace) {
+        return None;
+    }
+    let matches: Vec<&str> = COMMANDS
+        .iter()
+        .chain(config::ACTIONS.iter().map(|(name, _)| name))
+        .copied()
+        .filter(|c| c.starts_with(input))
+        .collect();
+    match matches.as_slice() {
+        [] => None,
+        // A command gets a space, ready for its argument
+        [only] if COMMANDS.contains(only) => Some(format!("{only} ")),
+        [first, rest @ ..] => {
+            let len = rest.iter().fold(first.len(), |len, c| {
+                first
+                    .bytes()
+                    .zip(c.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+                    .min(len)
+            });
+            Some(first[..len].to_string())
+        }
+    }
+}
+
+/// A 1-based page number, returned 0-based.
+fn parse_page(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n >= 1 => Ok(n - 1),
+        _ => Err(format!("Invalid page '{s}'")),
+    }
+}
+
+/// A page range like `3-7`, or a single page; both ends are 0-based.
+pub fn parse_range(s: &str) -> Result<(usize, usize), String> {
+    let (first, last) = s.split_once('-').unwrap_or((s, s));
+    let (first, last) = (parse_page(first)?, parse_page(last)?);
+    if first > last {
+        return Err(format!("Invalid range '{s}'"));
+    }
+    Ok((first, last))
+}
+
+fn parse_rotation(s: &str) -> Result<u16, String> {
+    match s.parse::<i32>() {
+        Ok(d) if d % 90 == 0 => Ok(d.rem_euclid(360) as u16),
+        _ => Err(format!("Rotation must be a multiple of 90, not '{s}'")),
+    }
+}
+
+fn parse_layout(s: &str) -> Result<PageLayout, String> {
+    match s {
+        "1" | "single" => Ok(PageLayout::Single),
+        "2" | "dual" => Ok(PageLayout::Dual),
+        "3" | "triple" => Ok(PageLayout::Triple),
+        "c" | "continuous" => Ok(PageLayout::Continuous),
+        _ => PageLayout::parse_grid(s).ok_or_else(|| {
+            format!("Unknown layout '{s}' (1, 2, 3, continuous or a grid like 3x2)")
+        }),
+    }
+}
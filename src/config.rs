@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui_image::picker::ProtocolType;
+
+use crate::app::{ColorMode, FitMode, Message, PageLayout, Quality, StatusSegments};
+use crate::graphics;
+
+/// User settings from `~/.config/tpdf/config.toml`. Problems in the file are
+/// reported on stderr and skipped, never fatal, unless the file was asked
+/// for with `--config` or `TPDF_CONFIG`.
+#[derive(Default)]
+pub struct Config {
+    pub keymap: Keymap,
+    /// `background = "f4ecd8"`: paper color outside night mode.
+    pub background: Option<[u8; 3]>,
+    /// `night_background = "1e1e1e"`: paper color in night mode.
+    pub night_background: Option<[u8; 3]>,
+    /// `preload = 5`: pages rendered ahead of and behind the visible ones.
+    pub preload: Option<usize>,
+    /// `threads = 4`: background render threads.
+    pub threads: Option<usize>,
+    /// `disk_cache = true`: keep rendered pages on disk between runs.
+    pub disk_cache: bool,
+    /// `disk_cache_mb = 1024`: size of the on-disk cache.
+    pub disk_cache_mb: Option<usize>,
+    /// `protocol = "sixel"`: image protocol, instead of the detected one.
+    pub protocol: Option<ProtocolType>,
+    /// `quality = "fast"`: render quality, `fast`, `balanced` or `high`.
+    pub quality: Option<Quality>,
+    /// `wrap_navigation = true`: turn from the last page to the first.
+    pub wrap_navigation: bool,
+    /// `vim_motions = true`: `gg`, `zz`, `zt` and `zb`.
+    pub vim_motions: bool,
+    /// `text_scroll = 5`: lines moved per scroll step in text mode.
+    pub text_scroll: Option<usize>,
+    /// `layout = "dual"`: layout of newly opened documents.
+    pub layout: Option<PageLayout>,
+    /// `fit = "width"`: fit mode of newly opened documents.
+    pub fit: Option<FitMode>,
+    /// `color_mode = "night"`: color mode of newly opened documents.
+    pub color_mode: Option<ColorMode>,
+    /// `fullscreen = true`: always start in fullscreen.
+    pub fullscreen: bool,
+    /// `cover = true`: show the first page alone in dual layout.
+    pub cover: bool,
+    /// `status_bar = "minimal"` or `["page", "keys"]`: what the status bar
+    /// shows.
+    pub status_segments: Option<StatusSegments>,
+    /// `status_bar = "hidden"`: start with the status bar hidden.
+    pub hide_status_bar: bool,
+    /// `separators = true`: draw lines between pages from the start.
+    pub separators: bool,
+    /// `separator_width = 2`: columns between pages side by side.
+    pub separator_width: Option<usize>,
+    /// `separator_color = "888888"`: color of the lines between pages.
+    pub separator_color: Option<[u8; 3]>,
+}
+
+/// Key overrides from the `[keys]` table, e.g. `next_page = ["s", "Right"]`.
+/// Keys that aren't mapped here keep their built-in binding.
+#[derive(Default, Clone)]
+pub struct Keymap {
+    bindings: HashMap<(KeyCode, KeyModifiers), Message>,
+}
+
+impl Keymap {
+    #[must_use]
+    pub fn get(&self, key: KeyEvent) -> Option<Message> {
+        self.bindings
+            .get(&normalize(key.code, key.modifiers))
+            .cloned()
+    }
+}
+
+fn config_file() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("tpdf").join("config.toml"))
+}
+
+impl Config {
+    /// Load the default config file, if there is one.
+    #[must_use]
+    pub fn load() -> Self {
+        let Some(path) = config_file() else {
+            return Self::default();
+        };
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        match contents.parse::<toml::Table>() {
+            Ok(table) => Self::from_table(&table),
+            Err(e) => {
+                warn(&format!("{}: {e}", path.display()));
+                Self::default()
+            }
+        }
+    }
+
+    /// Load a config file named explicitly, which unlike the default has to
+    /// exist and parse.
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("config file {}: {e}", path.display()))?;
+        let table = contents
+            .parse::<toml::Table>()
+            .map_err(|e| format!("config file {}: {e}", path.display()))?;
+        Ok(Self::from_table(&table))
+    }
+
+    fn from_table(table: &toml::Table) -> Self {
+        let mut config = Self::default();
+        if let Some(keys) = table.get("keys") {
+            match keys.as_table() {
+                Some(keys) => config.keymap = parse_keymap(keys),
+                None => warn("[keys] must be a table"),
+            }
+        }
+        config.background = color_setting(table, "background");
+        config.night_background = color_setting(table, "night_background");
+        config.preload = count_setting(table, "preload");
+        config.threads = count_setting(table, "threads");
+        config.disk_cache = bool_setting(table, "disk_cache").unwrap_or(false);
+        config.disk_cache_mb = count_setting(table, "disk_cache_mb");
+        config.quality = quality_setting(table);
+        config.protocol = protocol_setting(table);
+        config.wrap_navigation = bool_setting(table, "wrap_navigation").unwrap_or(false);
+        config.vim_motions = bool_setting(table, "vim_motions").unwrap_or(false);
+        config.text_scroll = count_setting(table, "text_scroll");
+        config.layout = name_setting(
+            table,
+            "layout",
+            PageLayout::from_name,
+            "\"single\", \"dual\", \"triple\", \"continuous\" or a grid like \"3x2\"",
+        );
+        config.fit = name_setting(
+            table,
+            "fit",
+            FitMode::from_name,
+            "\"page\", \"width\" or \"height\"",
+        );
+        config.color_mode = name_setting(
+            table,
+            "color_mode",
+            ColorMode::from_name,
+            "\"normal\", \"night\", \"smart-night\", \"dark-paper\", \"grayscale\" or \"sepia\"",
+        );
+        config.fullscreen = bool_setting(table, "fullscreen").unwrap_or(false);
+        config.cover = bool_setting(table, "cover").unwrap_or(false);
+        config.separators = bool_setting(table, "separators").unwrap_or(false);
+        config.separator_width = count_setting(table, "separator_width");
+        config.separator_color = color_setting(table, "separator_color");
+        if let Some(value) = table.get("status_bar") {
+            match value.as_str() {
+                Some("hidden") => config.hide_status_bar = true,
+                _ => config.status_segments = status_bar_setting(value),
+            }
+        }
+        config
+    }
+}
+
+/// A preset name or a list of segments.
+fn status_bar_setting(value: &toml::Value) -> Option<StatusSegments> {
+    match value {
+        toml::Value::String(preset) => match preset.as_str() {
+            "full" => Some(StatusSegments::FULL),
+            "minimal" => Some(StatusSegments::MINIMAL),
+            _ => {
+                warn("status_bar must be \"full\", \"minimal\", \"hidden\" or a list of segments");
+                None
+            }
+        },
+        toml::Value::Array(names) => {
+            let mut segments = StatusSegments::NONE;
+            for name in names {
+                match name.as_str() {
+                    Some(name) if segments.enable(name) => {}
+                    _ => warn(&format!(
+                        "unknown status bar segment {name} (page, zoom, layout, mode or keys)"
+                    )),
+                }
+            }
+            Some(segments)
+        }
+        _ => {
+            warn("status_bar must be a preset name or a list of segments");
+            None
+        }
+    }
+}
+
+fn quality_setting(table: &toml::Table) -> Option<Quality> {
+    let value = table.get("quality")?;
+    let quality = value.as_str().and_then(Quality::from_name);
+    if quality.is_none() {
+        warn("quality must be \"fast\", \"balanced\" or \"high\"");
+    }
+    quality
+}
+
+fn protocol_setting(table: &toml::Table) -> Option<ProtocolType> {
+    let value = table.get("protocol")?;
+    let protocol = value.as_str().and_then(graphics::protocol_from_name);
+    if protocol.is_none() {
+        warn(&format!(
+            "protocol must be one of {}",
+            graphics::PROTOCOL_NAMES
+        ));
+    }
+    protocol
+}
+
+/// A setting whose value is one of the names `parse` knows.
+fn name_setting<T>(
+    table: &toml::Table,
+    name: &str,
+    parse: impl Fn(&str) -> Option<T>,
+    expected: &str,
+) -> Option<T> {
+    let value = table.get(name)?;
+    let parsed = value.as_str().and_then(parse);
+    if parsed.is_none() {
+        warn(&format!("{name} must be {expected}"));
+    }
+    parsed
+}
+
+fn count_setting(table: &toml::Table, name: &str) -> Option<usize> {
+    let value = table.get(name)?;
+    let count = value.as_integer().and_then(|n| usize::try_from(n).ok());
+    if count.is_none() {
+        warn(&format!("{name} must be a non-negative integer"));
+    }
+    count
+}
+
+fn bool_setting(table: &toml::Table, name: &str) -> Option<bool> {
+    let value = table.get(name)?;
+    let flag = value.as_bool();
+    if flag.is_none() {
+        warn(&format!("{name} must be true or false"));
+    }
+    flag
+}
+
+fn color_setting(table: &toml::Table, name: &str) -> Option<[u8; 3]> {
+    let value = table.get(name)?;
+    let color = value.as_str().and_then(parse_color);
+    if color.is_none() {
+        warn(&format!("{name} must be a hex color like \"f4ecd8\""));
+    }
+    color
+}
+
+/// Parse a hex color like `f4ecd8` or `#f4ecd8`.
+#[must_use]
+pub fn parse_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn parse_keymap(keys: &toml::Table) -> Keymap {
+    let mut keymap = Keymap::default();
+    for (action, value) in keys {
+        let Some(msg) = action_message(action) else {
+            warn(&format!("unknown action '{action}'"));
+            continue;
+        };
+        let names: Vec<&str> = match value {
+            toml::Value::String(s) => vec![s.as_str()],
+            toml::Value::Array(items) => items.iter().filter_map(toml::Value::as_str).collect(),
+            _ => {
+                warn(&format!(
+                    "keys for '{action}' must be a string or list of strings"
+                ));
+                continue;
+            }
+        };
+        for name in names {
+            match parse_key(name) {
+                Some(key) => {
+                    keymap.bindings.insert(key, msg.clone());
+                }
+                None => warn(&format!("invalid key '{name}' for '{action}'")),
+            }
+        }
+    }
+    keymap
+}
+
+/// Config names for the remappable actions.
+pub const ACTIONS: &[(&str, Message)] = &[
+    ("quit", Message::Quit),
+    ("next_tab", Message::NextTab),
+    ("prev_tab", Message::PrevTab),
+    ("next_page", Message::NextPage),
+    ("prev_page", Message::PrevPage),
+    ("first_page", Message::FirstPage),
+    ("last_page", Message::LastPage),
+    ("zoom_in", Message::ZoomIn),
+    ("zoom_out", Message::ZoomOut),
+    ("zoom_reset", Message::ZoomReset),
+    ("fit_width", Message::FitWidth),
+    ("fit_height", Message::FitHeight),
+    ("toggle_lock_view", Message::ToggleLockView),
+    ("toggle_pinned_zoom", Message::TogglePinnedZoom),
+    ("toggle_cache_status", Message::ToggleCacheStatus),
+    ("toggle_debug", Message::ToggleDebug),
+    ("toggle_clock", Message::ToggleClock),
+    ("retry_render", Message::RetryRender),
+    ("scroll_up", Message::ScrollUp),
+    ("scroll_down", Message::ScrollDown),
+    ("scroll_left", Message::ScrollLeft),
+    ("scroll_right", Message::ScrollRight),
+    ("pan_top", Message::PanTop),
+    ("pan_bottom", Message::PanBottom),
+    ("cycle_layout", Message::CycleLayout),
+    ("toggle_cover_offset", Message::ToggleCoverOffset),
+    ("toggle_compare", Message::ToggleCompare),
+    ("toggle_dim_inactive", Message::ToggleDimInactive),
+    ("toggle_minimap", Message::ToggleMinimap),
+    ("toggle_separators", Message::ToggleSeparators),
+    ("switch_pane", Message::SwitchPane),
+    ("toggle_auto_advance", Message::ToggleAutoAdvance),
+    ("cycle_quality", Message::CycleQuality),
+    ("toggle_autocrop", Message::ToggleAutoCrop),
+    ("cycle_lens", Message::CycleLens),
+    ("rotate_cw", Message::RotateCW),
+    ("rotate_ccw", Message::RotateCCW),
+    ("toggle_dark_mode", Message::ToggleDarkMode),
+    ("cycle_color_mode", Message::CycleColorMode),
+    ("brightness_up", Message::BrightnessUp),
+    ("brightness_down", Message::BrightnessDown),
+    ("contrast_up", Message::ContrastUp),
+    ("contrast_down", Message::ContrastDown),
+    ("reset_adjustments", Message::ResetAdjustments),
+    ("toggle_fullscreen", Message::ToggleFullscreen),
+    ("toggle_status_bar", Message::ToggleStatusBar),
+    ("toggle_text_mode", Message::ToggleTextMode),
+    ("toggle_split_text", Message::ToggleSplitText),
+    ("text_scroll_up", Message::TextScrollUp),
+    ("text_scroll_down", Message::TextScrollDown),
+    ("text_page_down", Message::TextPageDown),
+    ("toggle_fields", Message::ToggleFields),
+    ("toggle_annotations", Message::ToggleAnnotations),
+    ("toggle_reflow", Message::ToggleReflow),
+    ("toggle_text_styles", Message::ToggleTextStyles),
+    ("enter_goto", Message::EnterGoto),
+    ("enter_command", Message::EnterCommand),
+    ("copy_text", Message::CopyText),
+    ("copy_image", Message::CopyImage),
+    ("copy_link", Message::CopyLink),
+    ("select_text", Message::ToggleSelection),
+    ("show_info", Message::ShowInfo),
+    ("toggle_thumbnails", Message::ToggleThumbnails),
+    ("set_bookmark", Message::SetBookmark),
+    ("set_named_bookmark", Message::EnterBookmarkLabel),
+    ("list_bookmarks", Message::ListBookmarks),
+    ("history_back", Message::HistoryBack),
+    ("history_forward", Message::HistoryForward),
+    ("next_link", Message::NextLink),
+    ("prev_link", Message::PrevLink),
+    ("follow_link", Message::FollowLink),
+];
+
+#[must_use]
+pub fn action_message(name: &str) -> Option<Message> {
+    ACTIONS
+        .iter()
+        .find(|(action, _)| *action == name)
+        .map(|(_, msg)| msg.clone())
+}
+
+/// Parse a key like `j`, `G`, `Space`, `PageDown`, `F5` or `ctrl-o`.
+fn parse_key(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, t)| !t.is_empty()) {
+        modifiers |= match prefix.to_ascii_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        rest = tail;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "insert" => KeyCode::Insert,
+            "delete" => KeyCode::Delete,
+            f => KeyCode::F(
+                f.strip_prefix('f')?
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=12).contains(n))?,
+            ),
+        },
+    };
+    Some(normalize(code, modifiers))
+}
+
+/// Shift is already reflected in the character itself (`G` vs `g`), and
+/// terminals disagree on whether to report it, so ignore it for characters.
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    let modifiers = match code {
+        KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+        _ => modifiers,
+    };
+    (
+        code,
+        modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT),
+    )
+}
+
+fn warn(msg: &str) {
+    eprintln!("tpdf: config: {msg}");
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::app::ColorMode;
+
+/// What white paper turns into: `day` in the normal and grayscale modes,
+/// `night` in the night modes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Paper {
+    pub day: [u8; 3],
+    pub night: [u8; 3],
+}
+
+impl Default for Paper {
+    fn default() -> Self {
+        Self {
+            day: [255; 3],
+            night: [0; 3],
+        }
+    }
+}
+
+/// Apply a color mode, or `None` when the page is shown as rendered.
+#[must_use]
+pub fn apply(mode: ColorMode, img: &DynamicImage, paper: Paper) -> Option<DynamicImage> {
+    let default = Paper::default();
+    match mode {
+        ColorMode::Normal if paper.day == default.day => None,
+        ColorMode::Normal => Some(tint(img, paper.day)),
+        ColorMode::Night if paper.night == default.night => Some(invert(img)),
+        ColorMode::Night => Some(lift(&invert(img), paper.night)),
+        ColorMode::SmartNight if paper.night == default.night => Some(smart_invert(img)),
+        ColorMode::SmartNight => Some(lift(&smart_invert(img), paper.night)),
+        ColorMode::DarkPaper => Some(background_swap(img, page_background(img), paper.night)),
+        ColorMode::Grayscale if paper.day == default.day => Some(grayscale(img)),
+        ColorMode::Grayscale => Some(tint(&grayscale(img), paper.day)),
+        ColorMode::Sepia => Some(sepia(img)),
+    }
+}
+
+/// Multiply by `color`, so white becomes `color` and black stays black.
+fn tint(img: &DynamicImage, color: [u8; 3]) -> DynamicImage {
+    let mut out = img.to_rgb8();
+    for px in out.pixels_mut() {
+        for (c, t) in px.0.iter_mut().zip(color) {
+            *c = (u16::from(*c) * u16::from(t) / 255) as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(out)
+}
+
+/// Raise the black point to `color`, keeping white white.
+fn lift(img: &DynamicImage, color: [u8; 3]) -> DynamicImage {
+    let mut out = img.to_rgb8();
+    for px in out.pixels_mut() {
+        for (c, t) in px.0.iter_mut().zip(color) {
+            *c = t + (u16::from(*c) * u16::from(255 - t) / 255) as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(out)
+}
+
+/// Swap black and white — classic night mode.
+#[must_use]
+pub fn invert(img: &DynamicImage) -> DynamicImage {
+    let mut out = img.clone();
+    out.invert();
+    out
+}
+
+/// Chroma (max - min channel) below which a pixel counts as ink or paper
+/// and is inverted; above the upper bound it is color and left alone, with a
+/// blend in between so edges don't band. Anti-aliased text stays well below
+/// the lower bound, while most photo and chart colors clear the upper one.
+const SMART_INK_CHROMA: f32 = 24.0;
+const SMART_COLOR_CHROMA: f32 = 72.0;
+
+/// Night mode for mixed content: invert the lightness of near-gray pixels
+/// (text, rules, paper) and keep colorful ones as they are.
+pub fn smart_invert(img: &DynamicImage) -> DynamicImage {
+    let mut out = img.to_rgb8();
+    for px in out.pixels_mut() {
+        let [r, g, b] = px.0.map(f32::from);
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let keep = ((max - min - SMART_INK_CHROMA) / (SMART_COLOR_CHROMA - SMART_INK_CHROMA))
+            .clamp(0.0, 1.0);
+        if keep >= 1.0 {
+            continue;
+        }
+        // Shifting every channel alike flips the lightness but keeps the hue
+        let shift = 255.0 - max - min;
+        px.0 =
+            px.0.map(|c| (f32::from(c) + shift * (1.0 - keep)).clamp(0.0, 255.0) as u8);
+    }
+    DynamicImage::ImageRgb8(out)
+}
+
+/// Largest channel difference from the paper color at which a pixel is
+/// still paper, and past which it is ink left alone. In between it is
+/// blended, so anti-aliased edges don't leave a light fringe.
+const PAPER_TOLERANCE: i16 = 12;
+const INK_TOLERANCE: i16 = 64;
+
+/// The color of the page's paper: the most common color along its edges,
+/// which are nearly always margin. Colors are bucketed so scanner noise
+/// doesn't split the count.
+#[must_use]
+pub fn page_background(img: &DynamicImage) -> [u8; 3] {
+    let rgb = img.to_rgb8();
+    let (w, h) = rgb.dimensions();
+    if w == 0 || h == 0 {
+        return [255; 3];
+    }
+    let edges = (0..w)
+        .flat_map(|x| [(x, 0), (x, h - 1)])
+        .chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]));
+    let mut counts: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+    for (x, y) in edges {
+        let px = rgb.get_pixel(x, y).0;
+        let (count, sum) = counts.entry(px.map(|c| c & 0xf0)).or_default();
+        *count += 1;
+        for (s, c) in sum.iter_mut().zip(px) {
+            *s += u32::from(c);
+        }
+    }
+    counts
+        .into_values()
+        .max_by_key(|(count, _)| *count)
+        .map_or([255; 3], |(count, sum)| sum.map(|s| (s / count) as u8))
+}
+
+/// Replace the paper color `from` with `to`, leaving text, lines and
+/// colors that differ from it enough as they are.
+#[must_use]
+pub fn background_swap(img: &DynamicImage, from: [u8; 3], to: [u8; 3]) -> DynamicImage {
+    let mut out = img.to_rgb8();
+    for px in out.pixels_mut() {
+        let distance =
+            px.0.iter()
+                .zip(from)
+                .map(|(&c, f)| (i16::from(c) - i16::from(f)).abs())
+                .max()
+                .unwrap_or(0);
+        if distance >= INK_TOLERANCE {
+            continue;
+        }
+        let ink = f32::from((distance - PAPER_TOLERANCE).max(0))
+            / f32::from(INK_TOLERANCE - PAPER_TOLERANCE);
+        for (c, t) in px.0.iter_mut().zip(to) {
+            *c = (f32::from(*c) - f32::from(t))
+                .mul_add(ink, f32::from(t))
+                .round() as u8;
+        }
+    }
+    DynamicImage::ImageRgb8(out)
+}
+
+#[must_use]
+pub fn grayscale(img: &DynamicImage) -> DynamicImage {
+    DynamicImage::ImageRgb8(img.grayscale().to_rgb8())
+}
+
+/// Warm brown tint using the common sepia matrix. White paper comes out as
+/// a soft cream rather than staying pure white.
+#[must_use]
+pub fn sepia(img: &DynamicImage) -> DynamicImage {
+    let src = img.to_rgb8();
+    let out = RgbImage::from_fn(src.width(), src.height(), |x, y| {
+        let [r, g, b] = src.get_pixel(x, y).0.map(f32::from);
+        let tone =
+            |cr: f32, cg: f32, cb: f32| cb.mul_add(b, cr.mul_add(r, cg * g)).min(255.0) as u8;
+        Rgb([
+            tone(0.393, 0.769, 0.189),
+            tone(0.349, 0.686, 0.168),
+            tone(0.272, 0.534, 0.131),
+        ])
+    });
+    DynamicImage::ImageRgb8(out)
+}
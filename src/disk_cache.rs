@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::{DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+
+use crate::pdf::Source;
+
+/// Default size of the on-disk cache, shared by all documents.
+pub const DEFAULT_BUDGET: u64 = 1024 * 1024 * 1024;
+
+/// Rendered pages kept on disk between runs.
+///
+/// Reopening a large scan loads PNGs instead of rasterizing every page
+/// again. Each document gets a directory under `~/.cache/tpdf/pages`, named by a hash of its path, size
+/// and modification time (or its bytes, for stdin): editing the file starts
+/// a fresh directory, and the stale one ages out of the budget.
+pub struct DiskCache {
+    dir: PathBuf,
+    budget: u64,
+    /// Bytes stored since the cache was last trimmed.
+    written: AtomicU64,
+}
+
+/// What a page was rendered with. Color modes and brightness are applied
+/// after rendering, so they aren't part of it.
+pub struct RenderKey {
+    pub idx: usize,
+    pub scale: f32,
+    pub rotation: u16,
+    pub antialias: bool,
+    pub annotations: bool,
+}
+
+impl RenderKey {
+    fn file_name(&self) -> String {
+        format!(
+            "{}-{:08x}-{}-{}{}.png",
+            self.idx,
+            self.scale.to_bits(),
+            self.rotation,
+            u8::from(self.antialias),
+            u8::from(self.annotations),
+        )
+    }
+}
+
+fn pages_dir() -> Option<PathBuf> {
+    Some(dirs::cache_dir()?.join("tpdf").join("pages"))
+}
+
+impl DiskCache {
+    /// The cache for `source`, trimmed to `budget` bytes. `None` when
+    /// there is no cache directory, the file can't be read or it is a
+    /// folder.
+    #[must_use]
+    pub fn open(source: &Source, budget: u64) -> Option<Self> {
+        let mut hasher = Sha256::new();
+        match source {
+            Source::File(path) => {
+                let meta = fs::metadata(path).ok()?;
+                // Folders of images decode quickly anyway, and editing an
+                // image wouldn't change the folder's modification time
+                if meta.is_dir() {
+                    return None;
+                }
+                let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+                hasher.update(fs::canonicalize(path).ok()?.to_string_lossy().as_bytes());
+                hasher.update(meta.len().to_le_bytes());
+                hasher.update(modified.as_nanos().to_le_bytes());
+            }
+            Source::Memory { data, .. } => hasher.update(data),
+        }
+        let dir = pages_dir()?.join(format!("{:x}", hasher.finalize()));
+        fs::create_dir_all(&dir).ok()?;
+        let cache = Self {
+            dir,
+            budget,
+            written: AtomicU64::new(0),
+        };
+        cache.trim();
+        Some(cache)
+    }
+
+    /// The stored render for `key`, if there is one.
+    pub fn load(&self, key: &RenderKey) -> Option<DynamicImage> {
+        let path = self.dir.join(key.file_name());
+        let img = image::open(&path).ok()?;
+        // Mark it used, so trimming removes the least recently read first
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(img)
+    }
+
+    /// Store a render for `key`. Failures only cost a re-render next time,
+    /// so they are ignored.
+    pub fn store(&self, key: &RenderKey, img: &DynamicImage) {
+        let path = self.dir.join(key.file_name());
+        let tmp = path.with_extension("tmp");
+        if img.save_with_format(&tmp, ImageFormat::Png).is_err() {
+            let _ = fs::remove_file(&tmp);
+            return;
+        }
+        let size = fs::metadata(&tmp).map_or(0, |m| m.len());
+        if fs::rename(&tmp, &path).is_err() {
+            let _ = fs::remove_file(&tmp);
+            return;
+        }
+        // Check the total now and then rather than after every page
+        if self.written.fetch_add(size, Ordering::Relaxed) + size > self.budget / 8 {
+            self.written.store(0, Ordering::Relaxed);
+            self.trim();
+        }
+    }
+
+    /// Delete the least recently used renders, of any document, until the
+    /// cache fits its budget.
+    fn trim(&self) {
+        let Some(root) = self.dir.parent() else {
+            return;
+        };
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+        for doc in read_dir(root) {
+            let entries = read_dir(&doc);
+            if entries.is_empty() && doc != self.dir {
+                let _ = fs::remove_dir(&doc);
+            }
+            files.extend(entries.into_iter().filter_map(|path| {
+                let meta = fs::metadata(&path).ok()?;
+                Some((meta.modified().ok()?, meta.len(), path))
+            }));
+        }
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, size, path) in files {
+            if total <= self.budget {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+            }
+        }
+    }
+}
+
+fn read_dir(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir).map_or_else(
+        |_| Vec::new(),
+        |entries| entries.filter_map(|e| Some(e.ok()?.path())).collect(),
+    )
+}
@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use image::DynamicImage;
+
+use crate::comic::{self, ComicDocument};
+use crate::images::ImageFolderDocument;
+use crate::pdf::{
+    DocumentInfo, FieldInfo, LinkInfo, NoteInfo, OutlineEntry, PageLabels, PdfDocument, Region,
+    Source,
+};
+
+/// A document tpdf can show, whatever its format.
+///
+/// Pages are all that is needed; text, links, forms, notes, labels and an
+/// outline are extras only PDFs have, and the defaults are for documents
+/// that are just pictures, like comics.
+///
+/// Documents stay on the thread that opened them, as mupdf's handles do:
+/// each render worker opens one of its own rather than sharing the viewer's.
+pub trait DocumentSource {
+    fn page_count(&self) -> usize;
+
+    /// Page size in points, as it is displayed.
+    ///
+    /// # Errors
+    ///
+    /// When the page can't be loaded or, for images, its header read.
+    fn page_bounds(&self, page_idx: usize) -> Result<(f32, f32), Box<dyn Error>>;
+
+    /// Rasterize a page at `scale`, turned clockwise by `rotation` degrees,
+    /// with or without its annotations.
+    ///
+    /// # Errors
+    ///
+    /// When the page can't be loaded or drawn, or its image decoded.
+    fn render_page(
+        &self,
+        page_idx: usize,
+        scale: f32,
+        rotation: u16,
+        annotations: bool,
+    ) -> Result<DynamicImage, Box<dyn Error>>;
+
+    /// Whether the pages have text to show, search and copy.
+    fn has_text(&self) -> bool {
+        false
+    }
+
+    /// The text of a page, in reading order.
+    ///
+    /// # Errors
+    ///
+    /// When the page can't be loaded, or the document has no text.
+    fn extract_text(&self, _page_idx: usize) -> Result<String, Box<dyn Error>> {
+        Err("no text in this document".into())
+    }
+
+    /// The text of a page inside `region`, a fraction of the unrotated page.
+    ///
+    /// # Errors
+    ///
+    /// As for [`extract_text`](Self::extract_text).
+    fn text_in_region(&self, page_idx: usize, _region: Region) -> Result<String, Box<dyn Error>> {
+        self.extract_text(page_idx)
+    }
+
+    /// Lines of a page's text set as headings, trimmed, for text mode to
+    /// pick out.
+    ///
+    /// # Errors
+    ///
+    /// When the page can't be loaded.
+    fn headings(&self, _page_idx: usize) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn metadata(&self) -> DocumentInfo {
+        DocumentInfo::default()
+    }
+
+    /// The table of contents.
+    ///
+    /// # Errors
+    ///
+    /// When the outline is there but can't be read.
+    fn outline(&self) -> Result<Vec<OutlineEntry>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// The printed page numbers, like `iv` or `A-3`.
+    ///
+    /// # Errors
+    ///
+    /// When the labels are there but can't be read.
+    fn page_labels(&self) -> Result<PageLabels, Box<dyn Error>> {
+        Ok(PageLabels::default())
+    }
+
+    fn resolve_named_dest(&self, _name: &str) -> Option<usize> {
+        None
+    }
+
+    /// The page's links, to other pages or to URLs.
+    ///
+    /// # Errors
+    ///
+    /// When the page can't be loaded.
+    fn links(&self, _page_idx: usize) -> Result<Vec<LinkInfo>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// The page's form fields and their values.
+    ///
+    /// # Errors
+    ///
+    /// When the page or its widgets can't be read.
+    fn form_fields(&self, _page_idx: usize) -> Result<Vec<FieldInfo>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// The page's note annotations and the text they hold.
+    ///
+    /// # Errors
+    ///
+    /// When the page or its annotations can't be read.
+    fn notes(&self, _page_idx: usize) -> Result<Vec<NoteInfo>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// The password the document was unlocked with, if it needed one.
+    fn password(&self) -> Option<&str> {
+        None
+    }
+
+    fn authenticate(&mut self, _password: &str) -> bool {
+        false
+    }
+}
+
+/// Open `source`: a directory as a folder of images, a comic archive if its
+/// extension says so, and anything else with mupdf.
+///
+/// # Errors
+///
+/// [`OpenError::PasswordRequired`] with the locked document when it is
+/// encrypted and `password` is missing or wrong; otherwise the reason it
+/// couldn't be read.
+pub fn open(source: &Source, password: Option<&str>) -> Result<Box<dyn DocumentSource>, OpenError> {
+    let unreadable = |e: Box<dyn Error>| OpenError::Images(format!("{}: {e}", source.name()));
+    let name = match source {
+        Source::File(path) if Path::new(path).is_dir() => {
+            let folder = ImageFolderDocument::open(Path::new(path)).map_err(unreadable)?;
+            return Ok(Box::new(folder));
+        }
+        Source::File(path) => path,
+        Source::Memory { magic, .. } => magic,
+    };
+    if comic::is_comic(name) {
+        let comic = ComicDocument::open(source).map_err(unreadable)?;
+        return Ok(Box::new(comic));
+    }
+    Ok(Box::new(PdfDocument::open_source(source, password)?))
+}
+
+/// Why a document didn't open.
+pub enum OpenError {
+    /// The document is encrypted and still needs a password; it is returned
+    /// locked so the caller can prompt and call [`DocumentSource::authenticate`].
+    PasswordRequired(PdfDocument),
+    Mupdf(mupdf::Error),
+    /// A comic archive or image folder that couldn't be read, with the
+    /// reason.
+    Images(String),
+}
+
+impl From<mupdf::Error> for OpenError {
+    fn from(e: mupdf::Error) -> Self {
+        Self::Mupdf(e)
+    }
+}
+
+impl fmt::Debug for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PasswordRequired(_) => f.debug_tuple("PasswordRequired").finish_non_exhaustive(),
+            Self::Mupdf(e) => f.debug_tuple("Mupdf").field(e).finish(),
+            Self::Images(e) => f.debug_tuple("Images").field(e).finish(),
+        }
+    }
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PasswordRequired(_) => f.write_str("the document needs a password"),
+            Self::Mupdf(e) => e.fmt(f),
+            Self::Images(e) => f.write_str(e),
+        }
+    }
+}
+
+impl Error for OpenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Mupdf(e) => Some(e),
+            Self::PasswordRequired(_) | Self::Images(_) => None,
+        }
+    }
+}
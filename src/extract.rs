@@ -0,0 +1,564 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{self, FilterType};
+use image::{Delay, DynamicImage, Frame, Rgba, RgbaImage};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::app::{Quality, EXPORT_SCALE};
+use crate::command;
+use crate::document::{self, DocumentSource, OpenError};
+use crate::pdf::{self, DocumentInfo, OutlineEntry, Source};
+
+/// What `tpdf info --json` prints. Page numbers are 1-based, as elsewhere
+/// on the command line.
+#[derive(Serialize)]
+struct Summary {
+    page_count: usize,
+    metadata: DocumentInfo,
+    pages: Vec<PageSummary>,
+    outline: Vec<OutlineSummary>,
+}
+
+/// Size in points and printed label of one page.
+#[derive(Serialize)]
+struct PageSummary {
+    page: usize,
+    label: Option<String>,
+    width: f32,
+    height: f32,
+}
+
+#[derive(Serialize)]
+struct OutlineSummary {
+    title: String,
+    page: Option<usize>,
+    children: Vec<Self>,
+}
+
+impl From<OutlineEntry> for OutlineSummary {
+    fn from(entry: OutlineEntry) -> Self {
+        Self {
+            title: entry.title,
+            page: entry.page.map(|p| p + 1),
+            children: entry.children.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+/// One page of `tpdf text --json`.
+#[derive(Serialize)]
+struct PageText {
+    page: usize,
+    text: String,
+}
+
+/// Print the text of `pages` (like `3-7`, default all) to stdout, through
+/// the same extraction as text mode. Pages that fail are reported on stderr
+/// and skipped.
+pub fn print_text(
+    path: &str,
+    pages: Option<&str>,
+    separator: &str,
+    json: bool,
+    password: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = open_text(path, password)?;
+    let (first, last) = page_range(pdf.as_ref(), pages)?;
+
+    let mut out = io::stdout().lock();
+    let mut printed = 0;
+    if json {
+        write!(out, "[")?;
+    }
+    for idx in first..=last {
+        let text = match pdf.extract_text(idx) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("tpdf: page {}: {e}", idx + 1);
+                continue;
+            }
+        };
+        if json {
+            // Written page by page, so long documents stream out
+            let sep = if printed == 0 { "" } else { "," };
+            write!(out, "{sep}\n  ")?;
+            serde_json::to_writer(
+                &mut out,
+                &PageText {
+                    page: idx + 1,
+                    text,
+                },
+            )?;
+        } else {
+            if printed > 0 {
+                write!(out, "{separator}")?;
+            }
+            write!(out, "{text}")?;
+        }
+        printed += 1;
+    }
+    if json {
+        writeln!(out, "\n]")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Pages `tpdf bench` renders unless told otherwise.
+const BENCH_PAGES: usize = 10;
+
+/// What `tpdf bench --json` prints. Times are in milliseconds and sizes in
+/// bytes.
+#[derive(Serialize)]
+struct BenchReport {
+    pages: usize,
+    scale: f32,
+    /// Whether `scale` was fitted to the terminal, as the viewer would
+    /// render, rather than given or a fallback.
+    fitted: bool,
+    quality: &'static str,
+    total_ms: f64,
+    pages_per_second: f64,
+    /// Average size of one render in memory, which is what each preloaded
+    /// page costs.
+    bytes_per_page: u64,
+    /// Peak resident memory of the process, where the system reports it.
+    peak_memory: Option<u64>,
+    per_page: Vec<PageTiming>,
+}
+
+#[derive(Serialize)]
+struct PageTiming {
+    page: usize,
+    width: u32,
+    height: u32,
+    ms: f64,
+}
+
+/// Render the first `pages` pages (default `BENCH_PAGES`) one after another
+/// and report how long each took.
+///
+/// Also reported are the pages rendered per second and the memory used, to
+/// help pick `preload` and `quality`. Pages are rendered at `scale`, or at
+/// the scale that fits the first one in the terminal.
+pub fn bench(
+    path: &str,
+    pages: Option<usize>,
+    scale: Option<f32>,
+    quality: Quality,
+    json: bool,
+    password: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = open(path, password)?;
+    let count = pages.unwrap_or(BENCH_PAGES).min(pdf.page_count());
+    if count == 0 {
+        return Err(format!("{path}: no pages").into());
+    }
+    let fitted = match scale {
+        Some(_) => None,
+        None => terminal_fit_scale(pdf.page_bounds(0)?),
+    };
+    let scale = scale.or(fitted).unwrap_or(EXPORT_SCALE);
+    pdf::set_antialiasing(quality.antialias());
+
+    let mut per_page = Vec::with_capacity(count);
+    let mut bytes = 0;
+    let start = Instant::now();
+    for idx in 0..count {
+        let page_start = Instant::now();
+        let img = pdf
+            .render_page(idx, scale, 0, true)
+            .map_err(|e| format!("page {}: {e}", idx + 1))?;
+        per_page.push(PageTiming {
+            page: idx + 1,
+            width: img.width(),
+            height: img.height(),
+            ms: millis(page_start.elapsed()),
+        });
+        bytes += img.as_bytes().len() as u64;
+    }
+    let total = start.elapsed();
+
+    let report = BenchReport {
+        pages: count,
+        scale,
+        fitted: fitted.is_some(),
+        quality: quality.name(),
+        total_ms: millis(total),
+        pages_per_second: count as f64 / total.as_secs_f64().max(f64::EPSILON),
+        bytes_per_page: bytes / count as u64,
+        peak_memory: peak_memory(),
+        per_page,
+    };
+    let mut out = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+    } else {
+        write_bench(&mut out, &report)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The human-readable form of `tpdf bench`.
+fn write_bench(out: &mut impl Write, report: &BenchReport) -> io::Result<()> {
+    for page in &report.per_page {
+        writeln!(
+            out,
+            "Page {:<5} {:>5}x{:<5} {:>8.1} ms",
+            page.page, page.width, page.height, page.ms
+        )?;
+    }
+    let how = if report.fitted {
+        "fitted to the terminal"
+    } else {
+        "fixed"
+    };
+    let times = report.per_page.iter().map(|p| p.ms);
+    let fastest = times.clone().fold(f64::INFINITY, f64::min);
+    let slowest = times.fold(0.0, f64::max);
+    writeln!(out)?;
+    writeln!(out, "{:<10} {:.2} ({how})", "Scale", report.scale)?;
+    writeln!(out, "{:<10} {}", "Quality", report.quality)?;
+    writeln!(
+        out,
+        "{:<10} {} pages in {:.0} ms",
+        "Total", report.pages, report.total_ms
+    )?;
+    writeln!(
+        out,
+        "{:<10} {:.1} ms average, {fastest:.1} fastest, {slowest:.1} slowest",
+        "Per page",
+        report.total_ms / report.pages as f64
+    )?;
+    writeln!(
+        out,
+        "{:<10} {:.1} pages/s",
+        "Speed", report.pages_per_second
+    )?;
+    writeln!(
+        out,
+        "{:<10} {} per rendered page",
+        "Memory",
+        megabytes(report.bytes_per_page)
+    )?;
+    if let Some(peak) = report.peak_memory {
+        writeln!(out, "{:<10} {}", "Peak", megabytes(peak))?;
+    }
+    Ok(())
+}
+
+/// The scale that fits a page of `page_w` x `page_h` points in the terminal
+/// above the status bar, as the viewer renders it at the default zoom.
+/// `None` when the terminal doesn't report its size in pixels.
+fn terminal_fit_scale((page_w, page_h): (f32, f32)) -> Option<f32> {
+    let size = crossterm::terminal::window_size().ok()?;
+    if size.width == 0 || size.height == 0 || size.rows == 0 {
+        return None;
+    }
+    let row_px = f32::from(size.height) / f32::from(size.rows);
+    let fit_w = f32::from(size.width) / page_w;
+    let fit_h = (f32::from(size.height) - row_px) / page_h;
+    Some(fit_w.min(fit_h))
+}
+
+/// Peak resident memory of this process in bytes, which only Linux reports
+/// without extra dependencies.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Scale of GIF frames by default: small enough for a quick preview.
+const GIF_SCALE: f32 = 1.0;
+
+/// GIF quantization speed, 1-30: the slowest setting takes minutes over
+/// full-page frames for little visible gain.
+const GIF_SPEED: i32 = 10;
+
+/// Render `pages` (default all) at `scale` and save them as PNG files named
+/// after the document in the current directory, like `:export`, or as one
+/// animated GIF showing each page for `delay_ms`.
+pub fn export(
+    path: &str,
+    pages: Option<&str>,
+    gif: Option<&str>,
+    delay_ms: u32,
+    scale: Option<f32>,
+    password: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = open(path, password)?;
+    let (first, last) = page_range(pdf.as_ref(), pages)?;
+    let scale = scale.unwrap_or_else(|| {
+        if gif.is_some() {
+            GIF_SCALE
+        } else {
+            EXPORT_SCALE
+        }
+    });
+    let render = |idx: usize| {
+        pdf.render_page(idx, scale, 0, true)
+            .map_err(|e| format!("page {}: {e}", idx + 1))
+    };
+
+    let Some(gif) = gif else {
+        let stem = Path::new(path)
+            .file_stem()
+            .map_or_else(|| path.to_string(), |s| s.to_string_lossy().into_owned());
+        for idx in first..=last {
+            let file = format!("{stem}-{}.png", idx + 1);
+            render(idx)?.save(&file)?;
+            println!("{file}");
+        }
+        return Ok(());
+    };
+
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(gif)?), GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+    let mut size = None;
+    for idx in first..=last {
+        let img = render(idx)?;
+        // A GIF has one canvas size, so later pages are fitted to the first
+        let (width, height) = *size.get_or_insert_with(|| (img.width(), img.height()));
+        let frame = fit_frame(&img, width, height);
+        encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+    }
+    println!("{gif}");
+    Ok(())
+}
+
+/// `img` scaled to fit within `width` x `height` and centered on white.
+fn fit_frame(img: &DynamicImage, width: u32, height: u32) -> RgbaImage {
+    if (img.width(), img.height()) == (width, height) {
+        return img.to_rgba8();
+    }
+    let scaled = img.resize(width, height, FilterType::Triangle).to_rgba8();
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+    let x = (width - scaled.width()) / 2;
+    let y = (height - scaled.height()) / 2;
+    imageops::overlay(&mut canvas, &scaled, x.into(), y.into());
+    canvas
+}
+
+/// The 0-based first and last page of `pages` (like `3-7`, default all),
+/// checked against the document.
+fn page_range(pdf: &dyn DocumentSource, pages: Option<&str>) -> Result<(usize, usize), String> {
+    let page_count = pdf.page_count();
+    let (first, last) = match pages {
+        Some(range) => command::parse_range(range)?,
+        None => (0, page_count.saturating_sub(1)),
+    };
+    if last >= page_count {
+        return Err(format!(
+            "No page {} (the document has {page_count})",
+            last + 1
+        ));
+    }
+    Ok((first, last))
+}
+
+/// Print each line of the document's text that matches `pattern` as
+/// `page:line`, returning whether anything matched.
+///
+/// The matches are highlighted when writing to a terminal. With `count`,
+/// print `page:matches` for each page that has any instead.
+pub fn print_matches(
+    path: &str,
+    pattern: &str,
+    ignore_case: bool,
+    count: bool,
+    password: Option<&str>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let pdf = open_text(path, password)?;
+    let re = RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()?;
+    let color = io::stdout().is_terminal();
+
+    let mut out = io::stdout().lock();
+    let mut found = false;
+    for idx in 0..pdf.page_count() {
+        let text = match pdf.extract_text(idx) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("tpdf: page {}: {e}", idx + 1);
+                continue;
+            }
+        };
+        let page = idx + 1;
+        if count {
+            let n: usize = text.lines().map(|l| re.find_iter(l).count()).sum();
+            if n > 0 {
+                writeln!(out, "{page}:{n}")?;
+                found = true;
+            }
+            continue;
+        }
+        for line in text.lines().filter(|l| re.is_match(l)) {
+            writeln!(out, "{page}:{}", highlight(&re, line.trim(), color))?;
+            found = true;
+        }
+    }
+    out.flush()?;
+    Ok(found)
+}
+
+/// Print a summary of the document: its metadata, page count and size, and
+/// outline. With `json`, print every page's size and label too, for tools
+/// to read.
+pub fn print_info(
+    path: &str,
+    json: bool,
+    password: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pdf = open(path, password)?;
+    let labels = pdf.page_labels().unwrap_or_default();
+    let pages: Vec<PageSummary> = (0..pdf.page_count())
+        .filter_map(|idx| match pdf.page_bounds(idx) {
+            Ok((width, height)) => Some(PageSummary {
+                page: idx + 1,
+                label: labels.label(idx),
+                width,
+                height,
+            }),
+            Err(e) => {
+                eprintln!("tpdf: page {}: {e}", idx + 1);
+                None
+            }
+        })
+        .collect();
+    let summary = Summary {
+        page_count: pdf.page_count(),
+        metadata: pdf.metadata(),
+        pages,
+        outline: pdf
+            .outline()
+            .unwrap_or_default()
+            .into_iter()
+            .map(OutlineSummary::from)
+            .collect(),
+    };
+
+    let mut out = io::stdout().lock();
+    if json {
+        serde_json::to_writer_pretty(&mut out, &summary)?;
+        writeln!(out)?;
+    } else {
+        write_info(&mut out, &summary)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The human-readable form of `tpdf info`, laid out like the `i` overlay.
+fn write_info(out: &mut impl Write, summary: &Summary) -> io::Result<()> {
+    let info = &summary.metadata;
+    let rows = [
+        ("Title", &info.title),
+        ("Author", &info.author),
+        ("Subject", &info.subject),
+        ("Creator", &info.creator),
+        ("Created", &info.created),
+        ("Version", &info.version),
+    ];
+    for (label, value) in rows {
+        writeln!(out, "{label:<10} {}", value.as_deref().unwrap_or("—"))?;
+    }
+    writeln!(out, "{:<10} {}", "Pages", summary.page_count)?;
+    if let Some(first) = summary.pages.first() {
+        let uniform = summary
+            .pages
+            .iter()
+            .all(|p| (p.width, p.height) == (first.width, first.height));
+        writeln!(
+            out,
+            "{:<10} {:.0} x {:.0} pt{}",
+            "Page size",
+            first.width,
+            first.height,
+            if uniform { "" } else { " (varies)" }
+        )?;
+    }
+    if !summary.outline.is_empty() {
+        writeln!(out, "\nOutline")?;
+        write_outline(out, &summary.outline, 1)?;
+    }
+    Ok(())
+}
+
+fn write_outline(out: &mut impl Write, entries: &[OutlineSummary], depth: usize) -> io::Result<()> {
+    for entry in entries {
+        let indent = "  ".repeat(depth);
+        match entry.page {
+            Some(page) => writeln!(out, "{indent}{} ({page})", entry.title)?,
+            None => writeln!(out, "{indent}{}", entry.title)?,
+        }
+        write_outline(out, &entry.children, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// `line` with the matches of `re` in bold red, if `color` is on.
+fn highlight<'a>(re: &Regex, line: &'a str, color: bool) -> Cow<'a, str> {
+    if color {
+        re.replace_all(line, "\x1b[1;31m$0\x1b[0m")
+    } else {
+        Cow::Borrowed(line)
+    }
+}
+
+/// Open the document at `path` for the headless commands, which can't
+/// prompt for a password.
+fn open(
+    path: &str,
+    password: Option<&str>,
+) -> Result<Box<dyn DocumentSource>, Box<dyn std::error::Error>> {
+    let source = Source::File(path.to_string());
+    match document::open(&source, password) {
+        Ok(pdf) => Ok(pdf),
+        Err(OpenError::PasswordRequired(_)) if password.is_some() => {
+            Err("Incorrect password".into())
+        }
+        Err(OpenError::PasswordRequired(_)) => {
+            Err("The document is encrypted; pass --password".into())
+        }
+        Err(OpenError::Mupdf(e)) => Err(source.open_failure(&e).into()),
+        Err(OpenError::Images(e)) => Err(e.into()),
+    }
+}
+
+/// Like [`open`], for the commands that need the document's text.
+fn open_text(
+    path: &str,
+    password: Option<&str>,
+) -> Result<Box<dyn DocumentSource>, Box<dyn std::error::Error>> {
+    let pdf = open(path, password)?;
+    if !pdf.has_text() {
+        return Err(format!("{path}: no text in this document").into());
+    }
+    Ok(pdf)
+}
@@ -0,0 +1,121 @@
+use std::env;
+
+use ratatui_image::picker::{Capability, Picker, ProtocolType};
+
+/// Names accepted by `--protocol` and the `protocol` setting.
+pub const PROTOCOL_NAMES: &str = "kitty, sixel, iterm or halfblocks";
+
+#[must_use]
+pub fn protocol_from_name(name: &str) -> Option<ProtocolType> {
+    match name {
+        "kitty" => Some(ProtocolType::Kitty),
+        "sixel" => Some(ProtocolType::Sixel),
+        "iterm" | "iterm2" => Some(ProtocolType::Iterm2),
+        "halfblocks" => Some(ProtocolType::Halfblocks),
+        _ => None,
+    }
+}
+
+const fn protocol_name(protocol: ProtocolType) -> &'static str {
+    match protocol {
+        ProtocolType::Kitty => "kitty",
+        ProtocolType::Sixel => "sixel",
+        ProtocolType::Iterm2 => "iterm",
+        ProtocolType::Halfblocks => "halfblocks",
+    }
+}
+
+/// Ask the terminal which image protocol it speaks and its font size, then
+/// apply `forced` if given.
+///
+/// Terminals without a graphics protocol, or that don't answer at all, get
+/// half-blocks: plain colored characters, blocky but still a picture of the
+/// page, with text mode a key away.
+#[must_use]
+pub fn picker(forced: Option<ProtocolType>) -> Picker {
+    let mut picker = Picker::from_query_stdio().unwrap_or_else(|_| Picker::halfblocks());
+    if let Some(protocol) = forced {
+        picker.set_protocol_type(protocol);
+    }
+    picker
+}
+
+/// What to tell the user on opening when pages are drawn with half-blocks
+/// without them asking for it.
+#[must_use]
+pub fn fallback_notice(picker: &Picker, forced: Option<ProtocolType>) -> Option<String> {
+    (forced.is_none() && picker.protocol_type() == ProtocolType::Halfblocks).then(|| {
+        "No terminal graphics found, drawing with half-blocks (T for text, tpdf doctor for more)"
+            .to_string()
+    })
+}
+
+/// Print what tpdf finds out about the terminal, for working out why pages
+/// don't show up or look wrong.
+pub fn print_report(forced: Option<ProtocolType>) -> Result<(), Box<dyn std::error::Error>> {
+    let detected = Picker::from_query_stdio().map_err(|e| {
+        format!("the terminal didn't answer the graphics query ({e}); try --protocol halfblocks")
+    })?;
+
+    let var = |name| env::var(name).ok().filter(|v| !v.is_empty());
+    let mut terminal = var("TERM").unwrap_or_else(|| "unknown".into());
+    if let Some(program) = var("TERM_PROGRAM") {
+        terminal = format!("{program} (TERM={terminal})");
+    }
+    if var("TMUX").is_some() {
+        terminal.push_str(", inside tmux");
+    }
+    println!("{:<11} {terminal}", "Terminal");
+
+    let caps = detected.capabilities();
+    let (w, h) = detected.font_size();
+    let reported = caps
+        .iter()
+        .any(|c| matches!(c, Capability::CellSize(Some(_))));
+    println!(
+        "{:<11} {w}x{h} px{}",
+        "Font size",
+        if reported {
+            ""
+        } else {
+            " (not reported; guessed)"
+        }
+    );
+
+    let supports: Vec<&str> = caps
+        .iter()
+        .filter_map(|c| match c {
+            Capability::Kitty => Some("kitty"),
+            Capability::Sixel => Some("sixel"),
+            _ => None,
+        })
+        .collect();
+    println!(
+        "{:<11} {}",
+        "Reports",
+        if supports.is_empty() {
+            "no graphics support".to_string()
+        } else {
+            supports.join(", ")
+        }
+    );
+
+    let detected = detected.protocol_type();
+    match forced {
+        Some(protocol) if protocol != detected => println!(
+            "{:<11} {} (forced; detected {})",
+            "Protocol",
+            protocol_name(protocol),
+            protocol_name(detected)
+        ),
+        _ => println!("{:<11} {}", "Protocol", protocol_name(detected)),
+    }
+
+    if forced.unwrap_or(detected) == ProtocolType::Halfblocks {
+        println!(
+            "\nPages are sharpest with kitty, sixel or iterm; halfblocks is a blocky fallback."
+        );
+    }
+    println!("If pages don't show or look wrong, try --protocol with {PROTOCOL_NAMES}.");
+    Ok(())
+}
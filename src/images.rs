@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+
+use crate::document::DocumentSource;
+use crate::pdf::DocumentInfo;
+
+/// Files with these extensions are pages.
+const IMAGE_EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+
+/// A folder of page images, such as scans, one page per image in file name
+/// order. Subfolders are left out.
+pub struct ImageFolderDocument {
+    files: Vec<PathBuf>,
+}
+
+impl ImageFolderDocument {
+    /// List the images in `dir` as pages.
+    ///
+    /// # Errors
+    ///
+    /// When the folder can't be read or has no images.
+    pub fn open(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut files: Vec<(Vec<Chunk>, PathBuf)> = fs::read_dir(dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                (is_image(&path) && path.is_file() && !name.starts_with('.'))
+                    .then(|| (natural_key(&name), path))
+            })
+            .collect();
+        if files.is_empty() {
+            return Err("no images in the folder".into());
+        }
+        files.sort();
+        Ok(Self {
+            files: files.into_iter().map(|(_, path)| path).collect(),
+        })
+    }
+
+    fn read(&self, page_idx: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(fs::read(self.file(page_idx)?)?)
+    }
+
+    fn file(&self, page_idx: usize) -> Result<&Path, Box<dyn Error>> {
+        let path = self
+            .files
+            .get(page_idx)
+            .ok_or_else(|| format!("no page {}", page_idx + 1))?;
+        Ok(path)
+    }
+}
+
+impl DocumentSource for ImageFolderDocument {
+    fn page_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Only the image's header is read, not the whole file.
+    fn page_bounds(&self, page_idx: usize) -> Result<(f32, f32), Box<dyn Error>> {
+        let (w, h) = ImageReader::open(self.file(page_idx)?)?
+            .with_guessed_format()?
+            .into_dimensions()?;
+        Ok((w as f32, h as f32))
+    }
+
+    fn render_page(
+        &self,
+        page_idx: usize,
+        scale: f32,
+        rotation: u16,
+        _annotations: bool,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        render_image(&self.read(page_idx)?, scale, rotation)
+    }
+
+    fn metadata(&self) -> DocumentInfo {
+        DocumentInfo {
+            version: Some("Image folder".to_string()),
+            ..DocumentInfo::default()
+        }
+    }
+}
+
+/// Whether `path` names an image file, going by its extension.
+#[must_use]
+pub fn is_image(path: &Path) -> bool {
+    path.extension().is_some_and(|e| {
+        IMAGE_EXTENSIONS.contains(&e.to_string_lossy().to_ascii_lowercase().as_str())
+    })
+}
+
+/// Size of an encoded page image, read from its header. Images have no
+/// physical size, so a pixel counts as a point.
+///
+/// # Errors
+///
+/// When the format isn't recognized or the header can't be read.
+pub fn image_size(data: &[u8]) -> Result<(f32, f32), Box<dyn Error>> {
+    let (w, h) = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    Ok((w as f32, h as f32))
+}
+
+/// Decode a page image and scale it to `scale` times its size, turned
+/// clockwise by `rotation` degrees, like a rendered PDF page.
+///
+/// # Errors
+///
+/// When the image can't be decoded.
+pub fn render_image(
+    data: &[u8],
+    scale: f32,
+    rotation: u16,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let img = image::load_from_memory(data)?;
+    let w = (img.width() as f32 * scale).round().max(1.0) as u32;
+    let h = (img.height() as f32 * scale).round().max(1.0) as u32;
+    let img = if (w, h) == (img.width(), img.height()) {
+        img
+    } else {
+        img.resize_exact(w, h, FilterType::Triangle)
+    };
+    let img = match rotation {
+        90 => img.rotate90(),
+        180 => img.rotate180(),
+        270 => img.rotate270(),
+        _ => img,
+    };
+    Ok(DynamicImage::ImageRgb8(img.into_rgb8()))
+}
+
+/// Part of a file name, for ordering names the way pages are numbered.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub enum Chunk {
+    Number(u64),
+    Text(String),
+}
+
+/// Sort key putting `page9.jpg` before `page10.jpg`, ignoring case.
+#[must_use]
+pub fn natural_key(name: &str) -> Vec<Chunk> {
+    let mut key = Vec::new();
+    let mut rest = name;
+    while let Some(first) = rest.chars().next() {
+        let digits = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        key.push(if digits {
+            Chunk::Number(chunk.parse().unwrap_or(u64::MAX))
+        } else {
+            Chunk::Text(chunk.to_lowercase())
+        });
+        rest = tail;
+    }
+    key
+}
@@ -1,20 +1,57 @@
-use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 use crate::app::Message;
+use crate::config::Keymap;
 
-pub fn key_to_message(key: KeyEvent) -> Option<Message> {
+/// `counting` is set while a count prefix is being typed: `0` then extends
+/// it instead of resetting the zoom, and `Esc` cancels it instead of quitting.
+/// With `vim_motions`, `g` and `z` wait for a second key.
+pub fn key_to_message(
+    key: KeyEvent,
+    keymap: &Keymap,
+    counting: bool,
+    vim_motions: bool,
+) -> Option<Message> {
+    if let Some(msg) = keymap.get(key) {
+        return Some(msg);
+    }
+
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
     match key.code {
+        KeyCode::Char(c @ '1'..='9') if !ctrl && !alt => Some(Message::CountDigit(c as u8 - b'0')),
+        KeyCode::Char('0') if counting && !ctrl && !alt => Some(Message::CountDigit(0)),
+        KeyCode::Esc if counting => Some(Message::ClearCount),
+
+        // Terminals send Ctrl-i as Tab, so forward lives on Alt-Right only
+        KeyCode::Char('o') if ctrl => Some(Message::HistoryBack),
+        KeyCode::Char('r') if ctrl => Some(Message::RetryRender),
+        KeyCode::Char('d') if ctrl => Some(Message::ToggleDebug),
+        KeyCode::Char('e') if ctrl => Some(Message::TextScrollDown),
+        KeyCode::Char('y') if ctrl => Some(Message::TextScrollUp),
+        KeyCode::Left if alt => Some(Message::HistoryBack),
+        KeyCode::Right if alt => Some(Message::HistoryForward),
+        KeyCode::PageDown if ctrl => Some(Message::NextTab),
+        KeyCode::PageUp if ctrl => Some(Message::PrevTab),
+        KeyCode::Char('>') => Some(Message::NextTab),
+        KeyCode::Char('<') => Some(Message::PrevTab),
+
         KeyCode::Char('q') | KeyCode::Esc => Some(Message::Quit),
 
         KeyCode::Right | KeyCode::Char('l' | ' ') | KeyCode::PageDown => Some(Message::NextPage),
         KeyCode::Left | KeyCode::Char('h') | KeyCode::PageUp => Some(Message::PrevPage),
 
+        KeyCode::Char(c @ ('g' | 'z')) if vim_motions => Some(Message::StartMotion(c)),
         KeyCode::Char('g') | KeyCode::Home => Some(Message::FirstPage),
         KeyCode::Char('G') | KeyCode::End => Some(Message::LastPage),
 
         KeyCode::Char('+' | '=') => Some(Message::ZoomIn),
         KeyCode::Char('-') => Some(Message::ZoomOut),
         KeyCode::Char('0') => Some(Message::ZoomReset),
+        KeyCode::Char('w') => Some(Message::FitWidth),
+        KeyCode::Char('v') => Some(Message::ToggleLockView),
+        KeyCode::Char('W') => Some(Message::FitHeight),
+        KeyCode::Char('Z') => Some(Message::TogglePinnedZoom),
 
         KeyCode::Up | KeyCode::Char('k') => Some(Message::ScrollUp),
         KeyCode::Down | KeyCode::Char('j') => Some(Message::ScrollDown),
@@ -22,20 +59,166 @@ pub fn key_to_message(key: KeyEvent) -> Option<Message> {
         KeyCode::Char('L') => Some(Message::ScrollRight),
 
         KeyCode::Char('d') => Some(Message::CycleLayout),
+        KeyCode::Char('x') => Some(Message::ToggleCompare),
+        KeyCode::Char('z') => Some(Message::ToggleDimInactive),
+        KeyCode::Char('a') => Some(Message::ToggleAutoAdvance),
+        KeyCode::Char('Q') => Some(Message::CycleQuality),
+        KeyCode::Char('D') => Some(Message::ToggleCoverOffset),
+        KeyCode::Char('c') => Some(Message::ToggleAutoCrop),
+        KeyCode::Char('e') => Some(Message::CycleLens),
+        KeyCode::Char('C') => Some(Message::ToggleCacheStatus),
+        KeyCode::Char('K') => Some(Message::ToggleClock),
+        KeyCode::Char('r') => Some(Message::RotateCW),
+        KeyCode::Char('R') => Some(Message::RotateCCW),
         KeyCode::Char('n') => Some(Message::ToggleDarkMode),
+        KeyCode::Char('m') => Some(Message::CycleColorMode),
+        KeyCode::Char(']') => Some(Message::BrightnessUp),
+        KeyCode::Char('[') => Some(Message::BrightnessDown),
+        KeyCode::Char('}') => Some(Message::ContrastUp),
+        KeyCode::Char('{') => Some(Message::ContrastDown),
+        KeyCode::Char('\\') => Some(Message::ResetAdjustments),
         KeyCode::Char('f') => Some(Message::ToggleFullscreen),
+        KeyCode::Char('s') => Some(Message::ToggleStatusBar),
+        KeyCode::Char('T') => Some(Message::ToggleTextMode),
+        KeyCode::Char('S') => Some(Message::ToggleSplitText),
+        KeyCode::Char('F') => Some(Message::ToggleFields),
+        KeyCode::Char('A') => Some(Message::ToggleAnnotations),
+        KeyCode::Char('J') => Some(Message::ToggleReflow),
+        KeyCode::Char('E') => Some(Message::ToggleTextStyles),
         KeyCode::Char('p') => Some(Message::EnterGoto),
+        KeyCode::Char(':') => Some(Message::EnterCommand),
+        KeyCode::Char('y') => Some(Message::CopyText),
+        KeyCode::Char('Y') => Some(Message::CopyImage),
+        KeyCode::Char('M') => Some(Message::ToggleMinimap),
+        KeyCode::Char('|') => Some(Message::ToggleSeparators),
+        KeyCode::Char('u') => Some(Message::CopyLink),
+        KeyCode::Char('V') => Some(Message::ToggleSelection),
+        KeyCode::Char('i') => Some(Message::ShowInfo),
+        KeyCode::Char('t') => Some(Message::ToggleThumbnails),
+        KeyCode::Char('b') => Some(Message::SetBookmark),
+        KeyCode::Char('B') => Some(Message::EnterBookmarkLabel),
+        KeyCode::Char('\'') => Some(Message::ListBookmarks),
+
+        KeyCode::Tab => Some(Message::NextLink),
+        KeyCode::BackTab => Some(Message::PrevLink),
+        KeyCode::Enter => Some(Message::FollowLink),
+
+        _ => None,
+    }
+}
+
+/// The second key of a motion started with `first`. Other keys drop the
+/// motion and do what they normally do; `Esc` just drops it.
+pub fn key_to_motion_message(first: char, key: KeyEvent) -> Option<Message> {
+    match (first, key.code) {
+        ('g', KeyCode::Char('g')) => Some(Message::FirstPage),
+        ('z', KeyCode::Char('z')) => Some(Message::ZoomReset),
+        ('z', KeyCode::Char('t')) => Some(Message::PanTop),
+        ('z', KeyCode::Char('b')) => Some(Message::PanBottom),
+        (_, KeyCode::Esc) => Some(Message::ClearCount),
+        _ => None,
+    }
+}
+
+/// What the first key of a motion does when no second key follows in
+/// time: the same as without `vim_motions`.
+pub const fn lone_motion_message(first: char) -> Message {
+    match first {
+        'g' => Message::FirstPage,
+        _ => Message::ToggleDimInactive,
+    }
+}
 
+pub fn mouse_to_message(mouse: MouseEvent) -> Option<Message> {
+    match mouse.kind {
+        MouseEventKind::ScrollUp => Some(Message::WheelUp),
+        MouseEventKind::ScrollDown => Some(Message::WheelDown),
+        MouseEventKind::Down(MouseButton::Left) => Some(Message::Click(mouse.column, mouse.row)),
+        _ => None,
+    }
+}
+
+pub fn key_to_thumbnail_message(key: KeyEvent) -> Option<Message> {
+    match key.code {
+        KeyCode::Left | KeyCode::Char('h') => Some(Message::ThumbnailLeft),
+        KeyCode::Right | KeyCode::Char('l') => Some(Message::ThumbnailRight),
+        KeyCode::Up | KeyCode::Char('k') => Some(Message::ThumbnailUp),
+        KeyCode::Down | KeyCode::Char('j') => Some(Message::ThumbnailDown),
+        KeyCode::Enter => Some(Message::ThumbnailSelect),
+        KeyCode::Esc | KeyCode::Char('t') => Some(Message::ToggleThumbnails),
+        KeyCode::Char('q') => Some(Message::Quit),
+        _ => None,
+    }
+}
+
+pub fn key_to_selection_message(key: KeyEvent) -> Option<Message> {
+    // Not every terminal reports Shift with capital letters
+    let resize = key.modifiers.contains(KeyModifiers::SHIFT)
+        || matches!(key.code, KeyCode::Char(c) if c.is_ascii_uppercase());
+    let (dx, dy) = match key.code {
+        KeyCode::Left | KeyCode::Char('h' | 'H') => (-1, 0),
+        KeyCode::Right | KeyCode::Char('l' | 'L') => (1, 0),
+        KeyCode::Up | KeyCode::Char('k' | 'K') => (0, -1),
+        KeyCode::Down | KeyCode::Char('j' | 'J') => (0, 1),
+        KeyCode::Char('y') | KeyCode::Enter => return Some(Message::CopySelection),
+        KeyCode::Esc | KeyCode::Char('V') => return Some(Message::ToggleSelection),
+        KeyCode::Char('q') => return Some(Message::Quit),
+        _ => return None,
+    };
+    Some(if resize {
+        Message::ResizeSelection(dx, dy)
+    } else {
+        Message::MoveSelection(dx, dy)
+    })
+}
+
+pub fn key_to_bookmark_label_message(key: KeyEvent) -> Option<Message> {
+    match key.code {
+        KeyCode::Char(c) if c.is_ascii_alphanumeric() => Some(Message::BookmarkLabel(c)),
+        KeyCode::Esc => Some(Message::CloseBookmarks),
+        _ => None,
+    }
+}
+
+pub fn key_to_bookmark_list_message(key: KeyEvent) -> Option<Message> {
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Some(Message::BookmarkUp),
+        KeyCode::Down | KeyCode::Char('j') => Some(Message::BookmarkDown),
+        KeyCode::Enter => Some(Message::BookmarkSelect),
+        KeyCode::Char('d') | KeyCode::Delete => Some(Message::BookmarkDelete),
+        KeyCode::Esc | KeyCode::Char('q' | '\'') => Some(Message::CloseBookmarks),
         _ => None,
     }
 }
 
 pub fn key_to_goto_message(key: KeyEvent) -> Option<Message> {
     match key.code {
-        KeyCode::Char(c) if c.is_ascii_digit() => Some(Message::GotoInput(c)),
-        KeyCode::Backspace => Some(Message::GotoBackspace),
+        // Letters too, for page labels like `iv` or `A-3`
+        KeyCode::Char(c) if !c.is_control() => Some(Message::PromptInput(c)),
+        KeyCode::Backspace => Some(Message::PromptBackspace),
         KeyCode::Enter => Some(Message::GotoConfirm),
-        KeyCode::Esc => Some(Message::GotoCancel),
+        KeyCode::Esc => Some(Message::PromptCancel),
+        _ => None,
+    }
+}
+
+pub fn key_to_command_message(key: KeyEvent) -> Option<Message> {
+    match key.code {
+        KeyCode::Char(c) => Some(Message::PromptInput(c)),
+        KeyCode::Backspace => Some(Message::PromptBackspace),
+        KeyCode::Tab => Some(Message::CommandComplete),
+        KeyCode::Enter => Some(Message::CommandConfirm),
+        KeyCode::Esc => Some(Message::PromptCancel),
+        _ => None,
+    }
+}
+
+pub fn key_to_password_message(key: KeyEvent) -> Option<Message> {
+    match key.code {
+        KeyCode::Char(c) => Some(Message::PasswordInput(c)),
+        KeyCode::Backspace => Some(Message::PasswordBackspace),
+        KeyCode::Enter => Some(Message::PasswordConfirm),
+        KeyCode::Esc => Some(Message::Quit),
         _ => None,
     }
 }
@@ -0,0 +1,48 @@
+//! Page rendering and caching from the tpdf terminal PDF viewer, for
+//! embedding in other terminal apps: a file manager preview, or a different
+//! frontend.
+//!
+//! Open a PDF, comic archive or folder of images with [`open`], rasterize
+//! pages with [`DocumentSource::render_page`], keep the results in a
+//! [`PageCache`], which turns them into ratatui-image protocols for the
+//! current [`ColorMode`], and place them with [`aligned_image_area`].
+//!
+//! The modules hidden from these docs are the viewer itself, public only so
+//! the `tpdf` binary can reach them; they can change in any release.
+
+pub mod cache;
+pub mod comic;
+pub mod dark;
+pub mod document;
+pub mod images;
+pub mod pdf;
+
+#[doc(hidden)]
+pub mod app;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod disk_cache;
+#[doc(hidden)]
+pub mod extract;
+#[doc(hidden)]
+pub mod graphics;
+#[doc(hidden)]
+pub mod update;
+
+mod clipboard;
+mod command;
+mod input;
+mod ocr;
+mod pace;
+mod queue;
+mod session;
+mod text;
+mod view;
+mod watch;
+
+pub use app::{ColorMode, Lens};
+pub use cache::PageCache;
+pub use document::{open, DocumentSource, OpenError};
+pub use pdf::{PdfDocument, Region, Source};
+pub use view::{aligned_image_area, HAlign};
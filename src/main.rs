@@ -1,87 +1,727 @@
-mod app;
-mod cache;
-mod input;
-mod pdf;
-mod update;
-mod view;
-
 use mimalloc::MiMalloc;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
+use std::io::{stdout, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use clap::{Parser, Subcommand};
-use ratatui_image::picker::Picker;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+use ratatui_image::picker::ProtocolType;
 
-use app::{AppConfig, PageLayout};
+use tpdf::app::{self, AppConfig, ColorMode, FitMode, PageLayout, Quality, StatusSegments};
+use tpdf::config::{self, Config};
+use tpdf::dark::Paper;
+use tpdf::pdf::Source;
+use tpdf::{cache, disk_cache, extract, graphics, update};
 
 #[derive(Parser)]
-#[command(name = "tpdf", about = "Terminal PDF viewer", version)]
+#[command(
+    name = "tpdf",
+    about = "Terminal PDF viewer",
+    version,
+    disable_version_flag = true
+)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Command>,
 
-    /// Path to PDF file
-    path: Option<String>,
+    /// Documents or http(s) URLs to open, each in its own tab; - reads one from stdin
+    #[arg(value_name = "PATH")]
+    paths: Vec<String>,
 
     /// Start in night mode
     #[arg(short, long)]
     night: bool,
 
     /// Start in fullscreen
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_fullscreen")]
     fullscreen: bool,
 
+    /// Start with the status bar, even if the config says fullscreen
+    #[arg(long, overrides_with = "fullscreen")]
+    no_fullscreen: bool,
+
     /// Start at page number
     #[arg(short, long, value_name = "N")]
     page: Option<usize>,
 
+    /// Open this far down the start page, from 0.0 (top) to 1.0 (bottom)
+    #[arg(long, value_name = "FRACTION", value_parser = parse_scroll_arg)]
+    scroll: Option<f32>,
+
+    /// Fit pages to the view's width or height rather than showing them whole
+    #[arg(long, value_name = "page|width|height", value_parser = ["page", "width", "height"])]
+    fit: Option<String>,
+
+    /// Start at a named destination (an anchor in the document)
+    #[arg(long, value_name = "NAME")]
+    dest: Option<String>,
+
     /// Layout: 1 (single), 2 (dual), 3 (triple)
     #[arg(short = 'd', long, value_name = "1|2|3")]
     layout: Option<u8>,
+
+    /// Show a grid of pages, columns by rows (e.g. 3x2)
+    #[arg(long, value_name = "COLSxROWS", value_parser = parse_grid_arg)]
+    grid: Option<PageLayout>,
+
+    /// Page background color outside night mode, as hex (e.g. f4ecd8)
+    #[arg(long, value_name = "RRGGBB", value_parser = parse_color_arg)]
+    bg: Option<[u8; 3]>,
+
+    /// Start in continuous scroll mode
+    #[arg(long)]
+    continuous: bool,
+
+    /// In 2-up layout, show the first page alone as a cover
+    #[arg(long, overrides_with = "no_cover")]
+    cover: bool,
+
+    /// In 2-up layout, pair the first page, even if the config sets cover
+    #[arg(long, overrides_with = "cover")]
+    no_cover: bool,
+
+    /// Don't restore or save the last-read position
+    #[arg(long)]
+    no_resume: bool,
+
+    /// Print a document that fits on one screen and exit, like less -F
+    #[arg(short = 'F', long)]
+    quit_if_one_page: bool,
+
+    // As a string, since clippy would have NO_COLOR in backticks, which
+    // --help prints as they are
+    #[arg(
+        long,
+        help = "Plain status bar and background, without styling (also NO_COLOR)"
+    )]
+    no_color: bool,
+
+    /// Read the text of scanned pages with tesseract, for text mode and copying
+    #[arg(long)]
+    ocr: bool,
+
+    /// Password for encrypted PDFs
+    #[arg(long, value_name = "PASSWORD", global = true)]
+    password: Option<String>,
+
+    /// Render quality: fast skips anti-aliasing and smoothing [default: high]
+    #[arg(long, value_name = "fast|balanced|high", value_parser = ["fast", "balanced", "high"])]
+    quality: Option<String>,
+
+    /// Pages to render ahead of and behind the visible ones (0-50) [default: 5]
+    #[arg(long, value_name = "N")]
+    preload: Option<usize>,
+
+    /// Render at a fixed resolution instead of one fitted to the terminal (also used by :export)
+    #[arg(long, value_name = "DPI", value_parser = clap::value_parser!(u32).range(18..=1200))]
+    dpi: Option<u32>,
+
+    /// Render this many times sharper than fits the terminal, for high-DPI screens (1-4)
+    #[arg(long, value_name = "FACTOR", value_parser = parse_scale_factor_arg, conflicts_with = "dpi")]
+    scale_factor: Option<f32>,
+
+    /// Background render threads (1-16) [default: one per core, up to 4]
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+
+    /// Memory budget for rendered pages, in megabytes
+    #[arg(long, value_name = "MB", default_value_t = cache::DEFAULT_BUDGET >> 20)]
+    cache_mb: usize,
+
+    /// Keep rendered pages on disk, so reopening large documents is fast
+    #[arg(long)]
+    disk_cache: bool,
+
+    /// Image protocol to draw pages with, instead of the detected one
+    #[arg(long, value_name = "kitty|sixel|iterm|halfblocks", value_parser = parse_protocol_arg)]
+    protocol: Option<ProtocolType>,
+
+    /// Read settings from this file instead of the default config.toml
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Reload the document when the file changes
+    #[arg(short, long)]
+    watch: bool,
+
+    /// Presentation mode: fullscreen, turning pages on a timer (Space pauses)
+    #[arg(long)]
+    present: bool,
+
+    /// Seconds per page in presentation mode
+    #[arg(long, value_name = "SECS", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+
+    /// In presentation mode, start over after the last page
+    #[arg(long = "loop")]
+    loop_pages: bool,
+
+    /// Turn from the last page to the first and back (implied by --loop)
+    #[arg(long)]
+    wrap: bool,
+
+    /// Print version
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// With --version, also print the mupdf release, target, features and build profile
+    #[arg(long, requires = "version")]
+    verbose: bool,
 }
 
 #[derive(Subcommand)]
 enum Command {
     /// Update tpdf to the latest version
     Update,
+    /// Show what tpdf detects about the terminal, to debug missing images
+    Doctor,
+    /// Print a document's text to stdout
+    Text {
+        /// Path to PDF file
+        path: String,
+
+        /// Pages to print, like 3 or 1-10 (default: all)
+        #[arg(long, value_name = "RANGE")]
+        pages: Option<String>,
+
+        /// Printed between pages [default: form feed]
+        #[arg(
+            long,
+            value_name = "SEP",
+            default_value = "\u{c}",
+            hide_default_value = true
+        )]
+        separator: String,
+
+        /// Print a JSON array of {"page", "text"} objects instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a document's metadata, page size and outline
+    Info {
+        /// Path to PDF file
+        path: String,
+
+        /// Print JSON with every page's size and label instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Save pages as PNG files, or as one animated GIF
+    Export {
+        /// Path to PDF file
+        path: String,
+
+        /// Pages to export, like 3 or 1-10 (default: all)
+        #[arg(long, value_name = "RANGE")]
+        pages: Option<String>,
+
+        /// Write an animated GIF of the pages to FILE instead
+        #[arg(long, value_name = "FILE")]
+        gif: Option<String>,
+
+        /// Milliseconds each page is shown in the GIF
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        delay: u32,
+
+        /// Render scale, 1 being 72 dpi [default: 2 for PNG, 1 for GIF]
+        #[arg(long, value_name = "SCALE", value_parser = parse_scale_arg, conflicts_with = "dpi")]
+        scale: Option<f32>,
+
+        /// Render resolution, instead of --scale
+        #[arg(long, value_name = "DPI", value_parser = clap::value_parser!(u32).range(18..=1200))]
+        dpi: Option<u32>,
+    },
+    /// Time rendering the first pages, to pick preload and quality
+    Bench {
+        /// Path to PDF file
+        path: String,
+
+        /// Number of pages to render, from the first [default: 10]
+        #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        pages: Option<usize>,
+
+        /// Render scale, 1 being 72 dpi [default: fitted to the terminal]
+        #[arg(long, value_name = "SCALE", value_parser = parse_scale_arg)]
+        scale: Option<f32>,
+
+        /// Print JSON with every page's time and size instead
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the lines of a document's text that match a regex
+    Grep {
+        /// Path to PDF file
+        path: String,
+
+        /// Regular expression to search for
+        pattern: String,
+
+        /// Match case-insensitively
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Print the number of matches on each page instead
+        #[arg(short, long)]
+        count: bool,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    if matches!(cli.command, Some(Command::Update)) {
-        return update::self_update();
+    if cli.version {
+        update::print_version(cli.verbose);
+        return Ok(());
+    }
+    if let Some(command) = &cli.command {
+        return run_command(command, &cli);
     }
 
-    let Some(path) = cli.path else {
+    if cli.paths.is_empty() {
         eprintln!("tpdf - Terminal PDF viewer\n");
-        eprintln!("Usage: tpdf <file.pdf>");
+        eprintln!("Usage: tpdf <file.pdf>...");
+        eprintln!("       some-tool | tpdf -");
+        eprintln!("       tpdf https://example.com/paper.pdf");
+        eprintln!("       tpdf text <file.pdf>");
+        eprintln!("       tpdf info <file.pdf>");
+        eprintln!("       tpdf export <file.pdf> [--gif out.gif]");
+        eprintln!("       tpdf grep <file.pdf> <pattern>");
+        eprintln!("       tpdf bench <file.pdf>");
+        eprintln!("       tpdf doctor");
         eprintln!("       tpdf update\n");
         eprintln!("Run 'tpdf --help' for more options.");
         std::process::exit(1);
-    };
+    }
+    if cli.paths.iter().filter(|p| *p == "-").count() > 1 {
+        return Err("stdin can only be read once".into());
+    }
 
-    let config = AppConfig {
-        dark_mode: cli.night,
-        fullscreen: cli.fullscreen,
-        start_page: cli.page.unwrap_or(1).saturating_sub(1),
-        layout: match cli.layout {
-            Some(2) => PageLayout::Dual,
-            Some(3) => PageLayout::Triple,
-            _ => PageLayout::Single,
-        },
-    };
+    // Drain a piped document before touching the terminal, so the protocol
+    // query below reads the terminal's reply rather than PDF bytes.
+    let mut sources = Vec::with_capacity(cli.paths.len());
+    let mut fragments = Vec::with_capacity(cli.paths.len());
+    let mut session_keys = Vec::with_capacity(cli.paths.len());
+    let mut downloads = Vec::new();
+    for path in &cli.paths {
+        let (path, fragment) = split_fragment(path);
+        fragments.push(fragment);
+        session_keys.push(is_url(&path).then(|| path.clone()));
+        sources.push(if path == "-" {
+            if cli.watch {
+                return Err("--watch needs a file path, not stdin".into());
+            }
+            Source::Memory {
+                data: read_stdin()?.into(),
+                magic: "application/pdf".to_string(),
+            }
+        } else if is_url(&path) {
+            if cli.watch {
+                return Err("--watch needs a file path, not a URL".into());
+            }
+            let download = Download::fetch(&path, downloads.len())?;
+            let file = download.file.to_string_lossy().into_owned();
+            downloads.push(download);
+            Source::File(file)
+        } else {
+            Source::File(path)
+        });
+    }
 
-    let picker = Picker::from_query_stdio()?;
+    let user_config = load_config(&cli)?;
+
+    let protocol = cli.protocol.or(user_config.protocol);
+    let picker = graphics::picker(protocol);
+    let notice = graphics::fallback_notice(&picker, protocol);
     let (term_cols, term_rows) = crossterm::terminal::size()?;
 
-    let mut app = app::App::new(&path, picker, term_cols, term_rows, &config)?;
+    let mut tabs = sources
+        .into_iter()
+        .zip(fragments)
+        .zip(session_keys)
+        .map(|((source, fragment), session_key)| {
+            let mut config = app_config(&cli, &user_config);
+            config.notice.clone_from(&notice);
+            config.session_key = session_key;
+            match fragment {
+                Some(Fragment::Page(page)) => {
+                    config.start_page = Some(page.saturating_sub(1));
+                    config.dest = None;
+                }
+                Some(Fragment::NamedDest(name)) => config.dest = Some(name),
+                None => {}
+            }
+            app::App::new(source, picker.clone(), term_cols, term_rows, config)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Like less -F: a document that fits on one screen is printed, not
+    // opened in a full-screen view
+    if cli.quit_if_one_page && tabs.len() == 1 && tabs[0].fits_one_screen() {
+        return tabs[0].print_inline();
+    }
 
     let mut terminal = ratatui::init();
-    let result = app.run(&mut terminal);
+    execute!(stdout(), EnableMouseCapture)?;
+    // ratatui's own panic hook restores the terminal but knows nothing
+    // about mouse capture, so release it first.
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = execute!(stdout(), DisableMouseCapture);
+        hook(info);
+    }));
+
+    let result = app::App::run_tabs(&mut tabs, &mut terminal);
+    let _ = execute!(stdout(), DisableMouseCapture);
     ratatui::restore();
+    // Delete the downloaded documents; returning early drops them too
+    drop(downloads);
 
     result?;
     Ok(())
 }
+
+/// The config file named by `--config` or `TPDF_CONFIG`, else the default.
+fn load_config(cli: &Cli) -> Result<Config, String> {
+    let explicit = cli.config.clone().or_else(|| {
+        std::env::var_os("TPDF_CONFIG")
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+    });
+    explicit.map_or_else(|| Ok(Config::load()), |path| Config::load_from(&path))
+}
+
+/// Run one of the headless subcommands.
+fn run_command(command: &Command, cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let password = cli.password.as_deref();
+    match command {
+        Command::Update => update::self_update(),
+        Command::Doctor => graphics::print_report(cli.protocol.or(load_config(cli)?.protocol)),
+        Command::Text {
+            path,
+            pages,
+            separator,
+            json,
+        } => extract::print_text(path, pages.as_deref(), separator, *json, password),
+        Command::Info { path, json } => extract::print_info(path, *json, password),
+        Command::Export {
+            path,
+            pages,
+            gif,
+            delay,
+            scale,
+            dpi,
+        } => extract::export(
+            path,
+            pages.as_deref(),
+            gif.as_deref(),
+            *delay,
+            scale.or_else(|| dpi.map(app::dpi_scale)),
+            password,
+        ),
+        Command::Bench {
+            path,
+            pages,
+            scale,
+            json,
+        } => {
+            let quality = cli
+                .quality
+                .as_deref()
+                .and_then(Quality::from_name)
+                .or(load_config(cli)?.quality)
+                .unwrap_or(Quality::High);
+            extract::bench(path, *pages, *scale, quality, *json, password)
+        }
+        Command::Grep {
+            path,
+            pattern,
+            ignore_case,
+            count,
+        } => {
+            let found = extract::print_matches(path, pattern, *ignore_case, *count, password)?;
+            // Like grep, exit with 1 when nothing matched
+            if !found {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Viewer settings from the command line, falling back to the config file.
+/// The view (layout, fit and color mode) goes command line, then what was
+/// remembered for the file, then the config file, then the built-in
+/// default; the last two are merged here, the rest by the viewer.
+fn app_config(cli: &Cli, user_config: &Config) -> AppConfig {
+    let default_paper = Paper::default();
+    // The config only turns these on, and the --no- flags keep it from that
+    let config_fullscreen = user_config.fullscreen && !cli.no_fullscreen;
+    let config_cover = user_config.cover && !cli.no_cover;
+    AppConfig {
+        color_mode: cli.night.then_some(ColorMode::Night),
+        default_color_mode: user_config.color_mode.unwrap_or(ColorMode::Normal),
+        fullscreen: cli.present || cli.fullscreen || config_fullscreen,
+        start_page: cli.page.map(|p| p.saturating_sub(1)),
+        dest: cli.dest.clone(),
+        scroll: cli.scroll,
+        fit: cli.fit.as_deref().and_then(FitMode::from_name),
+        default_fit: user_config.fit.unwrap_or(FitMode::Page),
+        layout: match cli.layout {
+            _ if cli.continuous => Some(PageLayout::Continuous),
+            _ if cli.grid.is_some() => cli.grid,
+            Some(2) => Some(PageLayout::Dual),
+            Some(3) => Some(PageLayout::Triple),
+            Some(_) => Some(PageLayout::Single),
+            None => None,
+        },
+        default_layout: user_config.layout.unwrap_or(PageLayout::Single),
+        cover: cli.cover || config_cover,
+        paper: Paper {
+            day: cli
+                .bg
+                .or(user_config.background)
+                .unwrap_or(default_paper.day),
+            night: user_config.night_background.unwrap_or(default_paper.night),
+        },
+        quality: cli
+            .quality
+            .as_deref()
+            .and_then(Quality::from_name)
+            .or(user_config.quality)
+            .unwrap_or(Quality::High),
+        show_status_bar: !user_config.hide_status_bar,
+        ocr: cli.ocr,
+        no_color: cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()),
+        status_segments: user_config.status_segments.unwrap_or(StatusSegments::FULL),
+        password: cli.password.clone(),
+        resume: !cli.no_resume,
+        session_key: None,
+        preload: cli
+            .preload
+            .or(user_config.preload)
+            .unwrap_or(app::DEFAULT_PRELOAD)
+            .min(app::MAX_PRELOAD),
+        render_threads: cli
+            .threads
+            .or(user_config.threads)
+            .map_or_else(app::default_render_threads, |n| {
+                n.clamp(1, app::MAX_RENDER_THREADS)
+            }),
+        disk_cache: (cli.disk_cache || user_config.disk_cache).then(|| {
+            user_config
+                .disk_cache_mb
+                .map_or(disk_cache::DEFAULT_BUDGET, |mb| (mb as u64) << 20)
+        }),
+        dpi: cli.dpi,
+        scale_factor: cli.scale_factor.unwrap_or(1.0),
+        cache_budget: cli.cache_mb.saturating_mul(1024 * 1024),
+        watch: cli.watch,
+        present: cli.present,
+        advance_interval: Duration::from_secs(cli.interval),
+        loop_pages: cli.loop_pages,
+        wrap_navigation: cli.wrap || cli.loop_pages || user_config.wrap_navigation,
+        vim_motions: user_config.vim_motions,
+        text_scroll: user_config
+            .text_scroll
+            .map_or(app::DEFAULT_TEXT_SCROLL, |n| {
+                n.clamp(1, app::MAX_TEXT_SCROLL)
+            }),
+        separators: user_config.separators,
+        separator_width: user_config
+            .separator_width
+            .map_or(app::DEFAULT_SEPARATOR_WIDTH, |n| {
+                n.clamp(1, app::MAX_SEPARATOR_WIDTH.into()) as u16
+            }),
+        separator_color: user_config.separator_color,
+        keymap: user_config.keymap.clone(),
+        notice: None,
+    }
+}
+
+/// Where a `#` fragment on a path says to start.
+enum Fragment {
+    /// 1-based, as in `report.pdf#page=42`.
+    Page(usize),
+    /// As in `report.pdf#nameddest=intro`.
+    NamedDest(String),
+}
+
+/// Split the PDF open parameters off a path, like `report.pdf#page=42` or
+/// `report.pdf#nameddest=intro`; other parameters (`zoom=`, ...) are
+/// ignored. Paths that exist as given keep their `#`.
+fn split_fragment(path: &str) -> (String, Option<Fragment>) {
+    let Some((file, params)) = path.rsplit_once('#') else {
+        return (path.to_string(), None);
+    };
+    if file.is_empty() || std::path::Path::new(path).exists() {
+        return (path.to_string(), None);
+    }
+    let fragment = params
+        .split('&')
+        .find_map(|param| match param.split_once('=')? {
+            ("page", page) => page.parse().ok().map(Fragment::Page),
+            ("nameddest", name) if !name.is_empty() => Some(Fragment::NamedDest(name.to_string())),
+            _ => None,
+        });
+    (file.to_string(), fragment)
+}
+
+fn is_url(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// A document fetched from a URL into a directory of its own, deleted when
+/// this is dropped. Its session is saved under the URL, not this path.
+struct Download {
+    dir: PathBuf,
+    file: PathBuf,
+}
+
+impl Download {
+    /// Fetch `url`, the `index`th download of this run.
+    fn fetch(url: &str, index: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = download_dir(index)?;
+        let download = Self {
+            file: dir.join(url_file_name(url)),
+            dir,
+        };
+
+        eprintln!("Downloading {url}...");
+        // -f turns HTTP errors into failures, -L follows redirects
+        let output = std::process::Command::new("curl")
+            .args(["-fsSL", "--proto", "=http,https", url, "-o"])
+            .arg(&download.file)
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => "opening a URL needs curl installed".to_string(),
+                _ => format!("couldn't run curl: {e}"),
+            })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // "curl: (22) The requested URL returned error: 404" -> the reason
+            let reason = stderr
+                .trim()
+                .rsplit_once(") ")
+                .map_or_else(|| stderr.trim(), |(_, reason)| reason);
+            return Err(format!("couldn't download {url}: {reason}").into());
+        }
+        Ok(download)
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A new directory only this user can read, named after the process so
+/// two instances never share one. A directory left by an earlier process
+/// with the same id is skipped rather than reused.
+fn download_dir(index: usize) -> std::io::Result<PathBuf> {
+    let parent = dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("tpdf")
+        .join("downloads");
+    std::fs::create_dir_all(&parent)?;
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    let mut attempt = 0;
+    loop {
+        let dir = parent.join(format!("{}-{index}-{attempt}", std::process::id()));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The last segment of the URL's path, for the tab and the document type;
+/// `document.pdf` when there isn't one.
+fn url_file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split_once('/'))
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && Path::new(name).extension().is_some());
+    name.map_or_else(|| "document.pdf".to_string(), str::to_string)
+}
+
+/// Read a piped document from stdin, then point stdin back at the
+/// controlling terminal so keyboard input and terminal queries still work.
+fn read_stdin() -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut data)?;
+    if data.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "no document on stdin",
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+        let tty = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")?;
+        // SAFETY: both descriptors are open for the duration of the call.
+        if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(data)
+}
+
+fn parse_grid_arg(s: &str) -> Result<PageLayout, String> {
+    PageLayout::parse_grid(s).ok_or_else(|| {
+        format!(
+            "'{s}' isn't a grid like 3x2 (each side 1-{})",
+            app::MAX_GRID
+        )
+    })
+}
+
+/// A fraction of the page, clamped to 0.0-1.0.
+fn parse_scroll_arg(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(f) if f.is_finite() => Ok(f.clamp(0.0, 1.0)),
+        _ => Err(format!("'{s}' isn't a fraction like 0.3")),
+    }
+}
+
+/// A render scale between 0.25 (18 dpi) and about 16 (1200 dpi).
+fn parse_scale_arg(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(f) if (0.25..=16.0).contains(&f) => Ok(f),
+        _ => Err(format!("'{s}' isn't a scale between 0.25 and 16")),
+    }
+}
+
+fn parse_scale_factor_arg(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(f) if (1.0..=4.0).contains(&f) => Ok(f),
+        _ => Err(format!("'{s}' isn't a scale factor between 1 and 4")),
+    }
+}
+
+fn parse_protocol_arg(s: &str) -> Result<ProtocolType, String> {
+    graphics::protocol_from_name(s)
+        .ok_or_else(|| format!("unknown protocol '{s}' ({})", graphics::PROTOCOL_NAMES))
+}
+
+fn parse_color_arg(s: &str) -> Result<[u8; 3], String> {
+    config::parse_color(s).ok_or_else(|| format!("'{s}' isn't a hex color like f4ecd8"))
+}
@@ -0,0 +1,48 @@
+use std::error::Error;
+use std::io::{self, Cursor, Write};
+use std::process::{Command, Stdio};
+
+use image::{DynamicImage, ImageFormat};
+
+/// Scale pages are rendered at for recognition: tesseract reads best at
+/// about 300 dpi.
+pub const OCR_SCALE: f32 = 300.0 / 72.0;
+
+/// Read the text off a rendered page with the `tesseract` command, for
+/// scans that have no text to extract. The page is piped in, so no file
+/// is left where other users could get at it.
+pub fn recognize(img: &DynamicImage) -> Result<String, Box<dyn Error>> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let child = Command::new("tesseract")
+        .args(["stdin", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err("tesseract isn't installed".into());
+        }
+        child => child?,
+    };
+    // Fed from another thread, so a full stdout pipe can't stall both
+    let feeder = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(&png);
+        })
+    });
+    let output = child.wait_with_output()?;
+    if let Some(feeder) = feeder {
+        let _ = feeder.join();
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr
+            .lines()
+            .rfind(|l| !l.trim().is_empty())
+            .unwrap_or("tesseract failed");
+        return Err(reason.into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Page turns remembered for the estimate, so it follows the recent pace.
+const SAMPLES: usize = 10;
+
+/// Turns needed before there is an estimate at all.
+const MIN_TURNS: usize = 3;
+
+/// A pause longer than this (a break, a phone call) starts the estimate
+/// afresh rather than dragging the pace down.
+const MAX_GAP: Duration = Duration::from_mins(10);
+
+/// Recent page turns, for estimating how long the rest of the document
+/// will take to read.
+pub struct ReadingPace {
+    turns: VecDeque<(Instant, usize)>,
+}
+
+impl ReadingPace {
+    pub fn new(page: usize) -> Self {
+        Self {
+            turns: VecDeque::from([(Instant::now(), page)]),
+        }
+    }
+
+    /// Note the page now being read. Turning forward by up to `step` pages
+    /// (a screenful) counts towards the pace; jumps and going back start
+    /// over.
+    pub fn note(&mut self, page: usize, step: usize) {
+        let now = Instant::now();
+        if let Some(&(at, last)) = self.turns.back() {
+            if page == last {
+                return;
+            }
+            if page < last || page - last > step || now - at > MAX_GAP {
+                self.turns.clear();
+            }
+        }
+        self.turns.push_back((now, page));
+        if self.turns.len() > SAMPLES + 1 {
+            self.turns.pop_front();
+        }
+    }
+
+    /// Time left to read `pages_left` more pages at the recent pace, or
+    /// `None` until there have been enough turns to tell.
+    pub fn remaining(&self, pages_left: usize) -> Option<Duration> {
+        if self.turns.len() <= MIN_TURNS {
+            return None;
+        }
+        let (&(first_at, first), &(last_at, last)) = (self.turns.front()?, self.turns.back()?);
+        let per_page = (last_at - first_at).as_secs_f64() / (last - first) as f64;
+        Some(Duration::from_secs_f64(per_page * pages_left as f64))
+    }
+}
+
+/// A reading time like `1h05m`, `12m` or `<1m`.
+pub fn format_remaining(d: Duration) -> String {
+    let mins = d.as_secs() / 60;
+    match mins {
+        0 => "<1m".into(),
+        1..60 => format!("{mins}m"),
+        _ => format!("{}h{:02}m", mins / 60, mins % 60),
+    }
+}
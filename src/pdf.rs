@@ -1,30 +1,855 @@
-use image::{DynamicImage, ImageBuffer, RgbImage};
-use mupdf::{Colorspace, Document, Matrix};
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::error::Error;
+use std::fmt::Write;
+use std::io::{self, Read};
+use std::sync::Arc;
 
+use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+use mupdf::pdf::PdfObject;
+use mupdf::{Colorspace, Context, Document, Matrix, MetadataName, TextPageFlags};
+use serde::Serialize;
+
+use crate::document::{DocumentSource, OpenError};
+
+/// Part of a page, as fractions of its width and height.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Region {
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        w: 1.0,
+        h: 1.0,
+    };
+
+    /// Re-express this region relative to `outer`, both being fractions of
+    /// the same page.
+    #[must_use]
+    pub fn within(self, outer: Self) -> Self {
+        Self {
+            x: (self.x - outer.x) / outer.w,
+            y: (self.y - outer.y) / outer.h,
+            w: self.w / outer.w,
+            h: self.h / outer.h,
+        }
+    }
+
+    /// The inverse of [`within`](Self::within): this region of `outer`, as
+    /// a region of the whole page.
+    #[must_use]
+    pub fn inside(self, outer: Self) -> Self {
+        Self {
+            x: self.x.mul_add(outer.w, outer.x),
+            y: self.y.mul_add(outer.h, outer.y),
+            w: self.w * outer.w,
+            h: self.h * outer.h,
+        }
+    }
+
+    /// Whether the two regions share any part of the page.
+    #[must_use]
+    pub fn overlaps(self, other: Self) -> bool {
+        self.x < other.x + other.w
+            && other.x < self.x + self.w
+            && self.y < other.y + other.h
+            && other.y < self.y + self.h
+    }
+
+    /// Whether the point `(x, y)`, in fractions of the page, lies inside.
+    #[must_use]
+    pub fn contains(self, x: f32, y: f32) -> bool {
+        (self.x..self.x + self.w).contains(&x) && (self.y..self.y + self.h).contains(&y)
+    }
+
+    /// Where this region ends up once the page is turned clockwise by
+    /// `rotation` degrees (a multiple of 90).
+    #[must_use]
+    pub fn rotated(self, rotation: u16) -> Self {
+        match rotation {
+            90 => Self {
+                x: 1.0 - self.y - self.h,
+                y: self.x,
+                w: self.h,
+                h: self.w,
+            },
+            180 => Self {
+                x: 1.0 - self.x - self.w,
+                y: 1.0 - self.y - self.h,
+                ..self
+            },
+            270 => Self {
+                x: self.y,
+                y: 1.0 - self.x - self.w,
+                w: self.h,
+                h: self.w,
+            },
+            _ => self,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum LinkTarget {
+    Page(usize),
+    Uri(String),
+}
+
+#[derive(Clone)]
+pub struct LinkInfo {
+    pub region: Region,
+    pub target: LinkTarget,
+}
+
+/// A filled-in form field, positioned like [`LinkInfo`].
+pub struct FieldInfo {
+    pub region: Region,
+    pub name: String,
+    pub value: String,
+}
+
+/// A line of a page's text, positioned like [`LinkInfo`]. Lines come in
+/// reading order, those of one block (a paragraph, a caption) together.
+pub struct TextSpan {
+    pub text: String,
+    pub region: Region,
+    /// Index of the block the line belongs to, for regrouping paragraphs.
+    pub block: usize,
+    /// Where each character of `text` sits, in order.
+    pub chars: Vec<Region>,
+    /// Font size of the line's largest characters, in points.
+    pub size: f32,
+}
+
+/// The text of a note or comment annotation, positioned like [`LinkInfo`].
+pub struct NoteInfo {
+    pub region: Region,
+    pub author: String,
+    pub contents: String,
+}
+
+/// Fields from the document's info dictionary; `None` where unset.
+#[derive(Default, Serialize)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub creator: Option<String>,
+    pub created: Option<String>,
+    /// Format and version, e.g. "PDF 1.7".
+    pub version: Option<String>,
+}
+
+/// An entry of the document's outline (its table of contents), with the
+/// entries nested under it.
+pub struct OutlineEntry {
+    pub title: String,
+    /// `None` for entries that point outside the document or nowhere.
+    pub page: Option<usize>,
+    pub children: Vec<Self>,
+}
+
+/// The page numbers printed in the document, from its `/PageLabels`, e.g.
+/// roman numerals for the front matter. Empty when it doesn't define any.
+#[derive(Default)]
+pub struct PageLabels {
+    /// Sorted by first page.
+    ranges: Vec<LabelRange>,
+}
+
+/// A run of pages numbered in one style, from `first` on.
+struct LabelRange {
+    first: usize,
+    style: NumberStyle,
+    prefix: String,
+    /// Number of the first page in the run.
+    start: usize,
+}
+
+#[derive(Clone, Copy)]
+enum NumberStyle {
+    Decimal,
+    UpperRoman,
+    LowerRoman,
+    UpperLetters,
+    LowerLetters,
+    /// Just the prefix.
+    None,
+}
+
+impl PageLabels {
+    /// The printed label of page `idx`, if the document labels its pages.
+    #[must_use]
+    pub fn label(&self, idx: usize) -> Option<String> {
+        let range = self.ranges.iter().rev().find(|r| r.first <= idx)?;
+        let n = range.start + (idx - range.first);
+        let number = match range.style {
+            NumberStyle::Decimal => n.to_string(),
+            NumberStyle::UpperRoman => roman(n).to_uppercase(),
+            NumberStyle::LowerRoman => roman(n),
+            NumberStyle::UpperLetters => letters(n, b'A'),
+            NumberStyle::LowerLetters => letters(n, b'a'),
+            NumberStyle::None => String::new(),
+        };
+        Some(format!("{}{number}", range.prefix))
+    }
+
+    /// The first page labeled `label`, ignoring case when nothing matches
+    /// exactly. Worked out from each run's numbering rather than by listing
+    /// every label, which for letter labels grow with the page count.
+    #[must_use]
+    pub fn page_for_label(&self, label: &str, page_count: usize) -> Option<usize> {
+        self.find_label(label, page_count, |l| l == label)
+            .or_else(|| self.find_label(label, page_count, |l| l.eq_ignore_ascii_case(label)))
+    }
+
+    /// The first page whose label `matches`, among the pages each run
+    /// could give `label` to.
+    fn find_label(
+        &self,
+        label: &str,
+        page_count: usize,
+        matches: impl Fn(&str) -> bool,
+    ) -> Option<usize> {
+        self.ranges
+            .iter()
+            .filter_map(|range| {
+                let prefix = label.get(..range.prefix.len())?;
+                if !prefix.eq_ignore_ascii_case(&range.prefix) {
+                    return None;
+                }
+                let rest = &label[range.prefix.len()..];
+                let n = match range.style {
+                    // Every page of the run has the same label
+                    NumberStyle::None => rest.is_empty().then_some(range.start)?,
+                    style => style.parse(rest)?,
+                };
+                Some(range.first + n.checked_sub(range.start)?)
+            })
+            .filter(|&idx| idx < page_count && self.label(idx).is_some_and(|l| matches(&l)))
+            .min()
+    }
+}
+
+impl NumberStyle {
+    /// The number `s` stands for in this style, whatever its case; `None`
+    /// if it isn't written in it, or the style has no numbers.
+    fn parse(self, s: &str) -> Option<usize> {
+        let s = s.to_ascii_lowercase();
+        match self {
+            Self::Decimal => s.parse().ok(),
+            Self::UpperRoman | Self::LowerRoman => from_roman(&s),
+            Self::UpperLetters | Self::LowerLetters => {
+                let first = s.bytes().next().filter(u8::is_ascii_lowercase)?;
+                s.bytes()
+                    .all(|b| b == first)
+                    .then(|| (s.len() - 1) * 26 + usize::from(first - b'a') + 1)
+            }
+            Self::None => None,
+        }
+    }
+}
+
+const ROMAN_NUMERALS: [(usize, &str); 13] = [
+    (1000, "m"),
+    (900, "cm"),
+    (500, "d"),
+    (400, "cd"),
+    (100, "c"),
+    (90, "xc"),
+    (50, "l"),
+    (40, "xl"),
+    (10, "x"),
+    (9, "ix"),
+    (5, "v"),
+    (4, "iv"),
+    (1, "i"),
+];
+
+/// Lowercase roman numeral for `n`.
+fn roman(mut n: usize) -> String {
+    let mut out = String::new();
+    for (value, numeral) in ROMAN_NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// The value of a lowercase roman numeral, read greedily like [`roman`]
+/// writes it.
+fn from_roman(mut s: &str) -> Option<usize> {
+    let mut n = 0;
+    for (value, numeral) in ROMAN_NUMERALS {
+        while let Some(rest) = s.strip_prefix(numeral) {
+            n += value;
+            s = rest;
+        }
+    }
+    (s.is_empty() && n > 0).then_some(n)
+}
+
+/// Letter numbering as PDF defines it: A-Z, then AA-ZZ, AAA-ZZZ, ...
+fn letters(n: usize, first: u8) -> String {
+    let Some(n) = n.checked_sub(1) else {
+        return String::new();
+    };
+    let letter = char::from(first + (n % 26) as u8);
+    letter.to_string().repeat(n / 26 + 1)
+}
+
+/// Where a document is read from. Render workers each open their own
+/// handle, so in-memory documents share one buffer instead of copying it.
+#[derive(Clone)]
+pub enum Source {
+    File(String),
+    Memory { data: Arc<[u8]>, magic: String },
+}
+
+impl Source {
+    /// The file path, for documents that have one.
+    #[must_use]
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::File(path) => Some(path),
+            Self::Memory { .. } => None,
+        }
+    }
+
+    /// Short name for the document, as shown on its tab.
+    #[must_use]
+    pub fn name(&self) -> String {
+        match self {
+            Self::File(path) => std::path::Path::new(path)
+                .file_name()
+                .map_or_else(|| path.clone(), |n| n.to_string_lossy().into_owned()),
+            Self::Memory { .. } => "stdin".to_string(),
+        }
+    }
+
+    /// Size of the document in bytes.
+    #[must_use]
+    pub fn size(&self) -> Option<u64> {
+        match self {
+            Self::File(path) => std::fs::metadata(path).ok().map(|m| m.len()),
+            Self::Memory { data, .. } => Some(data.len() as u64),
+        }
+    }
+
+    /// Why the document couldn't be opened, in plain words rather than
+    /// mupdf's: missing, unreadable, not a PDF at all, or damaged.
+    #[must_use]
+    pub fn open_failure(&self, e: &mupdf::Error) -> String {
+        let header = match self {
+            Self::File(path) => match read_header(path) {
+                Ok(header) => header,
+                Err(io) => {
+                    let reason = match io.kind() {
+                        io::ErrorKind::NotFound => "File not found".to_string(),
+                        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+                        _ => io.to_string(),
+                    };
+                    return format!("{}: {reason}", self.name());
+                }
+            },
+            Self::Memory { data, .. } => data[..data.len().min(HEADER_WINDOW)].to_vec(),
+        };
+        let detail = e.to_string();
+        let reason = if !header.windows(5).any(|w| w == b"%PDF-") {
+            "Not a PDF file".to_string()
+        } else if detail.to_lowercase().contains("permission denied") {
+            "Permission denied".to_string()
+        } else {
+            format!("File is corrupted or truncated ({detail})")
+        };
+        format!("{}: {reason}", self.name())
+    }
+}
+
+/// The release of the mupdf library tpdf runs on.
+///
+/// The bindings leave out `FZ_VERSION`, but mupdf stamps it on every
+/// document it creates, as the producer `MuPDF 1.27.0`, so it's read back
+/// from a blank one.
+#[must_use]
+pub fn mupdf_version() -> Option<String> {
+    let doc = mupdf::pdf::PdfDocument::new().ok()?;
+    let info = doc.catalog().ok()?.get_dict("Info").ok()??;
+    let producer = info.get_dict("Producer").ok()??;
+    let producer = producer.as_string().ok()?;
+    Some(producer.strip_prefix("MuPDF ")?.to_string())
+}
+
+/// How much larger than the body text a line must be set to count as a
+/// heading.
+const HEADING_RATIO: f32 = 1.2;
+
+/// PDF readers accept the `%PDF-` header anywhere in a file's first
+/// kilobyte.
+const HEADER_WINDOW: usize = 1024;
+
+fn read_header(path: &str) -> io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_WINDOW);
+    std::fs::File::open(path)?
+        .take(HEADER_WINDOW as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// Turn anti-aliasing of text and line art on or off for renders made on
+/// this thread. mupdf keeps the setting per thread, so each render worker
+/// sets it for itself.
+pub fn set_antialiasing(enabled: bool) {
+    let bits = if enabled { 8 } else { 0 };
+    Context::get().set_aa_level(bits);
+}
+
+/// A document opened with mupdf: PDF, and the EPUB, XPS and other formats
+/// it reads.
 pub struct PdfDocument {
     doc: Document,
+    source: Source,
+    /// The password that unlocked the document, for reopening it.
+    password: Option<String>,
+    /// The handle [`pdf_objects`](Self::pdf_objects) reads through.
+    objects: OnceCell<Option<mupdf::pdf::PdfDocument>>,
 }
 
 impl PdfDocument {
-    pub fn open(path: &str) -> Result<Self, mupdf::Error> {
+    /// Open the document at `path`, unlocking it with `password` if it is
+    /// encrypted.
+    ///
+    /// # Errors
+    ///
+    /// [`OpenError::PasswordRequired`] with the locked document when
+    /// `password` is missing or wrong, or mupdf's error when it can't be
+    /// opened at all.
+    pub fn open(path: &str, password: Option<&str>) -> Result<Self, OpenError> {
         let doc = Document::open(path)?;
-        Ok(Self { doc })
+        Self::unlock(doc, Source::File(path.to_string()), password)
+    }
+
+    /// Open a document held in memory; `magic` is a file extension or MIME
+    /// type telling mupdf how to parse it.
+    ///
+    /// # Errors
+    ///
+    /// As for [`open`](Self::open).
+    pub fn open_bytes(
+        data: impl Into<Arc<[u8]>>,
+        magic: &str,
+        password: Option<&str>,
+    ) -> Result<Self, OpenError> {
+        let data = data.into();
+        let doc = Document::from_bytes(&data, magic)?;
+        let source = Source::Memory {
+            data,
+            magic: magic.to_string(),
+        };
+        Self::unlock(doc, source, password)
     }
 
-    pub fn page_count(&self) -> usize {
+    /// Open a document from a file or memory.
+    ///
+    /// # Errors
+    ///
+    /// As for [`open`](Self::open).
+    pub fn open_source(source: &Source, password: Option<&str>) -> Result<Self, OpenError> {
+        match source {
+            Source::File(path) => Self::open(path, password),
+            Source::Memory { data, magic } => Self::open_bytes(Arc::clone(data), magic, password),
+        }
+    }
+
+    fn unlock(doc: Document, source: Source, password: Option<&str>) -> Result<Self, OpenError> {
+        let mut pdf = Self {
+            doc,
+            source,
+            password: None,
+            objects: OnceCell::new(),
+        };
+        if pdf.doc.needs_password()? && !password.is_some_and(|pw| pdf.authenticate(pw)) {
+            return Err(OpenError::PasswordRequired(pdf));
+        }
+        Ok(pdf)
+    }
+
+    /// A page's annotation dictionaries, each with its `/Rect` as a region
+    /// of the page. Empty when the page has no usable page box.
+    fn annotations(&self, page_idx: usize) -> Result<Vec<(PdfObject, Region)>, mupdf::Error> {
+        let Some(pdf) = self.pdf_objects() else {
+            return Ok(Vec::new());
+        };
+        let page = pdf.find_page(page_idx as i32)?;
+        let Some(annots) = page.get_dict("Annots")? else {
+            return Ok(Vec::new());
+        };
+        let page_box = match inherited(&page, "CropBox")? {
+            Some(b) => rect_of(&b)?,
+            None => inherited(&page, "MediaBox")?.map_or(Ok(None), |b| rect_of(&b))?,
+        };
+        let Some([bx0, by0, bx1, by1]) = page_box else {
+            return Ok(Vec::new());
+        };
+        let (w, h) = (bx1 - bx0, by1 - by0);
+        if w <= 0.0 || h <= 0.0 {
+            return Ok(Vec::new());
+        }
+
+        let mut found = Vec::new();
+        for i in 0..annots.len()? {
+            let Some(annot) = annots.get_array(i as i32)? else {
+                continue;
+            };
+            let Some([x0, y0, x1, y1]) =
+                annot.get_dict("Rect")?.map_or(Ok(None), |r| rect_of(&r))?
+            else {
+                continue;
+            };
+            // PDF space has y pointing up from the bottom of the page box
+            let region = Region {
+                x: (x0.min(x1) - bx0) / w,
+                y: (by1 - y0.max(y1)) / h,
+                w: (x1 - x0).abs() / w,
+                h: (y1 - y0).abs() / h,
+            };
+            found.push((annot, region));
+        }
+        Ok(found)
+    }
+
+    /// A second handle on the document for reading raw PDF objects, which
+    /// the generic `Document` doesn't expose. It is opened on first use and
+    /// kept; `None` when the document isn't a PDF.
+    fn pdf_objects(&self) -> Option<&mupdf::pdf::PdfDocument> {
+        self.objects
+            .get_or_init(|| {
+                let mut pdf = match &self.source {
+                    Source::File(path) => mupdf::pdf::PdfDocument::open(path),
+                    Source::Memory { data, .. } => mupdf::pdf::PdfDocument::from_bytes(data),
+                }
+                .ok()?;
+                if let Some(password) = &self.password {
+                    pdf.authenticate(password).ok()?;
+                }
+                Some(pdf)
+            })
+            .as_ref()
+    }
+
+    /// The page's text as laid out, line by line in reading order, with
+    /// where each line and character sits.
+    ///
+    /// # Errors
+    ///
+    /// When the page can't be loaded or its text extracted.
+    pub fn extract_structured(&self, page_idx: usize) -> Result<Vec<TextSpan>, mupdf::Error> {
+        let page = self.doc.load_page(page_idx as i32)?;
+        let bounds = page.bounds()?;
+        let (width, height) = (bounds.x1 - bounds.x0, bounds.y1 - bounds.y0);
+        if width <= 0.0 || height <= 0.0 {
+            return Ok(Vec::new());
+        }
+        let to_region = |x0: f32, y0: f32, x1: f32, y1: f32| Region {
+            x: (x0 - bounds.x0) / width,
+            y: (y0 - bounds.y0) / height,
+            w: (x1 - x0) / width,
+            h: (y1 - y0) / height,
+        };
+
+        let text_page = page.to_text_page(TextPageFlags::empty())?;
+        let mut spans = Vec::new();
+        for (block_idx, block) in text_page.blocks().enumerate() {
+            for line in block.lines() {
+                let mut text = String::new();
+                let mut chars = Vec::new();
+                let (mut x0, mut y0) = (f32::MAX, f32::MAX);
+                let (mut x1, mut y1) = (f32::MIN, f32::MIN);
+                let mut size = 0.0_f32;
+                for c in line.chars() {
+                    let Some(ch) = c.char() else {
+                        continue;
+                    };
+                    let quad = c.quad();
+                    let corners = [quad.ul, quad.ur, quad.ll, quad.lr];
+                    let left = corners.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+                    let top = corners.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+                    let right = corners.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+                    let bottom = corners.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+                    (x0, y0) = (x0.min(left), y0.min(top));
+                    (x1, y1) = (x1.max(right), y1.max(bottom));
+                    size = size.max(c.size());
+                    text.push(ch);
+                    chars.push(to_region(left, top, right, bottom));
+                }
+                if !chars.is_empty() {
+                    spans.push(TextSpan {
+                        text,
+                        region: to_region(x0, y0, x1, y1),
+                        block: block_idx,
+                        chars,
+                        size,
+                    });
+                }
+            }
+        }
+        Ok(spans)
+    }
+}
+
+impl DocumentSource for PdfDocument {
+    fn authenticate(&mut self, password: &str) -> bool {
+        let ok = self.doc.authenticate(password).unwrap_or(false);
+        if ok {
+            self.password = Some(password.to_string());
+            // A handle opened while locked can't read the objects
+            self.objects = OnceCell::new();
+        }
+        ok
+    }
+
+    /// The password the document was unlocked with, if it needed one.
+    fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+
+    fn page_count(&self) -> usize {
         self.doc.page_count().unwrap_or(0) as usize
     }
 
-    pub fn page_bounds(&self, page_idx: usize) -> Result<(f32, f32), mupdf::Error> {
+    /// Page size in points, as laid out by the page's own `/Rotate` entry:
+    /// mupdf applies it when bounding and rendering pages.
+    fn page_bounds(&self, page_idx: usize) -> Result<(f32, f32), Box<dyn Error>> {
         let page = self.doc.load_page(page_idx as i32)?;
         let bounds = page.bounds()?;
         Ok((bounds.x1 - bounds.x0, bounds.y1 - bounds.y0))
     }
 
-    pub fn render_page(&self, page_idx: usize, scale: f32) -> Result<DynamicImage, mupdf::Error> {
+    fn metadata(&self) -> DocumentInfo {
+        let get = |name| {
+            self.doc
+                .metadata(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        DocumentInfo {
+            title: get(MetadataName::Title),
+            author: get(MetadataName::Author),
+            subject: get(MetadataName::Subject),
+            creator: get(MetadataName::Creator),
+            created: get(MetadataName::CreationDate).map(|d| format_pdf_date(&d)),
+            version: get(MetadataName::Format),
+        }
+    }
+
+    /// The document's outline, empty when it has none.
+    fn outline(&self) -> Result<Vec<OutlineEntry>, Box<dyn Error>> {
+        fn convert(items: Vec<mupdf::Outline>) -> Vec<OutlineEntry> {
+            items
+                .into_iter()
+                .map(|item| OutlineEntry {
+                    title: item.title.trim().to_string(),
+                    page: item.page.map(|p| p as usize),
+                    children: convert(item.down),
+                })
+                .collect()
+        }
+        Ok(convert(self.doc.outlines()?))
+    }
+
+    /// The page a named destination (an anchor such as `section3`) points
+    /// to, if the document defines it.
+    fn resolve_named_dest(&self, name: &str) -> Option<usize> {
+        let uri = format!("#nameddest={}", encode_uri_component(name));
+        let location = self.doc.resolve_link(&uri).ok()??;
+        Some(location.page as usize)
+    }
+
+    fn links(&self, page_idx: usize) -> Result<Vec<LinkInfo>, Box<dyn Error>> {
+        let page = self.doc.load_page(page_idx as i32)?;
+        let bounds = page.bounds()?;
+        let (w, h) = (bounds.x1 - bounds.x0, bounds.y1 - bounds.y0);
+        if w <= 0.0 || h <= 0.0 {
+            return Ok(Vec::new());
+        }
+
+        let links = page
+            .links()?
+            .map(|link| {
+                let target = if is_external(&link.uri) {
+                    LinkTarget::Uri(link.uri)
+                } else {
+                    LinkTarget::Page(link.page as usize)
+                };
+                let region = Region {
+                    x: (link.bounds.x0 - bounds.x0) / w,
+                    y: (link.bounds.y0 - bounds.y0) / h,
+                    w: (link.bounds.x1 - link.bounds.x0) / w,
+                    h: (link.bounds.y1 - link.bounds.y0) / h,
+                };
+                LinkInfo { region, target }
+            })
+            .collect();
+        Ok(links)
+    }
+
+    /// Form fields on a page that have a value, read from the widget
+    /// annotations since the raster render may not show them.
+    fn form_fields(&self, page_idx: usize) -> Result<Vec<FieldInfo>, Box<dyn Error>> {
+        let mut fields = Vec::new();
+        for (annot, region) in self.annotations(page_idx)? {
+            if annot_subtype(&annot)?.as_deref() != Some(b"Widget".as_slice()) {
+                continue;
+            }
+            let Some(value) = widget_value(&annot)? else {
+                continue;
+            };
+            let name = match inherited(&annot, "T")? {
+                Some(t) => t.as_string()?.to_string(),
+                None => String::new(),
+            };
+            fields.push(FieldInfo {
+                region,
+                name,
+                value,
+            });
+        }
+        Ok(fields)
+    }
+
+    /// The text of a page's sticky notes and of comments attached to its
+    /// highlights and other markup. Free text annotations are left out, as
+    /// their text is already drawn on the page.
+    fn notes(&self, page_idx: usize) -> Result<Vec<NoteInfo>, Box<dyn Error>> {
+        let mut notes = Vec::new();
+        for (annot, region) in self.annotations(page_idx)? {
+            let Some(subtype) = annot_subtype(&annot)? else {
+                continue;
+            };
+            if matches!(
+                subtype.as_slice(),
+                b"Widget" | b"Link" | b"Popup" | b"FreeText"
+            ) {
+                continue;
+            }
+            let Some(contents) = annot.get_dict("Contents")? else {
+                continue;
+            };
+            let contents = contents.as_string()?.trim().to_string();
+            if contents.is_empty() {
+                continue;
+            }
+            let author = match annot.get_dict("T")? {
+                Some(t) => t.as_string()?.to_string(),
+                None => String::new(),
+            };
+            notes.push(NoteInfo {
+                region,
+                author,
+                contents,
+            });
+        }
+        Ok(notes)
+    }
+
+    /// The document's page labels, read from its `/PageLabels` number tree.
+    fn page_labels(&self) -> Result<PageLabels, Box<dyn Error>> {
+        let Some(pdf) = self.pdf_objects() else {
+            return Ok(PageLabels::default());
+        };
+        let Some(tree) = pdf.catalog()?.get_dict("PageLabels")? else {
+            return Ok(PageLabels::default());
+        };
+        let mut ranges = Vec::new();
+        collect_label_ranges(&tree, &mut ranges, 0)?;
+        ranges.sort_by_key(|r| r.first);
+        Ok(PageLabels { ranges })
+    }
+
+    fn extract_text(&self, page_idx: usize) -> Result<String, Box<dyn Error>> {
+        let page = self.doc.load_page(page_idx as i32)?;
+        Ok(page.to_text()?)
+    }
+
+    /// Lines set noticeably larger than most of the page's text. The
+    /// bindings don't tell a font's weight, so bold body-sized lines aren't
+    /// picked out, only bigger ones.
+    fn headings(&self, page_idx: usize) -> Result<Vec<String>, Box<dyn Error>> {
+        let spans = self.extract_structured(page_idx)?;
+        // The body is whatever size most of the characters are set in,
+        // counted in half points
+        let mut sizes: Vec<(u32, usize)> = Vec::new();
+        for span in &spans {
+            let size = (span.size * 2.0).round() as u32;
+            match sizes.iter_mut().find(|(s, _)| *s == size) {
+                Some((_, count)) => *count += span.chars.len(),
+                None => sizes.push((size, span.chars.len())),
+            }
+        }
+        let Some(&(body, _)) = sizes.iter().max_by_key(|(_, count)| *count) else {
+            return Ok(Vec::new());
+        };
+        Ok(spans
+            .into_iter()
+            .filter(|span| span.size >= body as f32 / 2.0 * HEADING_RATIO)
+            .map(|span| span.text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect())
+    }
+
+    /// The text of a page within `region`: the characters whose centers
+    /// fall inside it, line by line, with a blank line between blocks like
+    /// [`extract_text`](Self::extract_text).
+    fn text_in_region(&self, page_idx: usize, region: Region) -> Result<String, Box<dyn Error>> {
+        let mut out = String::new();
+        let mut last_block = None;
+        for span in self.extract_structured(page_idx)? {
+            if !span.region.overlaps(region) {
+                continue;
+            }
+            let text: String = span
+                .text
+                .chars()
+                .zip(&span.chars)
+                .filter(|(_, r)| region.contains(r.x + r.w / 2.0, r.y + r.h / 2.0))
+                .map(|(c, _)| c)
+                .collect();
+            if text.trim().is_empty() {
+                continue;
+            }
+            if last_block.is_some_and(|b| b != span.block) {
+                out.push('\n');
+            }
+            last_block = Some(span.block);
+            out.push_str(text.trim_end());
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Rasterize a page at `scale`, turned clockwise by `rotation` degrees
+    /// on top of its own `/Rotate`, with or without its annotations
+    /// (highlights, notes, ink and the like).
+    fn render_page(
+        &self,
+        page_idx: usize,
+        scale: f32,
+        rotation: u16,
+        annotations: bool,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
         let page = self.doc.load_page(page_idx as i32)?;
-        let matrix = Matrix::new_scale(scale, scale);
-        let pixmap = page.to_pixmap(&matrix, &Colorspace::device_rgb(), false, true)?;
+        let mut matrix = Matrix::new_scale(scale, scale);
+        matrix.pre_rotate(f32::from(rotation));
+        let pixmap = page.to_pixmap(&matrix, &Colorspace::device_rgb(), false, annotations)?;
 
         let width = pixmap.width();
         let height = pixmap.height();
@@ -50,4 +875,398 @@ impl PdfDocument {
 
         Ok(DynamicImage::ImageRgb8(img))
     }
+
+    fn has_text(&self) -> bool {
+        true
+    }
+}
+
+/// Parents deeper than this are assumed to be a reference cycle.
+const MAX_INHERIT_DEPTH: usize = 32;
+
+/// Look up `key` on `obj` or, failing that, the nearest `/Parent` that has
+/// it (page boxes and field values are inheritable).
+fn inherited(obj: &PdfObject, key: &str) -> Result<Option<PdfObject>, mupdf::Error> {
+    if let Some(value) = obj.get_dict(key)? {
+        return Ok(Some(value));
+    }
+    let mut parent = obj.get_dict("Parent")?;
+    for _ in 0..MAX_INHERIT_DEPTH {
+        let Some(node) = parent else {
+            break;
+        };
+        if let Some(value) = node.get_dict(key)? {
+            return Ok(Some(value));
+        }
+        parent = node.get_dict("Parent")?;
+    }
+    Ok(None)
+}
+
+/// Gather the label ranges of a `/PageLabels` number tree node: its own
+/// `/Nums` pairs of first page and label dictionary, then its `/Kids`.
+fn collect_label_ranges(
+    node: &PdfObject,
+    ranges: &mut Vec<LabelRange>,
+    depth: usize,
+) -> Result<(), mupdf::Error> {
+    if let Some(nums) = node.get_dict("Nums")? {
+        for i in (0..nums.len()?).step_by(2) {
+            let (Some(first), Some(dict)) =
+                (nums.get_array(i as i32)?, nums.get_array(i as i32 + 1)?)
+            else {
+                continue;
+            };
+            let style = match dict.get_dict("S")? {
+                Some(s) => match s.as_name()? {
+                    b"D" => NumberStyle::Decimal,
+                    b"R" => NumberStyle::UpperRoman,
+                    b"r" => NumberStyle::LowerRoman,
+                    b"A" => NumberStyle::UpperLetters,
+                    b"a" => NumberStyle::LowerLetters,
+                    _ => NumberStyle::None,
+                },
+                None => NumberStyle::None,
+            };
+            let prefix = match dict.get_dict("P")? {
+                Some(p) => p.as_string()?.to_string(),
+                None => String::new(),
+            };
+            let start = match dict.get_dict("St")? {
+                Some(st) => usize::try_from(st.as_int()?).unwrap_or(1),
+                None => 1,
+            };
+            if let Ok(first) = usize::try_from(first.as_int()?) {
+                ranges.push(LabelRange {
+                    first,
+                    style,
+                    prefix,
+                    start,
+                });
+            }
+        }
+    }
+    if depth < MAX_INHERIT_DEPTH {
+        if let Some(kids) = node.get_dict("Kids")? {
+            for i in 0..kids.len()? {
+                if let Some(kid) = kids.get_array(i as i32)? {
+                    collect_label_ranges(&kid, ranges, depth + 1)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An annotation's `/Subtype` name, e.g. `Highlight`.
+fn annot_subtype(annot: &PdfObject) -> Result<Option<Vec<u8>>, mupdf::Error> {
+    annot
+        .get_dict("Subtype")?
+        .map_or(Ok(None), |s| Ok(Some(s.as_name()?.to_vec())))
+}
+
+/// A `[x0 y0 x1 y1]` array, or `None` if `obj` isn't one.
+fn rect_of(obj: &PdfObject) -> Result<Option<[f32; 4]>, mupdf::Error> {
+    if !obj.is_array()? || obj.len()? != 4 {
+        return Ok(None);
+    }
+    let mut rect = [0.0; 4];
+    for (i, v) in rect.iter_mut().enumerate() {
+        match obj.get_array(i as i32)? {
+            Some(n) => *v = n.as_float()?,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(rect))
+}
+
+/// Display text for a widget's value: the text of text and choice fields,
+/// a check mark for ticked boxes and buttons. `None` when empty or off.
+fn widget_value(widget: &PdfObject) -> Result<Option<String>, mupdf::Error> {
+    // Check boxes and radio buttons carry their own on/off state
+    if let Some(state) = widget.get_dict("AS")? {
+        return Ok((state.as_name()? != b"Off").then(|| "✓".to_string()));
+    }
+    let Some(value) = inherited(widget, "V")? else {
+        return Ok(None);
+    };
+
+    let text = if value.is_string()? {
+        value.as_string()?.trim().to_string()
+    } else if value.is_name()? {
+        let name = String::from_utf8_lossy(value.as_name()?).into_owned();
+        if name == "Off" {
+            String::new()
+        } else {
+            name
+        }
+    } else if value.is_array()? {
+        // Multi-select list boxes
+        let mut items = Vec::new();
+        for i in 0..value.len()? {
+            if let Some(item) = value.get_array(i as i32)? {
+                if item.is_string()? {
+                    items.push(item.as_string()?.trim().to_string());
+                }
+            }
+        }
+        items.join(", ")
+    } else {
+        String::new()
+    };
+    Ok((!text.is_empty()).then_some(text))
+}
+
+/// Percent-encode everything but unreserved characters, as mupdf decodes
+/// link fragments.
+fn encode_uri_component(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            out.push(char::from(b));
+        } else {
+            let _ = write!(out, "%{b:02X}");
+        }
+    }
+    out
+}
+
+/// Internal links are `#page=N`-style fragments; anything with a scheme
+/// points outside the document.
+fn is_external(uri: &str) -> bool {
+    uri.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Turn a PDF date like `D:20230415093000+02'00'` into `2023-04-15 09:30`,
+/// passing anything unrecognised through unchanged.
+fn format_pdf_date(date: &str) -> String {
+    let digits = date.strip_prefix("D:").unwrap_or(date);
+    let field = |range: std::ops::Range<usize>| {
+        digits
+            .get(range)
+            .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+    };
+    match (field(0..4), field(4..6), field(6..8)) {
+        (Some(y), Some(m), Some(d)) => match (field(8..10), field(10..12)) {
+            (Some(hh), Some(mm)) => format!("{y}-{m}-{d} {hh}:{mm}"),
+            _ => format!("{y}-{m}-{d}"),
+        },
+        _ => date.to_string(),
+    }
+}
+
+/// Bounding box of everything that differs from the page background (taken
+/// from the top-left pixel) by more than `bg_threshold` in any channel, with
+/// a little padding. A blank page yields the full page.
+pub fn content_bbox(img: &DynamicImage, bg_threshold: u8) -> Region {
+    let rgb = img
+        .as_rgb8()
+        .map_or_else(|| Cow::Owned(img.to_rgb8()), Cow::Borrowed);
+    let (w, h) = rgb.dimensions();
+    if w == 0 || h == 0 {
+        return Region::FULL;
+    }
+
+    let bg = rgb.get_pixel(0, 0).0;
+    let is_content = |p: &Rgb<u8>| {
+        p.0.iter()
+            .zip(bg)
+            .any(|(&c, b)| c.abs_diff(b) > bg_threshold)
+    };
+
+    let (mut x0, mut y0, mut x1, mut y1) = (w, h, 0, 0);
+    for (x, y, p) in rgb.enumerate_pixels() {
+        if is_content(p) {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x + 1);
+            y1 = y1.max(y + 1);
+        }
+    }
+    if x1 <= x0 || y1 <= y0 {
+        return Region::FULL;
+    }
+
+    let pad = w.max(h) / 100;
+    let (x0, y0) = (x0.saturating_sub(pad), y0.saturating_sub(pad));
+    let (x1, y1) = ((x1 + pad).min(w), (y1 + pad).min(h));
+    Region {
+        x: x0 as f32 / w as f32,
+        y: y0 as f32 / h as f32,
+        w: (x1 - x0) as f32 / w as f32,
+        h: (y1 - y0) as f32 / h as f32,
+    }
+}
+
+/// Where a two-column page divides, as a fraction of the page width.
+///
+/// That is the middle of the widest blank strip through the central fifth
+/// of its content. `None` for pages without one, or with little on either
+/// side.
+pub fn column_gutter(img: &DynamicImage, bg_threshold: u8) -> Option<f32> {
+    let rgb = img
+        .as_rgb8()
+        .map_or_else(|| Cow::Owned(img.to_rgb8()), Cow::Borrowed);
+    let (w, h) = rgb.dimensions();
+    if w == 0 || h == 0 {
+        return None;
+    }
+
+    let bg = rgb.get_pixel(0, 0).0;
+    let mut ink = vec![0u32; w as usize];
+    for (x, _, p) in rgb.enumerate_pixels() {
+        if p.0
+            .iter()
+            .zip(bg)
+            .any(|(&c, b)| c.abs_diff(b) > bg_threshold)
+        {
+            ink[x as usize] += 1;
+        }
+    }
+    let first = ink.iter().position(|&n| n > 0)?;
+    let last = ink.iter().rposition(|&n| n > 0)?;
+    let width = last + 1 - first;
+    // Headings and figures spanning both columns leave a little ink in
+    // the gutter
+    let blank = ink.iter().max()? / 20;
+
+    let (lo, hi) = (first + width * 2 / 5, first + width * 3 / 5);
+    let mut widest: Option<(usize, usize)> = None;
+    let mut run_start = None;
+    for (x, &n) in ink.iter().enumerate().take(hi + 1).skip(lo) {
+        match run_start {
+            None if n <= blank => run_start = Some(x),
+            Some(start) if n > blank || x == hi => {
+                let len = x - start;
+                if widest.is_none_or(|(_, best)| len > best) {
+                    widest = Some((start, len));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    let (start, len) = widest.filter(|&(_, len)| len > width / 100)?;
+
+    let total: u32 = ink.iter().sum();
+    let left: u32 = ink[..start].iter().sum();
+    let right = total - left;
+    if left < total / 5 || right < total / 5 {
+        return None;
+    }
+    Some((start as f32 + len as f32 / 2.0) / w as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(first: usize, style: NumberStyle, prefix: &str, start: usize) -> LabelRange {
+        LabelRange {
+            first,
+            style,
+            prefix: prefix.to_string(),
+            start,
+        }
+    }
+
+    /// Labels for a 100,000 page document: roman front matter, the body,
+    /// an appendix, a run of plates all labeled alike, and letters to the
+    /// end, which run to labels thousands of letters long.
+    fn huge_document() -> PageLabels {
+        PageLabels {
+            ranges: vec![
+                range(0, NumberStyle::LowerRoman, "", 1),
+                range(10, NumberStyle::Decimal, "", 1),
+                range(50_010, NumberStyle::Decimal, "A-", 1),
+                range(50_020, NumberStyle::None, "Plate", 1),
+                range(50_030, NumberStyle::UpperLetters, "", 1),
+            ],
+        }
+    }
+
+    const HUGE_PAGE_COUNT: usize = 100_000;
+
+    #[test]
+    fn roman_numerals_round_trip() {
+        for n in 1..=3999 {
+            assert_eq!(from_roman(&roman(n)), Some(n), "{n}");
+        }
+        assert_eq!(roman(1994), "mcmxciv");
+        assert_eq!(from_roman(""), None);
+        assert_eq!(from_roman("iiv"), None);
+        assert_eq!(from_roman("abc"), None);
+    }
+
+    #[test]
+    fn letters_round_trip() {
+        for n in 1..=26 * 5 {
+            assert_eq!(NumberStyle::UpperLetters.parse(&letters(n, b'A')), Some(n));
+            assert_eq!(NumberStyle::LowerLetters.parse(&letters(n, b'a')), Some(n));
+        }
+        assert_eq!(letters(27, b'A'), "AA");
+        assert_eq!(NumberStyle::LowerLetters.parse("ab"), None);
+        assert_eq!(NumberStyle::LowerLetters.parse(""), None);
+        assert_eq!(NumberStyle::None.parse("a"), None);
+    }
+
+    #[test]
+    fn finds_labels_in_a_huge_document() {
+        let labels = huge_document();
+        let find = |label: &str| labels.page_for_label(label, HUGE_PAGE_COUNT);
+        assert_eq!(find("i"), Some(0));
+        assert_eq!(find("iv"), Some(3));
+        assert_eq!(find("1"), Some(10));
+        assert_eq!(find("50000"), Some(50_009));
+        assert_eq!(find("A-7"), Some(50_016));
+        assert_eq!(find("Plate"), Some(50_020));
+        assert_eq!(find("A"), Some(50_030));
+        assert_eq!(find("AA"), Some(50_056));
+
+        let last = HUGE_PAGE_COUNT - 1;
+        let label = labels.label(last).unwrap();
+        assert_eq!(label.len(), (49_970 - 1) / 26 + 1);
+        assert_eq!(find(&label), Some(last));
+    }
+
+    #[test]
+    fn rejects_labels_no_page_has() {
+        let labels = huge_document();
+        let find = |label: &str| labels.page_for_label(label, HUGE_PAGE_COUNT);
+        // Numbers past the end of their run, or of the document
+        assert_eq!(find("xi"), None);
+        assert_eq!(find("50001"), None);
+        assert_eq!(find("A-11"), None);
+        assert_eq!(labels.page_for_label("A", 50_030), None);
+        // Not written in any run's style
+        assert_eq!(find("Plate2"), None);
+        assert_eq!(find("AB"), None);
+        assert_eq!(find(""), None);
+    }
+
+    #[test]
+    fn falls_back_to_ignoring_case() {
+        let labels = huge_document();
+        let find = |label: &str| labels.page_for_label(label, HUGE_PAGE_COUNT);
+        assert_eq!(find("IV"), Some(3));
+        assert_eq!(find("a-7"), Some(50_016));
+        assert_eq!(find("PLATE"), Some(50_020));
+        assert_eq!(find("aa"), Some(50_056));
+
+        // An exact match wins over an earlier one in another case
+        let labels = PageLabels {
+            ranges: vec![
+                range(0, NumberStyle::LowerLetters, "", 1),
+                range(26, NumberStyle::UpperLetters, "", 1),
+            ],
+        };
+        assert_eq!(labels.page_for_label("b", 52), Some(1));
+        assert_eq!(labels.page_for_label("B", 52), Some(27));
+        assert_eq!(labels.page_for_label("B", 26), Some(1));
+    }
 }
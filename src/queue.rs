@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// A channel with two lanes: urgent items are always taken before the rest,
+/// however long the rest have been waiting. Render workers take pages on
+/// screen from it ahead of pages rendered in advance, so a jump never waits
+/// behind prewarming. Idle receivers sleep, and each item sent wakes one.
+struct Shared<T> {
+    lanes: Mutex<Lanes<T>>,
+    ready: Condvar,
+}
+
+struct Lanes<T> {
+    urgent: VecDeque<T>,
+    background: VecDeque<T>,
+    /// Set when the sender is dropped, to let the receivers finish.
+    closed: bool,
+}
+
+pub struct Sender<T>(Arc<Shared<T>>);
+
+pub struct Receiver<T>(Arc<Shared<T>>);
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        lanes: Mutex::new(Lanes {
+            urgent: VecDeque::new(),
+            background: VecDeque::new(),
+            closed: false,
+        }),
+        ready: Condvar::new(),
+    });
+    (Sender(Arc::clone(&shared)), Receiver(shared))
+}
+
+impl<T> Sender<T> {
+    /// Queue `item`, ahead of everything in the background lane if
+    /// `urgent`. Gives it back when there are no receivers left to take it.
+    pub fn send(&self, item: T, urgent: bool) -> Result<(), T> {
+        if Arc::strong_count(&self.0) == 1 {
+            return Err(item);
+        }
+        let mut lanes = self.0.lanes.lock().unwrap();
+        if urgent {
+            lanes.urgent.push_back(item);
+        } else {
+            lanes.background.push_back(item);
+        }
+        drop(lanes);
+        self.0.ready.notify_one();
+        Ok(())
+    }
+
+    /// Sort what is still waiting into the lanes again: items `urgent`
+    /// picks out go first, in the order they were sent, and everything
+    /// else waits behind them.
+    pub fn reprioritize(&self, urgent: impl Fn(&T) -> bool) {
+        let mut lanes = self.0.lanes.lock().unwrap();
+        let mut waiting = std::mem::take(&mut lanes.urgent);
+        waiting.append(&mut lanes.background);
+        for item in waiting {
+            if urgent(&item) {
+                lanes.urgent.push_back(item);
+            } else {
+                lanes.background.push_back(item);
+            }
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.0.lanes.lock().unwrap().closed = true;
+        self.0.ready.notify_all();
+    }
+}
+
+impl<T> Receiver<T> {
+    /// The next item, waiting for one if the queue is empty. `None` once
+    /// the sender is gone.
+    pub fn recv(&self) -> Option<T> {
+        let mut lanes = self.0.lanes.lock().unwrap();
+        loop {
+            if lanes.closed {
+                return None;
+            }
+            if let Some(item) = lanes.urgent.pop_front() {
+                return Some(item);
+            }
+            if let Some(item) = lanes.background.pop_front() {
+                return Some(item);
+            }
+            lanes = self.0.ready.wait(lanes).unwrap();
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::app::{ColorMode, FitMode, PageLayout};
+
+/// Per-file reading position and view, restored the next time the file is
+/// opened.
+pub struct SessionState {
+    pub page: usize,
+    pub zoom: f32,
+    pub layout: PageLayout,
+    pub fit: FitMode,
+    /// Degrees clockwise, on top of the page's own rotation.
+    pub rotation: u16,
+    pub color_mode: ColorMode,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self {
+            page: 0,
+            zoom: 1.0,
+            layout: PageLayout::Single,
+            fit: FitMode::Page,
+            rotation: 0,
+            color_mode: ColorMode::Normal,
+            bookmarks: Vec::new(),
+        }
+    }
+}
+
+/// A user-set mark on a page, named by a single character.
+#[derive(Clone, Copy)]
+pub struct Bookmark {
+    pub label: char,
+    pub page: usize,
+}
+
+/// The state file holds one section per document, keyed by absolute path,
+/// or by URL for a downloaded one:
+///
+/// ```text
+/// [/home/me/books/rust.pdf]
+/// page=41
+/// zoom=1.2
+/// layout=dual
+/// fit=width
+/// rotation=90
+/// color=night
+/// bookmark=a:12
+/// bookmark=1:87
+/// ```
+fn state_file() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("tpdf").join("sessions"))
+}
+
+/// The key of a document on disk, its absolute path.
+pub fn document_key(path: &str) -> Option<String> {
+    let abs = fs::canonicalize(path).ok()?;
+    Some(abs.to_string_lossy().into_owned())
+}
+
+/// The session saved under `key`, from [`document_key`] or a URL.
+pub fn load(key: &str) -> Option<SessionState> {
+    let contents = fs::read_to_string(state_file()?).ok()?;
+    let (_, lines) = parse(&contents).into_iter().find(|(k, _)| *k == key)?;
+
+    // Values this version doesn't understand (say, from a newer one) keep
+    // their defaults
+    let mut state = SessionState::default();
+    for (name, value) in lines.iter().filter_map(|l| l.split_once('=')) {
+        match name {
+            "page" => state.page = value.parse().unwrap_or(0),
+            "zoom" => state.zoom = value.parse().unwrap_or(1.0),
+            "layout" => state.layout = parse_layout(value).unwrap_or(PageLayout::Single),
+            "fit" => state.fit = FitMode::from_name(value).unwrap_or(FitMode::Page),
+            "rotation" => state.rotation = parse_rotation(value).unwrap_or(0),
+            "color" => state.color_mode = ColorMode::from_name(value).unwrap_or(ColorMode::Normal),
+            "bookmark" => state.bookmarks.extend(parse_bookmark(value)),
+            _ => {}
+        }
+    }
+    Some(state)
+}
+
+pub fn save(key: &str, state: &SessionState) -> std::io::Result<()> {
+    let Some(file) = state_file() else {
+        return Ok(());
+    };
+    let contents = fs::read_to_string(&file).unwrap_or_default();
+
+    let mut out = String::new();
+    for (k, lines) in parse(&contents).into_iter().filter(|(k, _)| *k != key) {
+        let _ = writeln!(out, "[{k}]");
+        for line in lines {
+            let _ = writeln!(out, "{line}");
+        }
+    }
+    let _ = writeln!(out, "[{key}]");
+    let _ = writeln!(out, "page={}", state.page);
+    let _ = writeln!(out, "zoom={}", state.zoom);
+    let _ = writeln!(out, "layout={}", layout_name(state.layout));
+    let _ = writeln!(out, "fit={}", state.fit.name());
+    let _ = writeln!(out, "rotation={}", state.rotation);
+    let _ = writeln!(out, "color={}", state.color_mode.name());
+    for b in &state.bookmarks {
+        let _ = writeln!(out, "bookmark={}:{}", b.label, b.page);
+    }
+
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(&file, &out)
+}
+
+/// Parse the state file into `(key, lines)` sections.
+fn parse(contents: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in contents.lines() {
+        if let Some(key) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((key, Vec::new()));
+        } else if let Some((_, lines)) = sections.last_mut() {
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+    }
+    sections
+}
+
+/// Write via a temp file and rename so a crash mid-write can't corrupt the
+/// sessions of every other document.
+fn write_atomic(file: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = file.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, file)
+}
+
+fn layout_name(layout: PageLayout) -> String {
+    match layout {
+        PageLayout::Single => "single".into(),
+        PageLayout::Dual => "dual".into(),
+        PageLayout::Triple => "triple".into(),
+        PageLayout::Continuous => "continuous".into(),
+        PageLayout::Grid { cols, rows } => format!("{cols}x{rows}"),
+    }
+}
+
+fn parse_layout(name: &str) -> Option<PageLayout> {
+    match name {
+        "single" => Some(PageLayout::Single),
+        "dual" => Some(PageLayout::Dual),
+        "triple" => Some(PageLayout::Triple),
+        "continuous" => Some(PageLayout::Continuous),
+        grid => PageLayout::parse_grid(grid),
+    }
+}
+
+fn parse_rotation(value: &str) -> Option<u16> {
+    value
+        .parse()
+        .ok()
+        .filter(|r| matches!(r, 0 | 90 | 180 | 270))
+}
+
+fn parse_bookmark(value: &str) -> Option<Bookmark> {
+    let (label, page) = value.split_once(':')?;
+    let mut chars = label.chars();
+    let (Some(label), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    Some(Bookmark {
+        label,
+        page: page.parse().ok()?,
+    })
+}
@@ -0,0 +1,125 @@
+/// A row of wrapped text.
+pub struct Row {
+    pub text: String,
+    /// Whether it comes from one of the page's headings.
+    pub heading: bool,
+}
+
+impl Row {
+    fn plain(text: String) -> Self {
+        Self {
+            text,
+            heading: false,
+        }
+    }
+}
+
+/// Word-wrap extracted page text to `width` columns.
+///
+/// With `reflow`, consecutive lines are joined into paragraphs (split at blank
+/// lines) before wrapping, and words longer than the width, like URLs, are
+/// broken across lines so nothing overflows. Otherwise the original lines
+/// are kept as they are, so code and tables keep their columns, and
+/// over-long ones are scrolled to sideways.
+///
+/// Rows from a line in `headings`, or a paragraph made up only of such
+/// lines, are marked as headings.
+pub fn wrap(text: &str, width: usize, reflow: bool, headings: &[String]) -> Vec<Row> {
+    let width = width.max(1);
+    let is_heading = |line: &str| headings.iter().any(|h| h == line);
+    let mut out = Vec::new();
+
+    if reflow {
+        for (i, (paragraph, heading)) in paragraphs(text, is_heading).iter().enumerate() {
+            if i > 0 {
+                out.push(Row::plain(String::new()));
+            }
+            let mut lines = Vec::new();
+            wrap_line(paragraph, width, &mut lines);
+            out.extend(lines.into_iter().map(|text| Row {
+                text,
+                heading: *heading,
+            }));
+        }
+    } else {
+        out.extend(text.lines().map(|line| Row {
+            text: line.trim_end().to_string(),
+            heading: is_heading(line.trim()),
+        }));
+        while out.last().is_some_and(|row| row.text.is_empty()) {
+            out.pop();
+        }
+    }
+    out
+}
+
+/// Join runs of non-blank lines into single-line paragraphs, each marked
+/// with whether all its lines are headings.
+fn paragraphs(text: &str, is_heading: impl Fn(&str) -> bool) -> Vec<(String, bool)> {
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    let mut heading = true;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push((std::mem::take(&mut current), heading));
+            }
+            heading = true;
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(line);
+            heading &= is_heading(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push((current, heading));
+    }
+    paragraphs
+}
+
+/// Greedily fill lines with whole words, keeping the line's indentation on
+/// its first row.
+fn wrap_line(line: &str, width: usize, out: &mut Vec<String>) {
+    let mut current: String = line
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .take(width - 1)
+        .map(|_| ' ')
+        .collect();
+    let mut len = current.len();
+    let mut empty = true;
+
+    for word in line.split_whitespace() {
+        let mut word_len = word.chars().count();
+        if !empty && len + 1 + word_len > width {
+            out.push(std::mem::take(&mut current));
+            len = 0;
+            empty = true;
+        }
+        if !empty {
+            current.push(' ');
+            len += 1;
+        }
+
+        // Hard-break words that can't fit on a line of their own
+        let mut rest = word;
+        while len + word_len > width {
+            let split = rest
+                .char_indices()
+                .nth(width - len)
+                .map_or(rest.len(), |(i, _)| i);
+            current.push_str(&rest[..split]);
+            out.push(std::mem::take(&mut current));
+            len = 0;
+            rest = &rest[split..];
+            word_len = rest.chars().count();
+        }
+
+        current.push_str(rest);
+        len += word_len;
+        empty = false;
+    }
+    out.push(current);
+}
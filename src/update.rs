@@ -1,14 +1,22 @@
 use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 use std::process::Command;
 
+use sha2::{Digest, Sha256};
+
 const REPO: &str = "IWhitebird/tpdf";
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn platform_name() -> Result<String, Box<dyn std::error::Error>> {
-    let os = if cfg!(target_os = "linux") {
-        "linux"
+/// Name of this platform's release asset, without its extension, and that
+/// extension: Windows builds ship as zips, everything else as tarballs.
+fn platform_asset() -> Result<(String, &'static str), Box<dyn std::error::Error>> {
+    let (os, ext) = if cfg!(target_os = "linux") {
+        ("linux", "tar.gz")
     } else if cfg!(target_os = "macos") {
-        "macos"
+        ("macos", "tar.gz")
+    } else if cfg!(target_os = "windows") {
+        ("windows", "zip")
     } else {
         return Err("Unsupported OS".into());
     };
@@ -21,7 +29,7 @@ fn platform_name() -> Result<String, Box<dyn std::error::Error>> {
         return Err("Unsupported architecture".into());
     };
 
-    Ok(format!("tpdf-{os}-{arch}"))
+    Ok((format!("tpdf-{os}-{arch}"), ext))
 }
 
 fn fetch_latest_tag() -> Result<String, Box<dyn std::error::Error>> {
@@ -44,6 +52,31 @@ fn fetch_latest_tag() -> Result<String, Box<dyn std::error::Error>> {
     Ok(tag.to_string())
 }
 
+/// Print the version, like clap would, and with `verbose` what tpdf was
+/// built with, for bug reports: rendering differences often come down to
+/// the mupdf release.
+pub fn print_version(verbose: bool) {
+    println!("tpdf {CURRENT_VERSION}");
+    if !verbose {
+        return;
+    }
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let mupdf = crate::pdf::mupdf_version().unwrap_or_else(|| "unknown".to_string());
+    println!("{:<11} {mupdf}", "mupdf");
+    let features = match env!("TPDF_FEATURES") {
+        "" => "none",
+        features => features,
+    };
+    println!("{:<11} {}", "Target", env!("TPDF_TARGET"));
+    println!("{:<11} {features}", "Features");
+    println!("{:<11} {profile}", "Profile");
+    println!("{:<11} mimalloc", "Allocator");
+}
+
 pub fn self_update() -> Result<(), Box<dyn std::error::Error>> {
     println!("tpdf v{CURRENT_VERSION}");
     println!("Checking for updates...");
@@ -58,12 +91,18 @@ pub fn self_update() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("New version available: v{latest}");
 
-    let platform = platform_name()?;
-    let url = format!("https://github.com/{REPO}/releases/download/{tag}/{platform}.tar.gz");
+    let (platform, ext) = platform_asset()?;
+    let asset = format!("{platform}.{ext}");
+    let release_url = format!("https://github.com/{REPO}/releases/download/{tag}");
+    let url = format!("{release_url}/{asset}");
     let current_exe = std::env::current_exe()?;
+    let backup = current_exe.with_extension("old");
+    // Windows can't delete a running binary, so the previous update's backup
+    // is only removed now
+    let _ = fs::remove_file(&backup);
 
     let tmp_dir = tempdir()?;
-    let archive = tmp_dir.join("tpdf.tar.gz");
+    let archive = tmp_dir.join(format!("tpdf.{ext}"));
 
     println!("Downloading {url}...");
     let status = Command::new("curl")
@@ -74,24 +113,45 @@ pub fn self_update() -> Result<(), Box<dyn std::error::Error>> {
         return Err("Download failed".into());
     }
 
-    println!("Extracting...");
-    let status = Command::new("tar")
-        .args(["xzf"])
-        .arg(&archive)
-        .arg("-C")
-        .arg(&tmp_dir)
-        .status()?;
-    if !status.success() {
-        return Err("Extraction failed".into());
+    println!("Verifying checksum...");
+    let sums = Command::new("curl")
+        .args(["-fsSL", &format!("{release_url}/SHA256SUMS")])
+        .output()?;
+    let expected = sums
+        .status
+        .success()
+        .then(|| expected_checksum(&String::from_utf8_lossy(&sums.stdout), &asset))
+        .flatten();
+    if let Some(expected) = expected {
+        let actual = format!("{:x}", Sha256::digest(fs::read(&archive)?));
+        if !actual.eq_ignore_ascii_case(&expected) {
+            let _ = fs::remove_dir_all(&tmp_dir);
+            return Err(format!(
+                "Checksum mismatch for {asset} (expected {expected}, got {actual}); \
+                 the download may be corrupted, so the current binary was left alone"
+            )
+            .into());
+        }
+    } else {
+        println!(
+            "Warning: no checksum for {asset} in this release; the download can't be verified."
+        );
+        if !confirm("Install it anyway?")? {
+            let _ = fs::remove_dir_all(&tmp_dir);
+            return Err("Update cancelled".into());
+        }
     }
 
-    let new_binary = tmp_dir.join("tpdf");
+    println!("Extracting...");
+    extract(&archive, &tmp_dir)?;
+
+    let new_binary = tmp_dir.join(format!("tpdf{}", std::env::consts::EXE_SUFFIX));
 
     // Rename-then-replace strategy (same as Bun/Claude Code):
-    // Linux won't let you overwrite a running binary (ETXTBSY), but
-    // renaming it is allowed. So we rename the old binary out of the way,
-    // place the new one at the original path, then delete the old one.
-    let backup = current_exe.with_extension("old");
+    // Linux won't let you overwrite a running binary (ETXTBSY) and Windows
+    // locks it outright, but both allow renaming it. So we rename the old
+    // binary out of the way, place the new one at the original path, then
+    // delete the old one.
     fs::rename(&current_exe, &backup)?;
 
     if let Err(e) = fs::copy(&new_binary, &current_exe) {
@@ -113,6 +173,47 @@ pub fn self_update() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Find the checksum listed for `asset` in a `sha256sum`-style file.
+fn expected_checksum(sums: &str, asset: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        // sha256sum marks files hashed in binary mode with a leading '*'
+        let name = name.trim_start();
+        let name = name.strip_prefix('*').unwrap_or(name);
+        (name == asset).then(|| hash.to_string())
+    })
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> io::Result<bool> {
+    print!("{question} [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(windows)]
+fn extract(archive: &Path, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+    zip.extract(dir)?;
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn extract(archive: &Path, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let status = Command::new("tar")
+        .args(["xzf"])
+        .arg(archive)
+        .arg("-C")
+        .arg(dir)
+        .status()?;
+    if !status.success() {
+        return Err("Extraction failed".into());
+    }
+    Ok(())
+}
+
 /// Create a temporary directory that we clean up ourselves.
 fn tempdir() -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
     let dir = std::env::temp_dir().join(format!("tpdf-update-{}", std::process::id()));
@@ -1,14 +1,28 @@
 use ratatui::{
-    layout::{Alignment, Constraint, Layout, Rect},
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 use ratatui_image::Image as RatatuiImage;
 
-use crate::app::{App, PageLayout};
+use crate::app::{App, ColorMode, FitMode, PageArea, PageLayout, Quality, CONTINUOUS_GAP};
+use crate::cache::pan_window;
+use crate::pace;
+use crate::pdf::{LinkTarget, Region};
+use crate::text::Row;
 
+/// Widest a note's text box gets, in columns.
+const NOTE_WIDTH: u16 = 40;
+/// Lines of a note shown before the rest is cut off.
+const NOTE_LINES: u16 = 4;
+/// Width of the minimap, border included, in columns.
+const MINIMAP_COLS: u16 = 14;
+/// Frames of the spinner on pages still loading.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Where a page narrower than its area sits.
 #[derive(Clone, Copy)]
 pub enum HAlign {
     Left,
@@ -17,7 +31,8 @@ pub enum HAlign {
 }
 
 pub fn draw(frame: &mut Frame, app: &mut App) {
-    let (content_area, status_area) = if app.fullscreen {
+    let bar_hidden = app.fullscreen || !app.show_status_bar;
+    let (content_area, status_area) = if bar_hidden && !app.password_mode {
         (frame.area(), None)
     } else {
         let [ca, sa] =
@@ -25,54 +40,457 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
         (ca, Some(sa))
     };
 
-    let bg = if app.dark_mode {
-        Color::Rgb(0, 0, 0)
-    } else {
-        Color::Rgb(255, 255, 255)
+    // Match what the color mode turns white paper into
+    let [r, g, b] = match app.color_mode {
+        ColorMode::Night | ColorMode::SmartNight | ColorMode::DarkPaper => app.paper.night,
+        ColorMode::Sepia => [255, 255, 239],
+        ColorMode::Normal | ColorMode::Grayscale => app.paper.day,
     };
+    let bg = Color::Rgb(r, g, b);
     frame.render_widget(
-        Block::default().style(Style::default().bg(bg)),
+        Block::default().style(styled(app, Style::default().bg(bg))),
         content_area,
     );
+    app.link_hits.clear();
+    app.page_areas.clear();
+
+    let fg = if app.color_mode.is_dark() {
+        Color::Rgb(220, 220, 220)
+    } else {
+        Color::Rgb(0, 0, 0)
+    };
+    let text_style = styled(app, Style::default().fg(fg).bg(bg));
+    if app.too_small() {
+        draw_notice(frame, content_area, "Terminal too small", text_style);
+        draw_status(frame, app, content_area, status_area);
+        return;
+    }
+
+    if app.thumbnails {
+        draw_thumbnails(frame, content_area, app);
+        draw_status(frame, app, content_area, status_area);
+        return;
+    }
+
+    if app.text_mode {
+        let page_idx = app.current_page;
+        draw_text_page(frame, content_area, app, page_idx, text_style);
+        draw_overlays(frame, content_area, app);
+        draw_status(frame, app, content_area, status_area);
+        return;
+    }
+
+    let pages_area = if app.split_text {
+        let [pages, text] =
+            Layout::horizontal([Constraint::Length(app.page_cols()), Constraint::Fill(1)])
+                .areas(content_area);
+        let divider = Block::new()
+            .borders(Borders::LEFT)
+            .border_style(text_style.add_modifier(Modifier::DIM));
+        let text_area = divider.inner(text);
+        frame.render_widget(divider, text);
+        let page_idx = app.split_text_page();
+        draw_text_page(frame, text_area, app, page_idx, text_style);
+        pages
+    } else {
+        content_area
+    };
 
     match app.layout {
+        _ if app.compare.is_some() => draw_compare(frame, pages_area, app),
         PageLayout::Single => {
-            render_page(frame, content_area, app, app.current_page, HAlign::Center);
+            render_page(
+                frame,
+                pages_area,
+                app,
+                app.current_page,
+                HAlign::Center,
+                None,
+                false,
+            );
         }
-        PageLayout::Dual => draw_multi_page(frame, content_area, app, 2),
-        PageLayout::Triple => draw_multi_page(frame, content_area, app, 3),
+        PageLayout::Dual => draw_multi_page(frame, pages_area, app, 2, 1),
+        PageLayout::Triple => draw_multi_page(frame, pages_area, app, 3, 1),
+        PageLayout::Grid { cols, rows } => {
+            draw_multi_page(frame, pages_area, app, cols.into(), rows.into());
+        }
+        PageLayout::Continuous => draw_continuous(frame, pages_area, app),
     }
 
-    if let Some(sa) = status_area {
-        draw_status_bar(frame, sa, app);
+    if app.show_minimap && app.compare.is_none() && app.layout != PageLayout::Continuous {
+        draw_minimap(frame, pages_area, app);
     }
+    draw_link_highlight(frame, pages_area, app);
+    draw_selection(frame, pages_area, app);
+    draw_overlays(frame, content_area, app);
+
+    draw_status(frame, app, content_area, status_area);
 }
 
-fn draw_multi_page(frame: &mut Frame, area: Rect, app: &mut App, count: usize) {
-    let constraints: Vec<Constraint> = (0..count).map(|_| Constraint::Fill(1)).collect();
-    let areas = Layout::horizontal(constraints).spacing(0).split(area);
+/// The status bar, if it has a row of its own. Hidden (but not in
+/// fullscreen), it still comes up over the bottom row for prompts and
+/// messages.
+fn draw_status(frame: &mut Frame, app: &mut App, content_area: Rect, status_area: Option<Rect>) {
+    let prompting = app.goto_mode || app.command_mode || app.bookmark_label_mode;
+    app.page_indicator = match status_area {
+        Some(area) => draw_status_bar(frame, area, app),
+        None if !app.fullscreen && (prompting || app.status_message.is_some()) => {
+            let row = Rect::new(
+                content_area.x,
+                content_area.bottom().saturating_sub(1),
+                content_area.width,
+                1,
+            );
+            frame.render_widget(Clear, row);
+            draw_status_bar(frame, row, app);
+            None
+        }
+        None => None,
+    };
+}
 
-    for i in 0..count {
-        let idx = app.current_page + i;
-        if idx < app.page_count {
-            let align = if i == 0 {
-                HAlign::Right
-            } else if i == count - 1 {
-                HAlign::Left
-            } else {
-                HAlign::Center
+/// Pages in `rows` rows of `cols`, filled left to right, with separator
+/// lines in the gutters between them if they are on.
+fn draw_multi_page(frame: &mut Frame, area: Rect, app: &mut App, cols: usize, rows: usize) {
+    let separators = app.show_separators;
+    let (gutter_w, gutter_h) = if separators {
+        (app.separator_width, 1)
+    } else {
+        (0, 0)
+    };
+    let line = separator_style(app);
+    let row_areas = Layout::vertical(vec![Constraint::Fill(1); rows])
+        .spacing(gutter_h)
+        .split(area);
+    if separators {
+        for pair in row_areas.windows(2) {
+            let rule = "─".repeat(area.width.into());
+            let row = Rect::new(area.x, pair[0].bottom(), area.width, 1);
+            frame.render_widget(Paragraph::new(rule).style(line), row);
+        }
+    }
+
+    // A lone cover sits on the right, where it would be in a book
+    let skip = cols * rows - app.spread_len();
+    for (r, &row_area) in row_areas.iter().enumerate() {
+        let areas = Layout::horizontal(vec![Constraint::Fill(1); cols])
+            .spacing(gutter_w)
+            .split(row_area);
+        if separators {
+            for pair in areas.windows(2) {
+                let rule = vec![Line::raw("│"); row_area.height.into()];
+                let col = Rect::new(
+                    pair[0].right() + gutter_w / 2,
+                    row_area.y,
+                    1,
+                    row_area.height,
+                );
+                frame.render_widget(Paragraph::new(rule).style(line), col);
+            }
+        }
+        for i in 0..cols {
+            let slot = r * cols + i;
+            if slot < skip {
+                continue;
+            }
+            let idx = app.current_page + slot - skip;
+            if idx < app.page_count {
+                // Pages lean toward their neighbors, like the halves of a
+                // spread; a lone column has none and stays centered
+                let align = if cols == 1 {
+                    HAlign::Center
+                } else if i == 0 {
+                    HAlign::Right
+                } else if i == cols - 1 {
+                    HAlign::Left
+                } else {
+                    HAlign::Center
+                };
+                let dim = app.dims_page(idx);
+                render_page(frame, areas[i], app, idx, align, None, dim);
+            }
+        }
+    }
+}
+
+/// Compare mode: the focused pane's page with the current zoom and pan, and
+/// the other pane's with its own.
+fn draw_compare(frame: &mut Frame, area: Rect, app: &mut App) {
+    let [left, right] = Layout::horizontal([Constraint::Fill(1); 2]).areas(area);
+    let focus_left = app.compare.as_ref().is_some_and(|c| c.focus_left);
+    let ((focused, focused_align), (other, other_align)) = if focus_left {
+        ((left, HAlign::Right), (right, HAlign::Left))
+    } else {
+        ((right, HAlign::Left), (left, HAlign::Right))
+    };
+    render_page(
+        frame,
+        focused,
+        app,
+        app.current_page,
+        focused_align,
+        None,
+        false,
+    );
+    app.swap_panes();
+    let dim = app.dim_inactive;
+    render_page(frame, other, app, app.current_page, other_align, None, dim);
+    app.swap_panes();
+}
+
+/// Stack pages vertically, offset by the scroll position, clipping the pages
+/// that straddle the top and bottom edges of the viewport.
+fn draw_continuous(frame: &mut Frame, area: Rect, app: &mut App) {
+    let (page_w, page_h) = app.continuous_page_size();
+    let pitch = i32::from(page_h + CONTINUOUS_GAP);
+
+    // When zoomed past the viewport width, show a horizontal window into the page
+    let shown_w = page_w.min(area.width);
+    let clip_w = f32::from(shown_w) / f32::from(page_w);
+    let clip_x = app.pan_x.mul_add(0.5, 0.5) * (1.0 - clip_w);
+    let x = area.x + (area.width - shown_w) / 2;
+
+    let view_h = i32::from(area.height);
+    let mut top = -(app.scroll_offset * pitch as f32).round() as i32;
+    let mut idx = app.current_page;
+    while idx < app.page_count && top < view_h {
+        let visible_top = (-top).max(0);
+        let visible_bottom = i32::from(page_h).min(view_h - top);
+        if visible_bottom > visible_top {
+            let clip = Region {
+                x: clip_x,
+                y: visible_top as f32 / f32::from(page_h),
+                w: clip_w,
+                h: (visible_bottom - visible_top) as f32 / f32::from(page_h),
             };
-            render_page(frame, areas[i], app, idx, align);
+            let rect = Rect::new(
+                x,
+                area.y + (top + visible_top) as u16,
+                shown_w,
+                (visible_bottom - visible_top) as u16,
+            );
+            render_page(frame, rect, app, idx, HAlign::Center, Some(clip), false);
+        }
+        // The gap row between this page and the next
+        let gap = top + i32::from(page_h);
+        if app.show_separators && idx + 1 < app.page_count && (0..view_h).contains(&gap) {
+            let rule = "─".repeat(shown_w.into());
+            let row = Rect::new(x, area.y + gap as u16, shown_w, 1);
+            frame.render_widget(Paragraph::new(rule).style(separator_style(app)), row);
+        }
+        top += pitch;
+        idx += 1;
+    }
+}
+
+/// The lines drawn between pages.
+fn separator_style(app: &App) -> Style {
+    let color = app
+        .separator_color
+        .map_or(Color::DarkGray, |[r, g, b]| Color::Rgb(r, g, b));
+    styled(app, Style::default().fg(color))
+}
+
+/// Overview grid of page thumbnails with the selected one outlined.
+fn draw_thumbnails(frame: &mut Frame, area: Rect, app: &mut App) {
+    app.scroll_thumbnail_into_view();
+    let (thumb_w, thumb_h) = app.thumbnail_size();
+    let (cell_w, cell_h) = (thumb_w + 2, thumb_h + 3);
+    let (cols, _) = app.thumbnail_grid();
+
+    // Center the grid horizontally
+    let grid_w = (cols as u16 * cell_w).min(area.width);
+    let left = area.x + (area.width - grid_w) / 2;
+
+    for idx in app.visible_thumbnails() {
+        let pos = idx - app.thumbnail_scroll * cols;
+        let x = left + (pos % cols) as u16 * cell_w;
+        let y = area.y + (pos / cols) as u16 * cell_h;
+        let cell = Rect::new(x, y, cell_w, cell_h).intersection(area);
+        let image_area = Rect::new(x + 1, y + 1, thumb_w, thumb_h).intersection(area);
+
+        if let Some((w, h)) = app.cache.thumbnail_dims(idx) {
+            let render_area = aligned_image_area(
+                w,
+                h,
+                image_area,
+                app.picker.font_size(),
+                1.0,
+                HAlign::Center,
+            );
+            if let Some(protocol) =
+                app.cache
+                    .get_thumbnail_protocol(idx, app.color_mode, &app.picker, render_area)
+            {
+                frame.render_widget(RatatuiImage::new(protocol), render_area);
+            }
+        }
+
+        let selected = idx == app.thumbnail_selected;
+        let label_style = if selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        let label = Paragraph::new(Span::styled(format!(" {} ", idx + 1), label_style))
+            .alignment(Alignment::Center);
+        let label_y = y + 1 + thumb_h;
+        frame.render_widget(
+            label,
+            Rect::new(x + 1, label_y, thumb_w, 1).intersection(area),
+        );
+
+        if selected {
+            frame.render_widget(
+                Block::bordered().border_style(Style::default().fg(Color::Yellow)),
+                cell,
+            );
         }
     }
 }
 
-fn render_page(frame: &mut Frame, area: Rect, app: &mut App, page_idx: usize, halign: HAlign) {
+/// A page's extracted text, wrapped to the pane width and scrolled by whole
+/// wrapped lines: all of text mode, or the right half of the split view.
+fn draw_text_page(frame: &mut Frame, area: Rect, app: &mut App, page_idx: usize, style: Style) {
+    // A column of padding either side keeps text off the terminal edge
+    let pane = area.inner(Margin::new(1, 0));
+    let height = usize::from(pane.height);
+    app.text_pane = (pane.width, pane.height);
+
+    let total = match app
+        .ensure_wrapped_text(page_idx, pane.width)
+        .map(<[Row]>::len)
+    {
+        None if app.recognizing(page_idx) => {
+            return draw_notice(frame, pane, "Recognizing text…", style);
+        }
+        None => return draw_notice(frame, pane, "Could not extract text", style),
+        Some(0) => return draw_notice(frame, pane, "No text on this page", style),
+        Some(n) => n,
+    };
+
+    // Clamped here since the line count depends on the width
+    app.text_scroll = app.text_scroll.min(total.saturating_sub(height));
+    let wrapped = app
+        .cache
+        .wrapped_text(page_idx, pane.width, app.text_reflow)
+        .unwrap_or_default();
+    let widest = wrapped
+        .iter()
+        .map(|row| row.text.chars().count())
+        .max()
+        .unwrap_or(0);
+    app.text_hscroll = app
+        .text_hscroll
+        .min(widest.saturating_sub(usize::from(pane.width)));
+    let heading = Style::default()
+        .add_modifier(Modifier::BOLD)
+        .patch(styled(app, Style::default().fg(Color::Cyan)));
+    // Lines shorter than the scroll offset come out blank
+    let lines: Vec<Line> = wrapped
+        .iter()
+        .skip(app.text_scroll)
+        .take(height)
+        .map(|row| {
+            let line = Line::raw(row.text.chars().skip(app.text_hscroll).collect::<String>());
+            if row.heading && app.text_styles {
+                line.style(heading)
+            } else {
+                line
+            }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).style(style), pane);
+}
+
+/// An outline of the current page in the bottom-right corner of `area`,
+/// with the part of it on screen filled in, whenever only part of it is:
+/// zoomed in, fitted to the width or height, or cropped. Drawn in text
+/// rather than from a thumbnail, since images can't be layered over the
+/// page's.
+fn draw_minimap(frame: &mut Frame, area: Rect, app: &App) {
+    // Where the page was just drawn, as a part of the whole page
+    let Some(drawn) = app.page_areas.iter().find(|p| p.page == app.current_page) else {
+        return;
+    };
+    let view = drawn.window.inside(drawn.content);
+    if view.w >= 0.999 && view.h >= 0.999 {
+        return;
+    }
+    let Some((w, h)) = app.cache.image_dims(app.current_page) else {
+        return;
+    };
+    // The cached size is that of the cropped page; the outline is the
+    // whole page's
+    let (w, h) = (w as f32 / drawn.content.w, h as f32 / drawn.content.h);
+    let (fw, fh) = app.picker.font_size();
+    let cols = MINIMAP_COLS.min(area.width / 3);
+    // Cells are taller than wide, so the page's shape needs fewer rows
+    let aspect = h / w * f32::from(fw) / f32::from(fh.max(1));
+    let inner_rows = (f32::from(cols.saturating_sub(2)) * aspect).round() as u16;
+    let rows = (inner_rows + 2).min(area.height / 2);
+    if cols < 4 || rows < 3 {
+        return;
+    }
+    let map = Rect::new(
+        area.right() - cols - 1,
+        area.bottom() - rows - 1,
+        cols,
+        rows,
+    );
+    frame.render_widget(Clear, map);
+    let outline = Block::bordered().border_style(styled(app, Style::default().fg(Color::DarkGray)));
+    let inner = outline.inner(map);
+    frame.render_widget(outline, map);
+
+    let span = |start: f32, len: f32, cells: u16| {
+        let cells_f = f32::from(cells);
+        let first = ((start * cells_f).floor() as u16).min(cells - 1);
+        let end = (((start + len) * cells_f).ceil() as u16).clamp(first + 1, cells);
+        (first, end - first)
+    };
+    let (x, width) = span(view.x, view.w, inner.width);
+    let (y, height) = span(view.y, view.h, inner.height);
+    let window = Rect::new(inner.x + x, inner.y + y, width, height);
+    let lines = vec![Line::raw("█".repeat(width.into())); height.into()];
+    frame.render_widget(
+        Paragraph::new(lines).style(styled(app, Style::default().fg(Color::Yellow))),
+        window,
+    );
+}
+
+/// A single centered line of text in the middle of `area`.
+fn draw_notice(frame: &mut Frame, area: Rect, msg: &str, style: Style) {
+    let notice = Paragraph::new(msg)
+        .style(style)
+        .alignment(Alignment::Center);
+    let y = area.y + area.height / 2;
+    frame.render_widget(notice, Rect::new(area.x, y, area.width, 1));
+}
+
+/// Draw a page into `area`, darkened when `dim` is set.
+#[allow(clippy::too_many_arguments)]
+fn render_page(
+    frame: &mut Frame,
+    area: Rect,
+    app: &mut App,
+    page_idx: usize,
+    halign: HAlign,
+    clip: Option<Region>,
+    dim: bool,
+) {
     if page_idx >= app.page_count {
         return;
     }
 
-    let render_area = if let Some((w, h)) = app.cache.image_dims(page_idx) {
+    let clip = clip.or_else(|| {
+        app.cache
+            .image_dims(page_idx)
+            .and_then(|dims| app.fit_clip(dims, area))
+    });
+    let render_area = if clip.is_some() {
+        area
+    } else if let Some((w, h)) = app.cache.image_dims(page_idx) {
         aligned_image_area(w, h, area, app.picker.font_size(), app.zoom, halign)
     } else {
         area
@@ -80,19 +498,380 @@ fn render_page(frame: &mut Frame, area: Rect, app: &mut App, page_idx: usize, ha
 
     if let Some(protocol) = app.cache.get_protocol(
         page_idx,
-        app.dark_mode,
+        app.color_mode,
         app.zoom,
         (app.pan_x, app.pan_y),
+        clip,
+        dim,
         &app.picker,
         render_area,
     ) {
         let widget = RatatuiImage::new(protocol);
         frame.render_widget(widget, render_area);
-    } else {
-        let text = format!("Loading page {}...", page_idx + 1);
-        let loading = Paragraph::new(text).alignment(Alignment::Center);
+        let window = clip.unwrap_or_else(|| pan_window(app.zoom, (app.pan_x, app.pan_y)));
+        let content = app.cache.content_box(page_idx).unwrap_or(Region::FULL);
+        app.page_areas.push(PageArea {
+            page: page_idx,
+            area: render_area,
+            content,
+            window,
+        });
+        collect_link_hits(app, page_idx, content, window, render_area);
+        if app.show_fields {
+            draw_fields(frame, app, page_idx, content, window, render_area);
+        }
+        if app.show_annotations {
+            draw_notes(frame, app, page_idx, content, window, render_area);
+        }
+    } else if let Some(e) = app.failed_pages.get(&page_idx) {
+        let text = format!(
+            "Failed to render page {}: {e} (Ctrl-r to retry)",
+            page_idx + 1
+        );
+        let failed = Paragraph::new(text).alignment(Alignment::Center);
         let y = area.y + area.height / 2;
-        frame.render_widget(loading, Rect::new(area.x, y, area.width, 1));
+        frame.render_widget(failed, Rect::new(area.x, y, area.width, 1));
+    } else {
+        draw_loading(frame, area, app, page_idx, halign);
+    }
+}
+
+/// Stand in for a page that is still rendering: a faint outline the size
+/// the page will take up, so nothing jumps when it arrives, with a spinner.
+fn draw_loading(frame: &mut Frame, area: Rect, app: &mut App, page_idx: usize, halign: HAlign) {
+    let (w, h) = app.page_size(page_idx);
+    let placeholder = aligned_image_area(
+        w.round() as u32,
+        h.round() as u32,
+        area,
+        app.picker.font_size(),
+        app.zoom,
+        halign,
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(block, placeholder);
+
+    let spinner = if app.present {
+        '·'
+    } else {
+        SPINNER[app.spinner_frame % SPINNER.len()]
+    };
+    let loading = Paragraph::new(format!("{spinner} Loading page {}", page_idx + 1))
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    let y = placeholder.y + placeholder.height / 2;
+    frame.render_widget(loading, Rect::new(placeholder.x, y, placeholder.width, 1));
+}
+
+/// Map a region of the unrotated page through the page `rotation`, the
+/// displayed `content` of the page and the visible `window` of that onto
+/// the screen cells of `area`. `None` when scrolled, zoomed or cropped out
+/// of view.
+fn region_to_cells(
+    region: Region,
+    rotation: u16,
+    content: Region,
+    window: Region,
+    area: Rect,
+) -> Option<Rect> {
+    let (w, h) = (f32::from(area.width), f32::from(area.height));
+    let r = region.rotated(rotation).within(content).within(window);
+    let (x0, y0) = (r.x.max(0.0), r.y.max(0.0));
+    let (x1, y1) = ((r.x + r.w).min(1.0), (r.y + r.h).min(1.0));
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+
+    let left = (x0 * w).floor() as u16;
+    let top = (y0 * h).floor() as u16;
+    let right = ((x1 * w).ceil() as u16).max(left + 1);
+    let bottom = ((y1 * h).ceil() as u16).max(top + 1);
+    Some(Rect::new(
+        area.x + left,
+        area.y + top,
+        right - left,
+        bottom - top,
+    ))
+}
+
+/// Record the screen areas of a page's visible links.
+fn collect_link_hits(app: &mut App, page_idx: usize, content: Region, window: Region, area: Rect) {
+    let rotation = app.rotation;
+    let hits: Vec<_> = app
+        .ensure_page_links(page_idx)
+        .iter()
+        .filter_map(|link| {
+            let rect = region_to_cells(link.region, rotation, content, window, area)?;
+            Some((rect, link.target.clone()))
+        })
+        .collect();
+    app.link_hits.extend(hits);
+}
+
+/// Write form field values over their widgets, vertically centered in the
+/// field and cut off at its right edge. Tall fields also get their name.
+fn draw_fields(
+    frame: &mut Frame,
+    app: &mut App,
+    page_idx: usize,
+    content: Region,
+    window: Region,
+    area: Rect,
+) {
+    let rotation = app.rotation;
+    let style = Style::default()
+        .fg(Color::Rgb(0, 0, 139))
+        .bg(Color::Rgb(255, 250, 205));
+    for field in app.ensure_page_fields(page_idx) {
+        let Some(rect) = region_to_cells(field.region, rotation, content, window, area) else {
+            continue;
+        };
+        let line = Rect::new(rect.x, rect.y + rect.height / 2, rect.width, 1);
+        frame.render_widget(Paragraph::new(field.value.as_str()).style(style), line);
+        // Tall fields have room to say which field this is
+        if rect.height >= 3 && !field.name.is_empty() {
+            let label = Rect::new(rect.x, rect.y, rect.width, 1);
+            let label_style = style.add_modifier(Modifier::DIM);
+            frame.render_widget(
+                Paragraph::new(field.name.as_str()).style(label_style),
+                label,
+            );
+        }
+    }
+}
+
+/// The text of notes and comments in a small box just below each one, or
+/// above it at the bottom of the page.
+fn draw_notes(
+    frame: &mut Frame,
+    app: &mut App,
+    page_idx: usize,
+    content: Region,
+    window: Region,
+    area: Rect,
+) {
+    let rotation = app.rotation;
+    let style = Style::default()
+        .fg(Color::Rgb(0, 0, 0))
+        .bg(Color::Rgb(255, 236, 139));
+    for note in app.ensure_page_notes(page_idx) {
+        let Some(rect) = region_to_cells(note.region, rotation, content, window, area) else {
+            continue;
+        };
+        let text = if note.author.is_empty() {
+            note.contents.clone()
+        } else {
+            format!("{}: {}", note.author, note.contents)
+        };
+        let width = (area.right() - rect.x).min(NOTE_WIDTH);
+        let lines = text.chars().count().div_ceil(usize::from(width.max(1)));
+        let height = (lines as u16).clamp(1, NOTE_LINES);
+        let y = if rect.bottom() + height <= area.bottom() {
+            rect.bottom()
+        } else {
+            rect.y.saturating_sub(height).max(area.y)
+        };
+        let note_area = Rect::new(rect.x, y, width, height).intersection(area);
+        frame.render_widget(
+            Paragraph::new(text).style(style).wrap(Wrap { trim: true }),
+            note_area,
+        );
+    }
+}
+
+/// Outline the selected link. The border sits one cell outside the link so
+/// the linked text itself stays readable.
+fn draw_link_highlight(frame: &mut Frame, area: Rect, app: &mut App) {
+    let Some(i) = app.selected_link else {
+        return;
+    };
+    let Some(&(rect, _)) = app.link_hits.get(i) else {
+        app.selected_link = None;
+        return;
+    };
+    draw_outline(frame, area, rect, Color::Yellow);
+}
+
+/// Outline the text selection the same way.
+fn draw_selection(frame: &mut Frame, area: Rect, app: &App) {
+    if let Some(sel) = &app.selection {
+        draw_outline(frame, area, sel.rect, Color::Cyan);
+    }
+}
+
+/// A border one cell outside `rect`, kept within `area`.
+fn draw_outline(frame: &mut Frame, area: Rect, rect: Rect, color: Color) {
+    let x = rect.x.saturating_sub(1).max(area.x);
+    let y = rect.y.saturating_sub(1).max(area.y);
+    let right = (rect.right() + 1).min(area.right());
+    let bottom = (rect.bottom() + 1).min(area.bottom());
+    let outline = Rect::new(x, y, right - x, bottom - y);
+    frame.render_widget(
+        Block::bordered().border_style(Style::default().fg(color)),
+        outline,
+    );
+}
+
+fn draw_overlays(frame: &mut Frame, area: Rect, app: &App) {
+    draw_debug_panel(frame, area, app);
+    draw_bookmark_list(frame, area, app);
+    draw_info_overlay(frame, area, app);
+}
+
+/// Live render and cache statistics in the top-right corner.
+fn draw_debug_panel(frame: &mut Frame, area: Rect, app: &App) {
+    if !app.show_debug {
+        return;
+    }
+    let (used, budget) = app.cache.memory();
+    let scale = app
+        .cache
+        .image_scale(app.current_page)
+        .map_or_else(|| "—".into(), |s| format!("{s:.2}"));
+    let rows = [
+        ("Images", app.cache.cached_count().to_string()),
+        ("Protocols", app.cache.protocol_count().to_string()),
+        ("Filtered", app.cache.filtered_count().to_string()),
+        ("Pending", app.pending_count().to_string()),
+        ("Scale", scale),
+        (
+            "Memory",
+            format!(
+                "{} / {}",
+                format_size(used as u64),
+                format_size(budget as u64)
+            ),
+        ),
+        ("Frames", app.frames_drawn.to_string()),
+    ];
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let lines: Vec<Line> = rows
+        .iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!(" {label:<10}"), bold),
+                Span::raw(format!("{value} ")),
+            ])
+        })
+        .collect();
+    let width = (lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let rect = Rect::new(area.right() - width, area.y, width, height);
+    frame.render_widget(Clear, rect);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Debug ")),
+        rect,
+    );
+}
+
+/// Centered rectangle of at most `width` x `height` cells.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let (width, height) = (width.min(area.width), height.min(area.height));
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Centered list of bookmarks with the selected one highlighted.
+fn draw_bookmark_list(frame: &mut Frame, area: Rect, app: &App) {
+    let Some(selected) = app.bookmark_list else {
+        return;
+    };
+
+    let lines: Vec<Line> = if app.bookmarks.is_empty() {
+        vec![Line::raw(" No bookmarks yet, press b to add one ")]
+    } else {
+        app.bookmarks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let style = if i == selected {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::styled(format!(" {}  page {:<6}", b.label, b.page + 1), style)
+            })
+            .collect()
+    };
+
+    // Keep the selection in view when the list is taller than the screen
+    let visible = usize::from(area.height.saturating_sub(2));
+    let skip = (selected + 1).saturating_sub(visible);
+    let content_w = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+    let rect = centered(area, (content_w + 2).max(30), lines.len() as u16 + 2);
+
+    let block = Block::bordered()
+        .title(" Bookmarks ")
+        .title_bottom(Line::from(" Enter:go  d:delete  Esc:close ").alignment(Alignment::Right));
+    frame.render_widget(Clear, rect);
+    frame.render_widget(
+        Paragraph::new(lines).block(block).scroll((skip as u16, 0)),
+        rect,
+    );
+}
+
+/// Centered box listing the document's metadata.
+fn draw_info_overlay(frame: &mut Frame, area: Rect, app: &App) {
+    let Some(info) = &app.info else {
+        return;
+    };
+
+    let field = |v: &Option<String>| v.clone().unwrap_or_else(|| "—".into());
+    let rows = [
+        ("Title", field(&info.title)),
+        ("Author", field(&info.author)),
+        ("Subject", field(&info.subject)),
+        ("Creator", field(&info.creator)),
+        ("Created", field(&info.created)),
+        ("Pages", app.page_count.to_string()),
+        (
+            "File size",
+            app.file_size.map_or_else(|| "—".into(), format_size),
+        ),
+        ("Version", field(&info.version)),
+    ];
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let lines: Vec<Line> = rows
+        .iter()
+        .map(|(label, value)| {
+            Line::from(vec![
+                Span::styled(format!(" {label:<10}"), bold),
+                Span::raw(format!("{value} ")),
+            ])
+        })
+        .collect();
+
+    let content_w = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
+    let rect = centered(area, (content_w + 2).max(30), lines.len() as u16 + 2);
+
+    let block = Block::bordered()
+        .title(" Document info ")
+        .title_bottom(Line::from(" any key to close ").alignment(Alignment::Right));
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+/// Human-readable byte count, e.g. `2.4 MB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
     }
 }
 
@@ -100,6 +879,7 @@ fn render_page(frame: &mut Frame, area: Rect, app: &mut App, page_idx: usize, ha
 ///
 /// Uses the Picker's `font_size` and `ceil()` to match ratatui-image's internal
 /// `round_pixel_size_to_cells`, so our area exactly matches the protocol footprint.
+#[must_use]
 pub fn aligned_image_area(
     img_w: u32,
     img_h: u32,
@@ -136,55 +916,288 @@ pub fn aligned_image_area(
     Rect::new(area.x + x_off, area.y + y_off, final_w, final_h)
 }
 
-fn draw_status_bar(frame: &mut Frame, area: Rect, app: &App) {
-    let bold = Style::default().add_modifier(Modifier::BOLD);
+/// Draw the status bar, returning where the page indicator landed.
+fn draw_status_bar(frame: &mut Frame, area: Rect, app: &App) -> Option<Rect> {
+    if app.password_mode {
+        let prompt = app.status_message.as_deref().map_or_else(
+            || " | password: ".to_string(),
+            |msg| format!(" | {msg} | password: "),
+        );
+        draw_bar(frame, area, app, &prompt, "Enter:unlock  Esc:quit ");
+        return None;
+    }
+
+    if app.thumbnails {
+        let info = format!(
+            " | {}/{} | overview",
+            app.thumbnail_selected + 1,
+            app.page_count
+        );
+        draw_bar(
+            frame,
+            area,
+            app,
+            &info,
+            "arrows:move  Enter:open  Esc:back ",
+        );
+        return None;
+    }
+
+    if app.bookmark_label_mode {
+        draw_bar(
+            frame,
+            area,
+            app,
+            " | bookmark label: ",
+            "a-z/0-9:label  Esc:cancel ",
+        );
+        return None;
+    }
+
+    if app.command_mode {
+        let prompt = format!(" | :{}", app.prompt_input);
+        draw_bar(
+            frame,
+            area,
+            app,
+            &prompt,
+            "Tab:complete  Enter:run  Esc:cancel ",
+        );
+        return None;
+    }
 
     if app.goto_mode {
-        let left_parts = vec![
-            Span::styled(" tpdf", bold),
-            Span::raw(format!(" | goto: {}", app.goto_input)),
-        ];
-        let right = "Enter:go  Esc:cancel ";
-        let left_len = 5 + 10 + app.goto_input.len();
-        let gap = (area.width as usize).saturating_sub(left_len + right.len());
-
-        let mut spans = left_parts;
-        spans.push(Span::raw(" ".repeat(gap)));
-        spans.push(Span::raw(right));
-        frame.render_widget(Paragraph::new(Line::from(spans)), area);
-        return;
+        let prompt = format!(" | goto: {}", app.prompt_input);
+        draw_bar(frame, area, app, &prompt, "Enter:go  Esc:cancel ");
+        return None;
+    }
+
+    let mut info_parts = Vec::new();
+    let mut indicator = None;
+    if app.status_segments.page {
+        let pages = page_indicator(app);
+        // Sits right after the " tpdf | " prefix and any tabs
+        let tabs: usize = tab_strip(app).iter().map(Span::width).sum();
+        indicator = Some(
+            Rect::new(area.x + 8 + tabs as u16, area.y, pages.len() as u16, 1).intersection(area),
+        );
+        info_parts.push(pages);
     }
+    info_parts.extend(view_tags(app));
+    info_parts.extend(status_extras(app));
 
-    let start = app.current_page + 1;
-    let n = app.layout.pages_across();
-    let end = (app.current_page + n).min(app.page_count);
-    let pages = if end > start {
-        format!("{start}-{end}/{}", app.page_count)
+    let info = if info_parts.is_empty() {
+        String::new()
     } else {
-        format!("{start}/{}", app.page_count)
+        format!(" | {}", info_parts.join(" | "))
     };
+    // A count being typed replaces the key hints, like Vim's showcmd
+    let hints = if app.status_segments.keys {
+        "h/l:page  jk:pan  +/-:zoom  d:layout  f:full  p:goto  n:night  q:quit "
+    } else {
+        ""
+    };
+    // Echo a count or the first key of a motion while it's being typed
+    let mut typed = app
+        .pending_count
+        .map_or_else(String::new, |c| c.to_string());
+    if let Some((key, _)) = app.pending_motion {
+        typed.push(key);
+    }
+    let keys = if typed.is_empty() {
+        Span::raw(hints)
+    } else {
+        Span::styled(
+            format!("{typed} "),
+            styled(app, Style::default().add_modifier(Modifier::DIM)),
+        )
+    };
+    draw_bar(frame, area, app, &info, keys);
+    indicator
+}
 
-    let zoom_pct = format!("{}%", (app.zoom * 100.0).round() as u32);
-
-    let mut info_parts = vec![pages, zoom_pct];
-    match app.layout {
-        PageLayout::Dual => info_parts.push("2UP".into()),
-        PageLayout::Triple => info_parts.push("3UP".into()),
-        PageLayout::Single => {}
+/// The pages on screen out of the total, e.g. `3-4/120`, followed by the
+/// first one's printed label where that differs, e.g. `7/120 (v)`. In
+/// compare mode both panes' pages are listed, the focused one bracketed.
+fn page_indicator(app: &App) -> String {
+    let first = app.dominant_page();
+    let start = first + 1;
+    let label = app
+        .page_labels
+        .label(first)
+        .filter(|l| *l != start.to_string())
+        .map_or_else(String::new, |l| format!(" ({l})"));
+    if let Some(c) = &app.compare {
+        let (focused, other) = (format!("[{start}]"), c.other.page + 1);
+        return if c.focus_left {
+            format!("{focused} {other}/{}{label}", app.page_count)
+        } else {
+            format!("{other} {focused}/{}{label}", app.page_count)
+        };
     }
-    if app.dark_mode {
-        info_parts.push("NIGHT".into());
+    let n = if app.text_mode { 1 } else { app.spread_len() };
+    let end = (first + n).min(app.page_count);
+    if end > start {
+        format!("{start}-{end}/{}{label}", app.page_count)
+    } else {
+        format!("{start}/{}{label}", app.page_count)
     }
+}
 
-    let info = info_parts.join(" | ");
-    let keys = "h/l:page  jk:pan  +/-:zoom  d:layout  f:full  p:goto  n:night  q:quit ";
+/// Status bar tags describing how the page is shown: zoom or text mode,
+/// layout, fit, rotation and adjustments.
+fn view_tags(app: &App) -> Vec<String> {
+    let shown = app.status_segments;
+    let mut tags = Vec::new();
+    if app.text_mode {
+        if shown.mode {
+            tags.push(if app.text_reflow { "TEXT" } else { "TEXT-RAW" }.into());
+        }
+    } else {
+        if shown.zoom {
+            tags.push(format!("{}%", (app.zoom * 100.0).round() as u32));
+            if app.pinned_zoom.is_some() {
+                tags.push("PIN".into());
+            }
+        }
+        if shown.layout {
+            match app.layout {
+                _ if app.compare.is_some() => tags.push("CMP".into()),
+                PageLayout::Dual if app.cover_offset => tags.push("BOOK".into()),
+                PageLayout::Dual => tags.push("2UP".into()),
+                PageLayout::Triple => tags.push("3UP".into()),
+                PageLayout::Continuous => tags.push("CONT".into()),
+                PageLayout::Grid { cols, rows } => tags.push(format!("{cols}x{rows}")),
+                PageLayout::Single => {}
+            }
+        }
+    }
+    if shown.zoom {
+        match app.fit {
+            FitMode::Width => tags.push("FIT-W".into()),
+            FitMode::Height => tags.push("FIT-H".into()),
+            FitMode::Page => {}
+        }
+    }
+    if !shown.mode {
+        return tags;
+    }
+    if app.lock_view {
+        tags.push("LOCK".into());
+    }
+    if app.auto_advance_at.is_some() {
+        tags.push(format!("AUTO {}s", app.advance_interval.as_secs()));
+    }
+    if app.rotation != 0 {
+        tags.push(format!("{}°", app.rotation));
+    }
+    if let Some(label) = app.color_mode.label() {
+        tags.push(label.into());
+    }
+    if app.autocrop {
+        tags.push("CROP".into());
+    }
+    if let Some(label) = app.lens.label() {
+        tags.push(label.into());
+    }
+    if app.quality != Quality::High {
+        tags.push(app.quality.name().to_uppercase());
+    }
+    if app.show_fields {
+        tags.push("FIELDS".into());
+    }
+    if app.dim_inactive {
+        tags.push("DIM".into());
+    }
+    if app.brightness != 0 {
+        tags.push(format!("B{:+}", app.brightness));
+    }
+    if app.contrast != 0.0 {
+        tags.push(format!("C{:+}", app.contrast.round() as i32));
+    }
+    tags
+}
 
-    let left_parts = vec![Span::styled(" tpdf", bold), Span::raw(format!(" | {info}"))];
-    let left_len = 5 + 3 + info.len();
-    let gap = (area.width as usize).saturating_sub(left_len + keys.len());
+/// The selected link, the extras turned on by the user and any flashed
+/// message, shown whatever the segments.
+fn status_extras(app: &App) -> Vec<String> {
+    let mut parts = Vec::new();
+    if let Some((_, target)) = app.selected_link.and_then(|i| app.link_hits.get(i)) {
+        parts.push(match target {
+            LinkTarget::Page(p) => format!("link: page {}", p + 1),
+            LinkTarget::Uri(uri) => format!("link: {uri}"),
+        });
+    }
+    if app.show_clock {
+        let left = app
+            .reading_time_left()
+            .map_or_else(|| "\u{2014}".into(), pace::format_remaining);
+        parts.push(format!(
+            "{} | {left} left",
+            chrono::Local::now().format("%H:%M")
+        ));
+    }
+    if app.show_cache_status {
+        parts.push(format!(
+            "cached {}/{}",
+            app.cache.cached_count(),
+            app.page_count
+        ));
+    }
+    if let Some(msg) = &app.status_message {
+        parts.push(msg.clone());
+    }
+    parts
+}
 
-    let mut spans = left_parts;
-    spans.push(Span::raw(" ".repeat(gap)));
-    spans.push(Span::raw(keys));
+/// The bold app name and any tabs, followed by `left`, with `right` flush
+/// right.
+fn draw_bar(frame: &mut Frame, area: Rect, app: &App, left: &str, right: impl Into<Span<'static>>) {
+    let right = right.into();
+    let bold = styled(app, Style::default().add_modifier(Modifier::BOLD));
+    let mut spans = vec![Span::styled(" tpdf", bold)];
+    spans.extend(tab_strip(app));
+    let used: usize = spans.iter().map(Span::width).sum();
+    let gap = (area.width as usize).saturating_sub(used + left.len() + right.width());
+    spans.extend([
+        Span::raw(left.to_string()),
+        Span::raw(" ".repeat(gap)),
+        right,
+    ]);
     frame.render_widget(Paragraph::new(Line::from(spans)), area);
 }
+
+/// The open documents' names with the active one bold, or nothing when only
+/// one is open.
+fn tab_strip(app: &App) -> Vec<Span<'static>> {
+    const MAX_NAME: usize = 24;
+    if app.tab_names.len() < 2 {
+        return Vec::new();
+    }
+    let mut spans = vec![Span::raw(" |")];
+    for (i, name) in app.tab_names.iter().enumerate() {
+        let name = if name.chars().count() > MAX_NAME {
+            let short: String = name.chars().take(MAX_NAME - 1).collect();
+            format!("{short}…")
+        } else {
+            name.clone()
+        };
+        spans.push(Span::raw(" "));
+        spans.push(match (i == app.tab_index, app.no_color) {
+            // Without styling the active tab is bracketed instead
+            (true, true) => Span::raw(format!("[{name}]")),
+            (true, false) => Span::styled(name, Style::default().add_modifier(Modifier::BOLD)),
+            (false, _) => Span::styled(name, styled(app, Style::default().fg(Color::DarkGray))),
+        });
+    }
+    spans
+}
+
+/// `style`, unless styling is off for `NO_COLOR`.
+fn styled(app: &App, style: Style) -> Style {
+    if app.no_color {
+        Style::default()
+    } else {
+        style
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long the file has to stay quiet before it is reloaded, so a document
+/// that is still being written isn't opened half-way through.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the open document for changes on disk.
+pub struct FileWatcher {
+    // Watching stops when this is dropped
+    _watcher: RecommendedWatcher,
+    events: Receiver<()>,
+    changed_at: Option<Instant>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &str) -> notify::Result<Self> {
+        let path = Path::new(path).canonicalize()?;
+        let name = path.file_name().map(ToOwned::to_owned);
+        let (tx, events) = mpsc::channel();
+
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                let Ok(event) = res else {
+                    return;
+                };
+                let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.iter().any(|p| p.file_name() == name.as_deref());
+                if relevant {
+                    let _ = tx.send(());
+                }
+            })?;
+        // Watch the directory rather than the file: many tools save by
+        // writing a new file and renaming it over the old one.
+        watcher.watch(path.parent().unwrap_or(&path), RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            changed_at: None,
+        })
+    }
+
+    /// Whether the file changed and has since settled, ready to reload.
+    pub fn poll(&mut self) -> bool {
+        while self.events.try_recv().is_ok() {
+            self.changed_at = Some(Instant::now());
+        }
+        if self.changed_at.is_some_and(|t| t.elapsed() >= DEBOUNCE) {
+            self.changed_at = None;
+            return true;
+        }
+        false
+    }
+
+    /// When the main loop should next call [`poll`](Self::poll).
+    pub fn next_check(&self) -> Instant {
+        self.changed_at.unwrap_or_else(Instant::now) + DEBOUNCE
+    }
+}
@@ -0,0 +1,51 @@
+//! Pages with a `/Rotate` entry: `tests/fixtures/rotated.pdf` has two
+//! 200x300 pt pages with a black square in the bottom-left corner, the
+//! second turned by `/Rotate 90`.
+
+use image::DynamicImage;
+use tpdf::document::DocumentSource;
+use tpdf::pdf::PdfDocument;
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rotated.pdf");
+
+fn open() -> PdfDocument {
+    PdfDocument::open(FIXTURE, None).unwrap_or_else(|_| panic!("couldn't open {FIXTURE}"))
+}
+
+/// Whether the pixel at `x`, `y` is part of the black square.
+fn is_dark(img: &DynamicImage, x: u32, y: u32) -> bool {
+    img.to_luma8().get_pixel(x, y).0[0] < 64
+}
+
+#[test]
+fn bounds_follow_the_page_rotation() {
+    let doc = open();
+    assert_eq!(doc.page_bounds(0).unwrap(), (200.0, 300.0));
+    assert_eq!(doc.page_bounds(1).unwrap(), (300.0, 200.0));
+}
+
+#[test]
+fn renders_upright_pages_as_they_are() {
+    let img = open().render_page(0, 1.0, 0, true).unwrap();
+    assert_eq!((img.width(), img.height()), (200, 300));
+    assert!(is_dark(&img, 10, 290));
+    assert!(!is_dark(&img, 10, 10));
+}
+
+#[test]
+fn renders_rotated_pages_turned() {
+    // Turned clockwise, the bottom-left corner ends up top-left
+    let img = open().render_page(1, 1.0, 0, true).unwrap();
+    assert_eq!((img.width(), img.height()), (300, 200));
+    assert!(is_dark(&img, 10, 10));
+    assert!(!is_dark(&img, 10, 190));
+}
+
+#[test]
+fn view_rotation_adds_to_the_page_rotation() {
+    // 90 from the page and 90 from the view turn the corner top-right
+    let img = open().render_page(1, 1.0, 90, true).unwrap();
+    assert_eq!((img.width(), img.height()), (200, 300));
+    assert!(is_dark(&img, 190, 10));
+    assert!(!is_dark(&img, 10, 290));
+}